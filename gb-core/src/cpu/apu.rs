@@ -0,0 +1,681 @@
+use crate::cpu::savestate::{SaveState, StateReader, StateWriter};
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 48_000;
+
+const CPU_CLOCK: u64 = 4_194_304;
+// The frame sequencer runs at 512Hz and clocks length, sweep and envelope
+const FRAME_SEQUENCER_DOTS: u32 = 8192;
+// Samples not taken by the frontend are dropped after about a second
+const MAX_BUFFERED_SAMPLES: usize = DEFAULT_SAMPLE_RATE as usize * 2;
+
+const DUTY_PATTERNS: [[u8; 8]; 4] = [
+    [0, 0, 0, 0, 0, 0, 0, 1],
+    [1, 0, 0, 0, 0, 0, 0, 1],
+    [1, 0, 0, 0, 0, 1, 1, 1],
+    [0, 1, 1, 1, 1, 1, 1, 0],
+];
+
+const NOISE_DIVISORS: [u32; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+
+// Bits that always read as 1 for every register from NR10 (0xFF10) to 0xFF2F
+const READ_MASKS: [u8; 0x20] = [
+    0x80, 0x3F, 0x00, 0xFF, 0xBF,
+    0xFF, 0x3F, 0x00, 0xFF, 0xBF,
+    0x7F, 0xFF, 0x9F, 0xFF, 0xBF,
+    0xFF, 0xFF, 0x00, 0x00, 0xBF,
+    0x00, 0x00, 0x70,
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+];
+
+struct Envelope {
+    register: u8,
+    volume: u8,
+    timer: u8,
+}
+
+impl Envelope {
+    fn new() -> Self {
+	Envelope { register: 0, volume: 0, timer: 0 }
+    }
+
+    // The DAC is powered as long as the initial volume or the direction are set
+    fn dac_enabled(&self) -> bool {
+	(self.register & 0xF8) != 0
+    }
+
+    fn trigger(&mut self) {
+	self.volume = self.register >> 4;
+	self.timer = self.register & 0x7;
+    }
+
+    fn clock(&mut self) {
+	let period = self.register & 0x7;
+	if period == 0 {
+	    return;
+	}
+
+	self.timer = self.timer.saturating_sub(1);
+	if self.timer == 0 {
+	    self.timer = period;
+
+	    if (self.register & 0x08) == 0x08 && self.volume < 15 {
+		self.volume += 1;
+	    } else if (self.register & 0x08) == 0 && self.volume > 0 {
+		self.volume -= 1;
+	    }
+	}
+    }
+}
+
+struct Length {
+    counter: u16,
+    enabled: bool,
+}
+
+impl Length {
+    // Returns true when the counter runs out and the channel must be disabled
+    fn clock(&mut self) -> bool {
+	if self.enabled && self.counter > 0 {
+	    self.counter -= 1;
+	    return self.counter == 0;
+	}
+	false
+    }
+}
+
+struct Square {
+    enabled: bool,
+    duty: u8,
+    duty_step: usize,
+    frequency: u16,
+    timer: u32,
+    length: Length,
+    envelope: Envelope,
+
+    // Only channel 1 has a sweep unit
+    sweep: u8,
+    sweep_timer: u8,
+    sweep_enabled: bool,
+    shadow_frequency: u16,
+}
+
+impl Square {
+    fn new() -> Self {
+	Square {
+	    enabled: false,
+	    duty: 0,
+	    duty_step: 0,
+	    frequency: 0,
+	    timer: 0,
+	    length: Length { counter: 0, enabled: false },
+	    envelope: Envelope::new(),
+	    sweep: 0,
+	    sweep_timer: 0,
+	    sweep_enabled: false,
+	    shadow_frequency: 0,
+	}
+    }
+
+    fn period(&self) -> u32 {
+	(2048 - self.frequency as u32) * 4
+    }
+
+    fn trigger(&mut self) {
+	self.enabled = self.envelope.dac_enabled();
+	if self.length.counter == 0 {
+	    self.length.counter = 64;
+	}
+	self.timer = self.period();
+	self.envelope.trigger();
+
+	let sweep_period = (self.sweep >> 4) & 0x7;
+	let shift = self.sweep & 0x7;
+	self.shadow_frequency = self.frequency;
+	self.sweep_timer = if sweep_period == 0 { 8 } else { sweep_period };
+	self.sweep_enabled = sweep_period != 0 || shift != 0;
+	if shift != 0 {
+	    self.next_sweep_frequency();
+	}
+    }
+
+    // Disables the channel when the new frequency overflows
+    fn next_sweep_frequency(&mut self) -> u16 {
+	let delta = self.shadow_frequency >> (self.sweep & 0x7);
+	let frequency = if (self.sweep & 0x08) == 0x08 {
+	    self.shadow_frequency.wrapping_sub(delta)
+	} else {
+	    self.shadow_frequency + delta
+	};
+
+	if frequency > 2047 {
+	    self.enabled = false;
+	}
+	frequency
+    }
+
+    fn clock_sweep(&mut self) {
+	self.sweep_timer = self.sweep_timer.saturating_sub(1);
+	if self.sweep_timer > 0 {
+	    return;
+	}
+
+	let sweep_period = (self.sweep >> 4) & 0x7;
+	self.sweep_timer = if sweep_period == 0 { 8 } else { sweep_period };
+
+	if self.sweep_enabled && sweep_period != 0 {
+	    let frequency = self.next_sweep_frequency();
+	    if frequency <= 2047 && (self.sweep & 0x7) != 0 {
+		self.frequency = frequency;
+		self.shadow_frequency = frequency;
+		self.next_sweep_frequency();
+	    }
+	}
+    }
+
+    fn tick(&mut self, cycles: u32) {
+	let mut cycles = cycles;
+	while cycles >= self.timer {
+	    cycles -= self.timer;
+	    self.timer = self.period();
+	    self.duty_step = (self.duty_step + 1) % 8;
+	}
+	self.timer -= cycles;
+    }
+
+    fn output(&self) -> Option<u8> {
+	if !self.envelope.dac_enabled() {
+	    return None;
+	}
+	if !self.enabled {
+	    return Some(0);
+	}
+	Some(DUTY_PATTERNS[self.duty as usize][self.duty_step] * self.envelope.volume)
+    }
+}
+
+struct Wave {
+    enabled: bool,
+    dac_enabled: bool,
+    volume_code: u8,
+    frequency: u16,
+    timer: u32,
+    position: usize,
+    length: Length,
+    ram: [u8; 16],
+}
+
+impl Wave {
+    fn new() -> Self {
+	Wave {
+	    enabled: false,
+	    dac_enabled: false,
+	    volume_code: 0,
+	    frequency: 0,
+	    timer: 0,
+	    position: 0,
+	    length: Length { counter: 0, enabled: false },
+	    ram: [0; 16],
+	}
+    }
+
+    fn period(&self) -> u32 {
+	(2048 - self.frequency as u32) * 2
+    }
+
+    fn trigger(&mut self) {
+	self.enabled = self.dac_enabled;
+	if self.length.counter == 0 {
+	    self.length.counter = 256;
+	}
+	self.timer = self.period();
+	self.position = 0;
+    }
+
+    fn tick(&mut self, cycles: u32) {
+	let mut cycles = cycles;
+	while cycles >= self.timer {
+	    cycles -= self.timer;
+	    self.timer = self.period();
+	    self.position = (self.position + 1) % 32;
+	}
+	self.timer -= cycles;
+    }
+
+    fn output(&self) -> Option<u8> {
+	if !self.dac_enabled {
+	    return None;
+	}
+	if !self.enabled || self.volume_code == 0 {
+	    return Some(0);
+	}
+
+	let byte = self.ram[self.position / 2];
+	let sample = if self.position & 0b1 == 0 { byte >> 4 } else { byte & 0xF };
+	Some(sample >> (self.volume_code - 1))
+    }
+}
+
+struct Noise {
+    enabled: bool,
+    polynomial: u8,
+    timer: u32,
+    lfsr: u16,
+    length: Length,
+    envelope: Envelope,
+}
+
+impl Noise {
+    fn new() -> Self {
+	Noise {
+	    enabled: false,
+	    polynomial: 0,
+	    timer: 0,
+	    lfsr: 0x7FFF,
+	    length: Length { counter: 0, enabled: false },
+	    envelope: Envelope::new(),
+	}
+    }
+
+    fn period(&self) -> u32 {
+	NOISE_DIVISORS[(self.polynomial & 0x7) as usize] << (self.polynomial >> 4)
+    }
+
+    fn trigger(&mut self) {
+	self.enabled = self.envelope.dac_enabled();
+	if self.length.counter == 0 {
+	    self.length.counter = 64;
+	}
+	self.timer = self.period();
+	self.lfsr = 0x7FFF;
+	self.envelope.trigger();
+    }
+
+    fn tick(&mut self, cycles: u32) {
+	let mut cycles = cycles;
+	while cycles >= self.timer {
+	    cycles -= self.timer;
+	    self.timer = self.period();
+
+	    let feedback = (self.lfsr & 0b1) ^ ((self.lfsr >> 1) & 0b1);
+	    self.lfsr = (self.lfsr >> 1) | (feedback << 14);
+	    // 7bit mode also feeds bit 6, making a much shorter sequence
+	    if (self.polynomial & 0x08) == 0x08 {
+		self.lfsr = (self.lfsr & !(1 << 6)) | (feedback << 6);
+	    }
+	}
+	self.timer -= cycles;
+    }
+
+    fn output(&self) -> Option<u8> {
+	if !self.envelope.dac_enabled() {
+	    return None;
+	}
+	if !self.enabled {
+	    return Some(0);
+	}
+	Some(((!self.lfsr & 0b1) as u8) * self.envelope.volume)
+    }
+}
+
+// Audio processing unit, 4 channels mixed into interleaved stereo samples
+pub struct Apu {
+    registers: [u8; 0x20],
+    powered: bool,
+    square1: Square,
+    square2: Square,
+    wave: Wave,
+    noise: Noise,
+
+    frame_sequencer_dots: u32,
+    frame_sequencer_step: u8,
+
+    sample_rate: u32,
+    sample_dots: u64,
+    samples: Vec<f32>,
+}
+
+impl Apu {
+    pub fn new() -> Self {
+	Apu {
+	    registers: [0; 0x20],
+	    powered: false,
+	    square1: Square::new(),
+	    square2: Square::new(),
+	    wave: Wave::new(),
+	    noise: Noise::new(),
+	    frame_sequencer_dots: 0,
+	    frame_sequencer_step: 0,
+	    sample_rate: DEFAULT_SAMPLE_RATE,
+	    sample_dots: 0,
+	    samples: Vec::new(),
+	}
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+	self.sample_rate = sample_rate.max(1);
+    }
+
+    // Left and right samples interleaved, from -1.0 to 1.0
+    pub fn take_samples(&mut self) -> Vec<f32> {
+	std::mem::take(&mut self.samples)
+    }
+
+    pub fn read_register(&self, address: u16) -> u8 {
+	match address {
+	    0xFF26 => {
+		let channels = [self.square1.enabled, self.square2.enabled, self.wave.enabled, self.noise.enabled];
+		let status = channels
+		    .iter()
+		    .enumerate()
+		    .fold(0, |status, (channel, enabled)| status | (*enabled as u8) << channel);
+
+		0x70 | (self.powered as u8) << 7 | status
+	    }
+	    0xFF10..=0xFF2F => self.registers[(address - 0xFF10) as usize] | READ_MASKS[(address - 0xFF10) as usize],
+	    0xFF30..=0xFF3F => self.wave.ram[(address - 0xFF30) as usize],
+	    _ => 0xFF,
+	}
+    }
+
+    pub fn write_register(&mut self, address: u16, value: u8) {
+	match address {
+	    0xFF30..=0xFF3F => self.wave.ram[(address - 0xFF30) as usize] = value,
+	    0xFF26 => {
+		let powered = (value & 0x80) == 0x80;
+		if self.powered && !powered {
+		    self.power_off();
+		} else if !self.powered && powered {
+		    self.frame_sequencer_step = 0;
+		}
+		self.powered = powered;
+	    }
+	    // Everything but NR52 and wave RAM is read only while the APU is off
+	    0xFF10..=0xFF25 if self.powered => {
+		self.registers[(address - 0xFF10) as usize] = value;
+		self.write_channel(address, value);
+	    }
+	    _ => {}
+	}
+    }
+
+    fn write_channel(&mut self, address: u16, value: u8) {
+	match address {
+	    0xFF10 => self.square1.sweep = value & 0x7F,
+	    0xFF11 => {
+		self.square1.duty = value >> 6;
+		self.square1.length.counter = 64 - (value & 0x3F) as u16;
+	    }
+	    0xFF12 => {
+		self.square1.envelope.register = value;
+		self.square1.enabled &= self.square1.envelope.dac_enabled();
+	    }
+	    0xFF13 => self.square1.frequency = (self.square1.frequency & 0x700) | value as u16,
+	    0xFF14 => {
+		self.square1.frequency = (self.square1.frequency & 0xFF) | ((value & 0x7) as u16) << 8;
+		self.square1.length.enabled = (value & 0x40) == 0x40;
+		if (value & 0x80) == 0x80 {
+		    self.square1.trigger();
+		}
+	    }
+	    0xFF16 => {
+		self.square2.duty = value >> 6;
+		self.square2.length.counter = 64 - (value & 0x3F) as u16;
+	    }
+	    0xFF17 => {
+		self.square2.envelope.register = value;
+		self.square2.enabled &= self.square2.envelope.dac_enabled();
+	    }
+	    0xFF18 => self.square2.frequency = (self.square2.frequency & 0x700) | value as u16,
+	    0xFF19 => {
+		self.square2.frequency = (self.square2.frequency & 0xFF) | ((value & 0x7) as u16) << 8;
+		self.square2.length.enabled = (value & 0x40) == 0x40;
+		if (value & 0x80) == 0x80 {
+		    self.square2.trigger();
+		}
+	    }
+	    0xFF1A => {
+		self.wave.dac_enabled = (value & 0x80) == 0x80;
+		self.wave.enabled &= self.wave.dac_enabled;
+	    }
+	    0xFF1B => self.wave.length.counter = 256 - value as u16,
+	    0xFF1C => self.wave.volume_code = (value >> 5) & 0b11,
+	    0xFF1D => self.wave.frequency = (self.wave.frequency & 0x700) | value as u16,
+	    0xFF1E => {
+		self.wave.frequency = (self.wave.frequency & 0xFF) | ((value & 0x7) as u16) << 8;
+		self.wave.length.enabled = (value & 0x40) == 0x40;
+		if (value & 0x80) == 0x80 {
+		    self.wave.trigger();
+		}
+	    }
+	    0xFF20 => self.noise.length.counter = 64 - (value & 0x3F) as u16,
+	    0xFF21 => {
+		self.noise.envelope.register = value;
+		self.noise.enabled &= self.noise.envelope.dac_enabled();
+	    }
+	    0xFF22 => self.noise.polynomial = value,
+	    0xFF23 => {
+		self.noise.length.enabled = (value & 0x40) == 0x40;
+		if (value & 0x80) == 0x80 {
+		    self.noise.trigger();
+		}
+	    }
+	    _ => {}
+	}
+    }
+
+    // Turning the APU off clears every register, wave RAM is kept
+    fn power_off(&mut self) {
+	let wave_ram = self.wave.ram;
+
+	self.registers = [0; 0x20];
+	self.square1 = Square::new();
+	self.square2 = Square::new();
+	self.wave = Wave::new();
+	self.wave.ram = wave_ram;
+	self.noise = Noise::new();
+    }
+
+    pub fn tick(&mut self, cycles: u32) {
+	if self.powered {
+	    self.square1.tick(cycles);
+	    self.square2.tick(cycles);
+	    self.wave.tick(cycles);
+	    self.noise.tick(cycles);
+
+	    self.frame_sequencer_dots += cycles;
+	    if self.frame_sequencer_dots >= FRAME_SEQUENCER_DOTS {
+		self.frame_sequencer_dots -= FRAME_SEQUENCER_DOTS;
+		self.clock_frame_sequencer();
+	    }
+	}
+
+	self.sample_dots += cycles as u64 * self.sample_rate as u64;
+	while self.sample_dots >= CPU_CLOCK {
+	    self.sample_dots -= CPU_CLOCK;
+	    self.push_sample();
+	}
+    }
+
+    fn clock_frame_sequencer(&mut self) {
+	if self.frame_sequencer_step & 0b1 == 0 {
+	    self.square1.enabled &= !self.square1.length.clock();
+	    self.square2.enabled &= !self.square2.length.clock();
+	    self.wave.enabled &= !self.wave.length.clock();
+	    self.noise.enabled &= !self.noise.length.clock();
+	}
+	if self.frame_sequencer_step == 2 || self.frame_sequencer_step == 6 {
+	    self.square1.clock_sweep();
+	}
+	if self.frame_sequencer_step == 7 {
+	    self.square1.envelope.clock();
+	    self.square2.envelope.clock();
+	    self.noise.envelope.clock();
+	}
+
+	self.frame_sequencer_step = (self.frame_sequencer_step + 1) % 8;
+    }
+
+    fn push_sample(&mut self) {
+	let outputs = [self.square1.output(), self.square2.output(), self.wave.output(), self.noise.output()];
+	let panning = self.registers[0x15];
+	let volume = self.registers[0x14];
+
+	let mut left = 0.0;
+	let mut right = 0.0;
+	for (channel, output) in outputs.iter().enumerate() {
+	    // A powered DAC maps 0-15 to 1.0 down to -1.0, a disabled one outputs silence
+	    let sample = match output {
+		Some(output) => 1.0 - *output as f32 / 7.5,
+		None => 0.0,
+	    };
+
+	    if (panning >> (channel + 4)) & 0b1 == 1 {
+		left += sample;
+	    }
+	    if (panning >> channel) & 0b1 == 1 {
+		right += sample;
+	    }
+	}
+
+	left *= (((volume >> 4) & 0x7) + 1) as f32 / 32.0;
+	right *= ((volume & 0x7) + 1) as f32 / 32.0;
+
+	if self.samples.len() >= MAX_BUFFERED_SAMPLES {
+	    self.samples.drain(..MAX_BUFFERED_SAMPLES / 2);
+	}
+	self.samples.push(left);
+	self.samples.push(right);
+    }
+}
+
+impl SaveState for Envelope {
+    fn save_state(&self, writer: &mut StateWriter) {
+	writer.u8(self.register);
+	writer.u8(self.volume);
+	writer.u8(self.timer);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), &'static str> {
+	self.register = reader.u8()?;
+	self.volume = reader.u8()?;
+	self.timer = reader.u8()?;
+	Ok(())
+    }
+}
+
+impl SaveState for Length {
+    fn save_state(&self, writer: &mut StateWriter) {
+	writer.u16(self.counter);
+	writer.bool(self.enabled);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), &'static str> {
+	self.counter = reader.u16()?;
+	self.enabled = reader.bool()?;
+	Ok(())
+    }
+}
+
+impl SaveState for Square {
+    fn save_state(&self, writer: &mut StateWriter) {
+	writer.bool(self.enabled);
+	writer.u8(self.duty);
+	writer.u8(self.duty_step as u8);
+	writer.u16(self.frequency);
+	writer.u32(self.timer);
+	self.length.save_state(writer);
+	self.envelope.save_state(writer);
+	writer.u8(self.sweep);
+	writer.u8(self.sweep_timer);
+	writer.bool(self.sweep_enabled);
+	writer.u16(self.shadow_frequency);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), &'static str> {
+	self.enabled = reader.bool()?;
+	self.duty = reader.u8()? & 0x3;
+	self.duty_step = (reader.u8()? & 0x7) as usize;
+	self.frequency = reader.u16()?;
+	self.timer = reader.u32()?;
+	self.length.load_state(reader)?;
+	self.envelope.load_state(reader)?;
+	self.sweep = reader.u8()?;
+	self.sweep_timer = reader.u8()?;
+	self.sweep_enabled = reader.bool()?;
+	self.shadow_frequency = reader.u16()?;
+	Ok(())
+    }
+}
+
+impl SaveState for Wave {
+    fn save_state(&self, writer: &mut StateWriter) {
+	writer.bool(self.enabled);
+	writer.bool(self.dac_enabled);
+	writer.u8(self.volume_code);
+	writer.u16(self.frequency);
+	writer.u32(self.timer);
+	writer.u8(self.position as u8);
+	self.length.save_state(writer);
+	writer.bytes(&self.ram);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), &'static str> {
+	self.enabled = reader.bool()?;
+	self.dac_enabled = reader.bool()?;
+	self.volume_code = reader.u8()? & 0x3;
+	self.frequency = reader.u16()?;
+	self.timer = reader.u32()?;
+	self.position = (reader.u8()? % 32) as usize;
+	self.length.load_state(reader)?;
+	reader.bytes(&mut self.ram)
+    }
+}
+
+impl SaveState for Noise {
+    fn save_state(&self, writer: &mut StateWriter) {
+	writer.bool(self.enabled);
+	writer.u8(self.polynomial);
+	writer.u32(self.timer);
+	writer.u16(self.lfsr);
+	self.length.save_state(writer);
+	self.envelope.save_state(writer);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), &'static str> {
+	self.enabled = reader.bool()?;
+	self.polynomial = reader.u8()?;
+	self.timer = reader.u32()?;
+	self.lfsr = reader.u16()?;
+	self.length.load_state(reader)?;
+	self.envelope.load_state(reader)
+    }
+}
+
+// The sample rate belongs to the frontend and buffered samples aren't kept
+impl SaveState for Apu {
+    fn save_state(&self, writer: &mut StateWriter) {
+	writer.bytes(&self.registers);
+	writer.bool(self.powered);
+	self.square1.save_state(writer);
+	self.square2.save_state(writer);
+	self.wave.save_state(writer);
+	self.noise.save_state(writer);
+	writer.u32(self.frame_sequencer_dots);
+	writer.u8(self.frame_sequencer_step);
+	writer.u64(self.sample_dots);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), &'static str> {
+	reader.bytes(&mut self.registers)?;
+	self.powered = reader.bool()?;
+	self.square1.load_state(reader)?;
+	self.square2.load_state(reader)?;
+	self.wave.load_state(reader)?;
+	self.noise.load_state(reader)?;
+	self.frame_sequencer_dots = reader.u32()?;
+	self.frame_sequencer_step = reader.u8()? & 0x7;
+	self.sample_dots = reader.u64()?;
+	self.samples.clear();
+	Ok(())
+    }
+}
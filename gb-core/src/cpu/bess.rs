@@ -0,0 +1,190 @@
+// Best Effort Save State, the block format read by SameBoy and other emulators:
+// https://github.com/LIJI32/SameBoy/blob/master/BESS.md
+// Blocks are a four letter ID, a little-endian length and the data. They come
+// after the memory buffers they point to, and the file ends with the offset of
+// the first block followed by "BESS".
+use crate::cpu::model::Model;
+
+const FOOTER: &[u8; 4] = b"BESS";
+const CORE_LENGTH: usize = 0xD0;
+
+pub struct Block {
+    pub id: [u8; 4],
+    pub data: Vec<u8>,
+}
+
+// What the CORE block holds, the buffers are already read from the file
+pub struct Core {
+    pub model: [u8; 4],
+    pub pc: u16,
+    pub af: u16,
+    pub bc: u16,
+    pub de: u16,
+    pub hl: u16,
+    pub sp: u16,
+    pub ime: bool,
+    pub ie: u8,
+    // 0 running, 1 halted, 2 stopped
+    pub execution_state: u8,
+    pub io: [u8; 0x80],
+    pub ram: Vec<u8>,
+    pub vram: Vec<u8>,
+    pub mbc_ram: Vec<u8>,
+    pub oam: Vec<u8>,
+    pub hram: Vec<u8>,
+    pub bg_palettes: Vec<u8>,
+    pub obj_palettes: Vec<u8>,
+}
+
+// Family, model and revision, the reader only looks at the family
+pub fn model_id(model: Model) -> [u8; 4] {
+    *match model {
+	Model::Dmg0 => b"GD0 ",
+	Model::Dmg => b"GDB ",
+	Model::Mgb => b"GM  ",
+	Model::Sgb => b"SN  ",
+	Model::Sgb2 => b"S2  ",
+	Model::Cgb => b"CCE ",
+	Model::Agb => b"CA  ",
+    }
+}
+
+pub fn same_family(id: [u8; 4], model: Model) -> bool {
+    id[0] == model_id(model)[0]
+}
+
+pub struct BessWriter {
+    buffers: Vec<u8>,
+    blocks: Vec<u8>,
+}
+
+impl BessWriter {
+    pub fn new() -> Self {
+	BessWriter {
+	    buffers: Vec::new(),
+	    blocks: Vec::new(),
+	}
+    }
+
+    // Stores the data before the blocks and adds its size and offset to `block`
+    pub fn buffer(&mut self, block: &mut Vec<u8>, data: &[u8]) {
+	block.extend_from_slice(&(data.len() as u32).to_le_bytes());
+	block.extend_from_slice(&(self.buffers.len() as u32).to_le_bytes());
+	self.buffers.extend_from_slice(data);
+    }
+
+    pub fn block(&mut self, id: &[u8; 4], data: &[u8]) {
+	self.blocks.extend_from_slice(id);
+	self.blocks.extend_from_slice(&(data.len() as u32).to_le_bytes());
+	self.blocks.extend_from_slice(data);
+    }
+
+    pub fn core(&mut self, core: &Core) {
+	let mut data = Vec::with_capacity(CORE_LENGTH);
+	for value in [1, 1] {
+	    data.extend_from_slice(&u16::to_le_bytes(value));
+	}
+	data.extend_from_slice(&core.model);
+	for register in [core.pc, core.af, core.bc, core.de, core.hl, core.sp] {
+	    data.extend_from_slice(&register.to_le_bytes());
+	}
+	data.extend_from_slice(&[core.ime as u8, core.ie, core.execution_state, 0]);
+	data.extend_from_slice(&core.io);
+	for buffer in [&core.ram, &core.vram, &core.mbc_ram, &core.oam, &core.hram, &core.bg_palettes, &core.obj_palettes] {
+	    self.buffer(&mut data, buffer);
+	}
+	self.block(b"CORE", &data);
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+	self.block(b"END ", &[]);
+
+	let mut data = self.buffers;
+	let first_block = data.len() as u32;
+	data.extend_from_slice(&self.blocks);
+	data.extend_from_slice(&first_block.to_le_bytes());
+	data.extend_from_slice(FOOTER);
+	data
+    }
+}
+
+// None when the file has no BESS footer
+pub fn blocks(file: &[u8]) -> Result<Option<Vec<Block>>, &'static str> {
+    if file.len() < 8 || &file[file.len() - 4..] != FOOTER {
+	return Ok(None);
+    }
+
+    let end = file.len() - 8;
+    let mut position = read_u32(file, end)? as usize;
+    let mut blocks = Vec::new();
+    while position < end {
+	let mut id = [0; 4];
+	id.copy_from_slice(slice(file, position, 4)?);
+	let length = read_u32(file, position + 4)? as usize;
+	let data = slice(file, position + 8, length)?.to_vec();
+	position += 8 + length;
+
+	if &id == b"END " {
+	    return Ok(Some(blocks));
+	}
+	blocks.push(Block { id, data });
+    }
+    Err("The BESS blocks don't end with an END block")
+}
+
+// The size and offset at `position` in a block, pointing to a buffer in the file
+pub fn buffer<'a>(file: &'a [u8], block: &[u8], position: usize) -> Result<&'a [u8], &'static str> {
+    let size = read_u32(block, position)? as usize;
+    let offset = read_u32(block, position + 4)? as usize;
+    slice(file, offset, size)
+}
+
+pub fn read_core(file: &[u8], block: &[u8]) -> Result<Core, &'static str> {
+    if block.len() < CORE_LENGTH {
+	return Err("The BESS CORE block is too short");
+    }
+    if read_u16(block, 0)? != 1 {
+	return Err("Unsupported BESS version");
+    }
+
+    let word = |position| read_u16(block, position);
+    let mut io = [0; 0x80];
+    io.copy_from_slice(&block[0x18..0x98]);
+    let buffer = |position| buffer(file, block, position).map(<[u8]>::to_vec);
+
+    Ok(Core {
+	model: [block[4], block[5], block[6], block[7]],
+	pc: word(0x08)?,
+	af: word(0x0A)?,
+	bc: word(0x0C)?,
+	de: word(0x0E)?,
+	hl: word(0x10)?,
+	sp: word(0x12)?,
+	ime: block[0x14] != 0,
+	ie: block[0x15],
+	execution_state: block[0x16],
+	io,
+	ram: buffer(0x98)?,
+	vram: buffer(0xA0)?,
+	mbc_ram: buffer(0xA8)?,
+	oam: buffer(0xB0)?,
+	hram: buffer(0xB8)?,
+	bg_palettes: buffer(0xC0)?,
+	obj_palettes: buffer(0xC8)?,
+    })
+}
+
+fn slice(data: &[u8], position: usize, length: usize) -> Result<&[u8], &'static str> {
+    match position.checked_add(length) {
+	Some(end) if end <= data.len() => Ok(&data[position..end]),
+	_ => Err("Truncated BESS save state"),
+    }
+}
+
+fn read_u16(data: &[u8], position: usize) -> Result<u16, &'static str> {
+    Ok(u16::from_le_bytes(slice(data, position, 2)?.try_into().unwrap()))
+}
+
+fn read_u32(data: &[u8], position: usize) -> Result<u32, &'static str> {
+    Ok(u32::from_le_bytes(slice(data, position, 4)?.try_into().unwrap()))
+}
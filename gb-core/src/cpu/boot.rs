@@ -0,0 +1,195 @@
+use crate::cpu::{
+    compatibility::{self, ManualPalette},
+    model::Model,
+    CPU,
+};
+
+const DMG_BOOT_ROM_SIZE: usize = 0x100;
+const CGB_BOOT_ROM_SIZE: usize = 0x900;
+
+// I/O registers as left by the boot ROM, shared by every model
+const IO_REGISTERS: [(u16, u8); 35] = [
+    (0xFF00, 0xCF),
+    (0xFF01, 0x00),
+    (0xFF02, 0x7E),
+    (0xFF05, 0x00),
+    (0xFF06, 0x00),
+    (0xFF07, 0xF8),
+    (0xFF0F, 0xE1),
+    (0xFF10, 0x80),
+    (0xFF11, 0xBF),
+    (0xFF12, 0xF3),
+    (0xFF13, 0xFF),
+    (0xFF14, 0xBF),
+    (0xFF16, 0x3F),
+    (0xFF17, 0x00),
+    (0xFF18, 0xFF),
+    (0xFF19, 0xBF),
+    (0xFF1A, 0x7F),
+    (0xFF1B, 0xFF),
+    (0xFF1C, 0x9F),
+    (0xFF1D, 0xFF),
+    (0xFF1E, 0xBF),
+    (0xFF20, 0xFF),
+    (0xFF21, 0x00),
+    (0xFF22, 0x00),
+    (0xFF23, 0xBF),
+    (0xFF24, 0x77),
+    (0xFF25, 0xF3),
+    (0xFF42, 0x00),
+    (0xFF43, 0x00),
+    (0xFF45, 0x00),
+    (0xFF47, 0xFC),
+    (0xFF4A, 0x00),
+    (0xFF4B, 0x00),
+    (0xFF40, 0x91),
+    (0xFFFF, 0x00),
+];
+
+pub struct BootRom {
+    data: Vec<u8>,
+}
+
+impl BootRom {
+    pub fn new(data: Vec<u8>, model: Model) -> Result<BootRom, &'static str> {
+	let expected_size = if model.is_cgb() { CGB_BOOT_ROM_SIZE } else { DMG_BOOT_ROM_SIZE };
+	if data.len() != expected_size {
+	    return Err("The boot ROM size doesn't match the selected model");
+	}
+
+	Ok(BootRom { data })
+    }
+
+    pub fn data(&self) -> &[u8] {
+	&self.data
+    }
+
+    // The CGB boot ROM is split in two, leaving the cartridge header visible at 0x100-0x1FF
+    pub fn maps(&self, address: u16) -> bool {
+	match address {
+	    0x0000..=0x00FF => true,
+	    0x0200..=0x08FF => self.data.len() == CGB_BOOT_ROM_SIZE,
+	    _ => false,
+	}
+    }
+
+    pub fn read_byte(&self, address: u16) -> u8 {
+	self.data[address as usize]
+    }
+}
+
+// Puts the CPU in the state the boot ROM of the given model leaves it in when
+// jumping to the cartridge entry point.
+pub fn skip_boot(cpu: &mut CPU, model: Model, manual_palette: Option<ManualPalette>) {
+    let cartridge = cpu.bus.cartridge();
+    let cgb_game = cartridge.supports_cgb();
+
+    let (af, bc, de, hl) = match model {
+	Model::Dmg0 => (0x0100, 0xFF13, 0x00C1, 0x8403),
+	Model::Dmg | Model::Mgb => {
+	    // H and C are only set if the header checksum isn't zero
+	    let a: u16 = if model == Model::Mgb { 0xFF } else { 0x01 };
+	    let f: u16 = if cartridge.header_checksum() == 0 { 0x80 } else { 0xB0 };
+	    (a << 8 | f, 0x0013, 0x00D8, 0x014D)
+	}
+	Model::Sgb => (0x0100, 0x0014, 0x0000, 0xC060),
+	Model::Sgb2 => (0xFF00, 0x0014, 0x0000, 0xC060),
+	Model::Cgb | Model::Agb => {
+	    let (bc, de, hl) = if cgb_game {
+		(0x0000, 0xFF56, 0x000D)
+	    } else {
+		let b = if cartridge.licensed_by_nintendo() { cartridge.title_checksum() } else { 0 };
+		let hl = if b == 0x43 || b == 0x58 { 0x991A } else { 0x007C };
+		((b as u16) << 8, 0x0008, hl)
+	    };
+
+	    if model == Model::Agb {
+		// The AGB boot ROM ends with an extra INC B, which also rewrites the flags
+		let b = ((bc >> 8) as u8).wrapping_add(1);
+		let zero: u16 = if b == 0 { 0x80 } else { 0 };
+		let half_carry: u16 = if (b & 0xF) == 0 { 0x20 } else { 0 };
+		(0x1100 | zero | half_carry, (b as u16) << 8 | (bc & 0xFF), de, hl)
+	    } else {
+		(0x1180, bc, de, hl)
+	    }
+	}
+    };
+
+    let palette = match manual_palette {
+	Some(palette) => palette.palette(),
+	None => compatibility::compatibility_palette(cartridge),
+    };
+
+    cpu.registers.set_af(af);
+    cpu.registers.set_bc(bc);
+    cpu.registers.set_de(de);
+    cpu.registers.set_hl(hl);
+    cpu.sp = 0xFFFE;
+    cpu.pc = 0x0100;
+
+    if model.is_cgb() {
+	if !cgb_game {
+	    cpu.bus.enter_dmg_compatibility();
+	    cpu.bus.ppu.set_compatibility_palette(&palette);
+	}
+    } else {
+	draw_logo(cpu);
+    }
+
+    // The sound registers can't be written while the APU is off
+    cpu.bus.write_byte(0xFF26, 0x80);
+
+    for (address, value) in IO_REGISTERS {
+	cpu.bus.write_byte(address, value);
+    }
+    cpu.bus.ppu.hand_over();
+
+    match model {
+	Model::Dmg0 => {
+	    cpu.bus.restore_div(0x18);
+	    cpu.bus.write_byte(0xFF26, 0xF1);
+	}
+	Model::Dmg | Model::Mgb => {
+	    cpu.bus.restore_div(0xAB);
+	    cpu.bus.write_byte(0xFF26, 0xF1);
+	}
+	Model::Sgb | Model::Sgb2 => cpu.bus.write_byte(0xFF26, 0xF0),
+	Model::Cgb | Model::Agb => {
+	    cpu.bus.write_byte(0xFF02, 0x7F);
+	    cpu.bus.write_byte(0xFF26, 0xF1);
+	}
+    }
+}
+
+// The DMG boot ROM scales the cartridge logo to twice its size into tiles 1-24,
+// followed by the registered trademark symbol, and leaves them on the BG map.
+fn draw_logo(cpu: &mut CPU) {
+    let mut address = 0x8010;
+
+    for offset in 0..48 {
+	let byte = cpu.bus.read_byte(0x0104 + offset);
+
+	for nibble in [byte >> 4, byte & 0xF] {
+	    let mut doubled = 0u8;
+	    for bit in 0..4 {
+		if (nibble >> bit) & 0b1 == 0b1 {
+		    doubled |= 0b11 << (bit * 2);
+		}
+	    }
+
+	    cpu.bus.write_byte(address, doubled);
+	    cpu.bus.write_byte(address + 2, doubled);
+	    address += 4;
+	}
+    }
+
+    for (offset, value) in [0x3C, 0x42, 0xB9, 0xA5, 0xB9, 0xA5, 0x42, 0x3C].iter().enumerate() {
+	cpu.bus.write_byte(0x8190 + offset as u16 * 2, *value);
+    }
+
+    cpu.bus.write_byte(0x9910, 0x19);
+    for tile in 0..12 {
+	cpu.bus.write_byte(0x9904 + tile, tile as u8 + 0x01);
+	cpu.bus.write_byte(0x9924 + tile, tile as u8 + 0x0D);
+    }
+}
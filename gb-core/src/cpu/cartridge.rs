@@ -0,0 +1,76 @@
+use std::fs;
+
+pub struct Cartridge {
+    rom: Vec<u8>,
+    crc32: u32,
+}
+
+impl Cartridge {
+    pub fn new(rom: Vec<u8>) -> Result<Cartridge, &'static str> {
+	if rom.len() < 0x150 {
+	    return Err("The ROM is too small to contain a cartridge header");
+	}
+
+	let crc32 = crc32(&rom);
+	Ok(Cartridge { rom, crc32 })
+    }
+
+    pub fn from_file(path: &str) -> Result<Cartridge, &'static str> {
+	match fs::read(path) {
+	    Ok(rom) => Cartridge::new(rom),
+	    Err(_) => Err("Couldn't read the ROM file"),
+	}
+    }
+
+    pub fn read_byte(&self, address: u16) -> u8 {
+	match self.rom.get(address as usize) {
+	    Some(value) => *value,
+	    None => 0xFF,
+	}
+    }
+
+    // Sum of the title bytes, used by the CGB boot ROM to pick a palette for DMG games
+    pub fn title_checksum(&self) -> u8 {
+	self.rom[0x134..=0x143]
+	    .iter()
+	    .fold(0, |sum, byte| sum.wrapping_add(*byte))
+    }
+
+    // Identifies the ROM in save states and movies
+    pub fn crc32(&self) -> u32 {
+	self.crc32
+    }
+
+    pub fn header_checksum(&self) -> u8 {
+	self.rom[0x14D]
+    }
+
+    pub fn licensed_by_nintendo(&self) -> bool {
+	match self.rom[0x14B] {
+	    0x01 => true,
+	    0x33 => &self.rom[0x144..=0x145] == b"01",
+	    _ => false,
+	}
+    }
+
+    pub fn supports_cgb(&self) -> bool {
+	(self.rom[0x143] & 0x80) == 0x80
+    }
+
+    // The SGB BIOS only enables its functions when the old licensee code is 0x33
+    pub fn supports_sgb(&self) -> bool {
+	self.rom[0x146] == 0x03 && self.rom[0x14B] == 0x33
+    }
+}
+
+// CRC-32 as used by zip and most ROM databases
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+	crc ^= *byte as u32;
+	for _ in 0..8 {
+	    crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+	}
+    }
+    !crc
+}
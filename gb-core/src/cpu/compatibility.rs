@@ -0,0 +1,298 @@
+use crate::cpu::cartridge::Cartridge;
+
+// Colors the CGB boot ROM loads into BG palette 0 and OBJ palettes 0 and 1
+// before starting a game that has no CGB support.
+pub struct CompatibilityPalette {
+    pub bg: [u16; 4],
+    pub obj0: [u16; 4],
+    pub obj1: [u16; 4],
+}
+
+#[derive(Copy, Clone)]
+pub enum ManualPalette {
+    Up,
+    UpA,
+    UpB,
+    Left,
+    LeftA,
+    LeftB,
+    Down,
+    DownA,
+    DownB,
+    Right,
+    RightA,
+    RightB,
+}
+
+// The 30 palettes of the CGB boot ROM, four RGB555 colors each
+const PALETTES: [u16; 120] = [
+    0x7FFF, 0x32BF, 0x00D0, 0x0000,
+    0x639F, 0x4279, 0x15B0, 0x04CB,
+    0x7FFF, 0x6E31, 0x454A, 0x0000,
+    0x7FFF, 0x1BEF, 0x0200, 0x0000,
+    0x7FFF, 0x421F, 0x1CF2, 0x0000,
+    0x7FFF, 0x5294, 0x294A, 0x0000,
+    0x7FFF, 0x03FF, 0x012F, 0x0000,
+    0x7FFF, 0x03EF, 0x01D6, 0x0000,
+    0x7FFF, 0x42B5, 0x3DC8, 0x0000,
+    0x7E74, 0x03FF, 0x0180, 0x0000,
+    0x67FF, 0x77AC, 0x1A13, 0x2D6B,
+    0x7ED6, 0x4BFF, 0x2175, 0x0000,
+    0x53FF, 0x4A5F, 0x7E52, 0x0000,
+    0x4FFF, 0x7ED2, 0x3A4C, 0x1CE0,
+    0x03ED, 0x7FFF, 0x255F, 0x0000,
+    0x036A, 0x021F, 0x03FF, 0x7FFF,
+    0x7FFF, 0x01DF, 0x0112, 0x0000,
+    0x231F, 0x035F, 0x00F2, 0x0009,
+    0x7FFF, 0x03EA, 0x011F, 0x0000,
+    0x299F, 0x001A, 0x000C, 0x0000,
+    0x7FFF, 0x027F, 0x001F, 0x0000,
+    0x7FFF, 0x03E0, 0x0206, 0x0120,
+    0x7FFF, 0x7EEB, 0x001F, 0x7C00,
+    0x7FFF, 0x3FFF, 0x7E00, 0x001F,
+    0x7FFF, 0x03FF, 0x001F, 0x0000,
+    0x03FF, 0x001F, 0x000C, 0x0000,
+    0x7FFF, 0x033F, 0x0193, 0x0000,
+    0x0000, 0x4200, 0x037F, 0x7FFF,
+    0x7FFF, 0x7E8C, 0x7C00, 0x0000,
+    0x7FFF, 0x1BEF, 0x6180, 0x0000,
+];
+
+// Where the OBJ0, OBJ1 and BG colors of a combination start in PALETTES
+const fn combination(obj0: usize, obj1: usize, bg: usize) -> [usize; 3] {
+    [obj0 * 4, obj1 * 4, bg * 4]
+}
+
+// A few combinations start their colors in the middle of a palette
+const COMBINATIONS: [[usize; 3]; 51] = [
+    combination(4, 4, 29),
+    combination(18, 18, 18),
+    combination(20, 20, 20),
+    combination(24, 24, 24),
+    combination(9, 9, 9),
+    combination(0, 0, 0),
+    combination(27, 27, 27),
+    combination(5, 5, 5),
+    combination(12, 12, 12),
+    combination(26, 26, 26),
+    combination(16, 8, 8),
+    combination(4, 28, 28),
+    combination(4, 2, 2),
+    combination(3, 4, 4),
+    combination(4, 29, 29),
+    combination(28, 4, 28),
+    combination(2, 17, 2),
+    combination(16, 16, 8),
+    combination(4, 4, 7),
+    combination(4, 4, 18),
+    combination(4, 4, 20),
+    combination(19, 19, 9),
+    [15, 15, 44],
+    combination(17, 17, 2),
+    combination(4, 4, 2),
+    combination(4, 4, 3),
+    combination(28, 28, 0),
+    combination(3, 3, 0),
+    combination(0, 0, 1),
+    combination(18, 22, 18),
+    combination(20, 22, 20),
+    combination(24, 22, 24),
+    combination(16, 22, 8),
+    combination(17, 4, 13),
+    [111, 0, 56],
+    [111, 16, 60],
+    combination(19, 22, 9),
+    combination(16, 28, 10),
+    combination(4, 23, 28),
+    combination(17, 22, 2),
+    combination(4, 0, 2),
+    combination(4, 28, 3),
+    combination(28, 3, 0),
+    combination(3, 28, 4),
+    combination(21, 28, 4),
+    combination(3, 28, 0),
+    combination(25, 3, 28),
+    combination(0, 28, 8),
+    combination(4, 3, 28),
+    combination(28, 3, 6),
+    combination(4, 28, 29),
+];
+
+// Used for games without an entry in the checksum table and for every non Nintendo game
+const DEFAULT_COMBINATION: usize = 0;
+
+// Title checksums of the licensed games the boot ROM knows, with their combination
+const CHECKSUMS: [(u8, usize); 65] = [
+    (0x00, 0),
+    (0x88, 4),  // ALLEY WAY
+    (0x16, 5),  // YAKUMAN
+    (0x36, 35), // BASEBALL
+    (0xD1, 34), // TENNIS
+    (0xDB, 3),  // TETRIS
+    (0xF2, 31), // QIX
+    (0x3C, 15), // DR.MARIO
+    (0x8C, 10), // RADARMISSION
+    (0x92, 5),  // F1RACE
+    (0x3D, 19), // YOSSY NO TAMAGO
+    (0x5C, 36),
+    (0x58, 7),  // X
+    (0xC9, 37), // MARIOLAND2
+    (0x3E, 30), // YOSSY NO COOKIE
+    (0x70, 44), // ZELDA
+    (0x1D, 21),
+    (0x59, 32),
+    (0x69, 31), // TETRIS FLASH
+    (0x19, 20), // DONKEY KONG
+    (0x35, 5),  // MARIO'S PICROSS
+    (0xA8, 33),
+    (0x14, 13), // POKEMON RED
+    (0xAA, 14), // POKEMON GREEN
+    (0x75, 5),  // PICROSS 2
+    (0x95, 29), // YOSSY NO PANEPON
+    (0x99, 5),  // KIRAKIRA KIDS
+    (0x34, 18), // GAMEBOY GALLERY
+    (0x6F, 9),  // POCKETCAMERA
+    (0x15, 3),
+    (0xFF, 2),  // BALLOON KID
+    (0x97, 26), // KINGOFTHEZOO
+    (0x4B, 25), // DMG FOOTBALL
+    (0x90, 25), // WORLD CUP
+    (0x17, 41), // OTHELLO
+    (0x10, 42), // SUPER RC PRO-AM
+    (0x39, 26), // DYNABLASTER
+    (0xF7, 45), // BOY AND BLOB GB2
+    (0xF6, 42), // MEGAMAN
+    (0xA2, 45), // STAR WARS-NOA
+    (0x49, 36),
+    (0x4E, 38), // WAVERACE
+    (0x43, 26),
+    (0x68, 42), // LOLO2
+    (0xE0, 30), // YOSHI'S COOKIE
+    (0x8B, 41), // MYSTIC QUEST
+    (0xF0, 34),
+    (0xCE, 34), // TOPRANKINGTENNIS
+    (0x0C, 5),  // MANSELL
+    (0x29, 42), // MEGAMAN3
+    (0xE8, 6),  // SPACE INVADERS
+    (0xB7, 5),  // GAME&WATCH
+    (0x86, 33), // DONKEYKONGLAND95
+    (0x9A, 25), // ASTEROIDS/MISCMD
+    (0x52, 42), // STREET FIGHTER 2
+    (0x01, 42), // DEFENDER/JOUST
+    (0x9D, 40), // KILLERINSTINCT95
+    (0x71, 2),  // TETRIS BLAST
+    (0x9C, 16), // PINOCCHIO
+    (0xBD, 25),
+    (0x5D, 42), // BA.TOSHINDEN
+    (0x6D, 42), // NETTOU KOF 95
+    (0x67, 5),
+    (0x3F, 0),  // TETRIS PLUS
+    (0x6B, 39), // DONKEYKONGLAND 3
+];
+
+// Checksums shared by several titles, which the fourth letter of the title tells apart
+const CHECKSUMS_BY_LETTER: [(u8, u8, usize); 29] = [
+    (0xB3, b'B', 36),
+    (0x46, b'E', 22), // SUPER MARIOLAND
+    (0x28, b'F', 25), // GOLF
+    (0xA5, b'A', 6),  // SOLARSTRIKER
+    (0xC6, b'A', 32), // GBWARS
+    (0xD3, b'R', 12), // KAERUNOTAMENI
+    (0x27, b'B', 36),
+    (0x61, b'E', 11), // POKEMON BLUE
+    (0x18, b'K', 39), // DONKEYKONGLAND
+    (0x66, b'E', 18), // GAMEBOY GALLERY2
+    (0x6A, b'K', 39), // DONKEYKONGLAND 2
+    (0xBF, b' ', 24), // KID ICARUS
+    (0x0D, b'R', 31), // TETRIS2
+    (0xF4, b'-', 50),
+    (0xB3, b'U', 17), // MOGURANYA
+    (0x46, b'R', 46),
+    (0x28, b'A', 6),  // GALAGA&GALAXIAN
+    (0xA5, b'R', 27), // BT2RAGNAROKWORLD
+    (0xC6, b' ', 0),  // KEN GRIFFEY JR
+    (0xD3, b'I', 47),
+    (0x27, b'N', 41), // MAGNETIC SOCCER
+    (0x61, b'A', 41), // VEGAS STAKES
+    (0x18, b'I', 0),
+    (0x66, b'L', 0),  // MILLI/CENTI/PEDE
+    (0x6A, b'I', 19), // MARIO & YOSHI
+    (0xBF, b'C', 34), // SOCCER
+    (0x0D, b'E', 23), // POKEBOM
+    (0xF4, b' ', 18), // G&W GALLERY
+    (0xB3, b'R', 29), // TETRIS ATTACK
+];
+
+fn combination_palette(id: usize) -> CompatibilityPalette {
+    let colors = |start: usize| PALETTES[start..start + 4].try_into().unwrap();
+    let [obj0, obj1, bg] = COMBINATIONS[id];
+
+    CompatibilityPalette {
+	bg: colors(bg),
+	obj0: colors(obj0),
+	obj1: colors(obj1),
+    }
+}
+
+impl ManualPalette {
+    // The boot ROM lets the player override the palette by holding a direction,
+    // optionally combined with A or B, while the logo is shown.
+    pub fn palette(&self) -> CompatibilityPalette {
+	let id = match self {
+	    ManualPalette::Up => 5,
+	    ManualPalette::UpA => 43,
+	    ManualPalette::UpB => 28,
+	    ManualPalette::Left => 48,
+	    ManualPalette::LeftA => 40,
+	    ManualPalette::LeftB => 7,
+	    ManualPalette::Down => 8,
+	    ManualPalette::DownA => 3,
+	    ManualPalette::DownB => 49,
+	    ManualPalette::Right => 1,
+	    ManualPalette::RightA => 0,
+	    ManualPalette::RightB => 6,
+	};
+	combination_palette(id)
+    }
+}
+
+impl std::convert::TryFrom<&str> for ManualPalette {
+    type Error = &'static str;
+
+    fn try_from(combination: &str) -> Result<Self, Self::Error> {
+	match combination.to_lowercase().as_str() {
+	    "up" => Ok(ManualPalette::Up),
+	    "up+a" => Ok(ManualPalette::UpA),
+	    "up+b" => Ok(ManualPalette::UpB),
+	    "left" => Ok(ManualPalette::Left),
+	    "left+a" => Ok(ManualPalette::LeftA),
+	    "left+b" => Ok(ManualPalette::LeftB),
+	    "down" => Ok(ManualPalette::Down),
+	    "down+a" => Ok(ManualPalette::DownA),
+	    "down+b" => Ok(ManualPalette::DownB),
+	    "right" => Ok(ManualPalette::Right),
+	    "right+a" => Ok(ManualPalette::RightA),
+	    "right+b" => Ok(ManualPalette::RightB),
+	    _ => Err("Unknown palette, expected a direction optionally followed by +a or +b"),
+	}
+    }
+}
+
+pub fn compatibility_palette(cartridge: &Cartridge) -> CompatibilityPalette {
+    if !cartridge.licensed_by_nintendo() {
+	return combination_palette(DEFAULT_COMBINATION);
+    }
+
+    let checksum = cartridge.title_checksum();
+    let fourth_letter = cartridge.read_byte(0x137);
+
+    let by_checksum = CHECKSUMS.iter().find(|(entry, _)| *entry == checksum).map(|(_, id)| *id);
+    let by_letter = || {
+	CHECKSUMS_BY_LETTER
+	    .iter()
+	    .find(|(entry, letter, _)| *entry == checksum && *letter == fourth_letter)
+	    .map(|(_, _, id)| *id)
+    };
+
+    combination_palette(by_checksum.or_else(by_letter).unwrap_or(DEFAULT_COMBINATION))
+}
@@ -0,0 +1,324 @@
+use std::fmt;
+
+use crate::cpu::instructions::{
+    ArithmeticTarget, BitPosition, GroupedArithmeticTarget, IncDecTarget, IndirectSrc, Instruction, JumpCondition, LoadByteSrc,
+    LoadByteTarget, LoadType, LoadWordTarget, StackTarget,
+};
+
+// Names of the I/O registers, as in hardware.inc without the r prefix
+const HARDWARE_REGISTERS: [(u16, &str); 60] = [
+    (0xFF00, "P1"), (0xFF01, "SB"), (0xFF02, "SC"), (0xFF04, "DIV"), (0xFF05, "TIMA"), (0xFF06, "TMA"),
+    (0xFF07, "TAC"), (0xFF0F, "IF"), (0xFF10, "NR10"), (0xFF11, "NR11"), (0xFF12, "NR12"), (0xFF13, "NR13"),
+    (0xFF14, "NR14"), (0xFF16, "NR21"), (0xFF17, "NR22"), (0xFF18, "NR23"), (0xFF19, "NR24"), (0xFF1A, "NR30"),
+    (0xFF1B, "NR31"), (0xFF1C, "NR32"), (0xFF1D, "NR33"), (0xFF1E, "NR34"), (0xFF20, "NR41"), (0xFF21, "NR42"),
+    (0xFF22, "NR43"), (0xFF23, "NR44"), (0xFF24, "NR50"), (0xFF25, "NR51"), (0xFF26, "NR52"), (0xFF40, "LCDC"),
+    (0xFF41, "STAT"), (0xFF42, "SCY"), (0xFF43, "SCX"), (0xFF44, "LY"), (0xFF45, "LYC"), (0xFF46, "DMA"),
+    (0xFF47, "BGP"), (0xFF48, "OBP0"), (0xFF49, "OBP1"), (0xFF4A, "WY"), (0xFF4B, "WX"), (0xFF4C, "KEY0"),
+    (0xFF4D, "KEY1"), (0xFF4F, "VBK"), (0xFF50, "BANK"), (0xFF51, "HDMA1"), (0xFF52, "HDMA2"), (0xFF53, "HDMA3"),
+    (0xFF54, "HDMA4"), (0xFF55, "HDMA5"), (0xFF56, "RP"), (0xFF68, "BCPS"), (0xFF69, "BCPD"), (0xFF6A, "OCPS"),
+    (0xFF6B, "OCPD"), (0xFF6C, "OPRI"), (0xFF70, "SVBK"), (0xFF76, "PCM12"), (0xFF77, "PCM34"), (0xFFFF, "IE"),
+];
+
+pub fn hardware_register_name(address: u16) -> Option<&'static str> {
+    HARDWARE_REGISTERS.iter().find(|(register, _)| *register == address).map(|(_, name)| *name)
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Operand {
+    // Registers, conditions and register indirections, written as is
+    Fixed(&'static str),
+    Byte(u8),
+    Word(u16),
+    // [$nnnn], LDH operands are shown with their full address
+    Address(u16),
+    // Absolute destination of a jump or call, relative jumps included
+    Target(u16),
+    // The signed operand of ADD SP,e8 and LD HL,SP+e8
+    Offset(i8),
+    SPOffset(i8),
+}
+
+impl Operand {
+    // Addresses and jump targets are given to `label`, which can name them
+    pub fn format<F: Fn(u16) -> Option<String>>(&self, label: F) -> String {
+	match *self {
+	    Operand::Fixed(text) => String::from(text),
+	    Operand::Byte(value) => format!("${:02x}", value),
+	    Operand::Word(value) => format!("${:04x}", value),
+	    Operand::Address(address) => match label(address) {
+		Some(name) => format!("[{}]", name),
+		None => format!("[${:04x}]", address),
+	    },
+	    Operand::Target(address) => label(address).unwrap_or_else(|| format!("${:04x}", address)),
+	    Operand::Offset(offset) => signed(offset),
+	    Operand::SPOffset(offset) => format!("sp{}{}", if offset < 0 { "" } else { "+" }, signed(offset)),
+	}
+    }
+}
+
+fn signed(value: i8) -> String {
+    if value < 0 {
+	format!("-${:02x}", value.unsigned_abs())
+    } else {
+	format!("${:02x}", value)
+    }
+}
+
+// One instruction in RGBDS syntax, bytes that aren't an opcode become `db`
+pub struct Disassembly {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub mnemonic: &'static str,
+    pub operands: Vec<Operand>,
+}
+
+impl Disassembly {
+    pub fn length(&self) -> u16 {
+	self.bytes.len() as u16
+    }
+
+    // The memory address or jump destination encoded in the instruction
+    pub fn target(&self) -> Option<u16> {
+	self.operands.iter().find_map(|operand| match operand {
+	    Operand::Address(address) | Operand::Target(address) => Some(*address),
+	    _ => None,
+	})
+    }
+
+    // Name of the hardware register accessed, used as a comment
+    pub fn comment(&self) -> Option<&'static str> {
+	self.operands.iter().find_map(|operand| match operand {
+	    Operand::Address(address) => hardware_register_name(*address),
+	    _ => None,
+	})
+    }
+
+    pub fn format<F: Fn(u16) -> Option<String>>(&self, label: F) -> String {
+	let operands: Vec<String> = self.operands.iter().map(|operand| operand.format(&label)).collect();
+
+	if operands.is_empty() {
+	    String::from(self.mnemonic)
+	} else {
+	    format!("{} {}", self.mnemonic, operands.join(", "))
+	}
+    }
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	write!(f, "{}", self.format(|_| None))
+    }
+}
+
+// Decodes the instruction at `address`, reading its bytes through `read`
+pub fn disassemble<F: Fn(u16) -> u8>(address: u16, read: F) -> Disassembly {
+    let opcode = read(address);
+    let is_prefixed = opcode == 0xCB;
+    let instruction_byte = if is_prefixed { read(address.wrapping_add(1)) } else { opcode };
+
+    let instruction = match Instruction::from_byte(instruction_byte, is_prefixed) {
+	Some(instruction) => instruction,
+	None => {
+	    return Disassembly {
+		address,
+		bytes: vec![opcode],
+		mnemonic: "db",
+		operands: vec![Operand::Byte(opcode)],
+	    }
+	}
+    };
+
+    let length = instruction.length(is_prefixed);
+    let bytes: Vec<u8> = (0..length).map(|offset| read(address.wrapping_add(offset))).collect();
+    let byte = bytes.get(1).copied().unwrap_or(0);
+    let word = (bytes.get(2).copied().unwrap_or(0) as u16) << 8 | byte as u16;
+    let next = address.wrapping_add(length);
+
+    let (mnemonic, operands) = match instruction {
+	Instruction::ADD(target) => ("add", vec![Operand::Fixed("a"), arithmetic(target, byte)]),
+	Instruction::ADC(target) => ("adc", vec![Operand::Fixed("a"), arithmetic(target, byte)]),
+	Instruction::SUB(target) => ("sub", vec![Operand::Fixed("a"), arithmetic(target, byte)]),
+	Instruction::SBC(target) => ("sbc", vec![Operand::Fixed("a"), arithmetic(target, byte)]),
+	Instruction::AND(target) => ("and", vec![Operand::Fixed("a"), arithmetic(target, byte)]),
+	Instruction::OR(target) => ("or", vec![Operand::Fixed("a"), arithmetic(target, byte)]),
+	Instruction::XOR(target) => ("xor", vec![Operand::Fixed("a"), arithmetic(target, byte)]),
+	Instruction::CP(target) => ("cp", vec![Operand::Fixed("a"), arithmetic(target, byte)]),
+	Instruction::ADDHL(target) => {
+	    let source = match target {
+		GroupedArithmeticTarget::BC => "bc",
+		GroupedArithmeticTarget::DE => "de",
+		GroupedArithmeticTarget::HL => "hl",
+		GroupedArithmeticTarget::SP => "sp",
+	    };
+	    ("add", vec![Operand::Fixed("hl"), Operand::Fixed(source)])
+	}
+	Instruction::ADDSP => ("add", vec![Operand::Fixed("sp"), Operand::Offset(byte as i8)]),
+	Instruction::INC(target) => ("inc", vec![Operand::Fixed(inc_dec(target))]),
+	Instruction::DEC(target) => ("dec", vec![Operand::Fixed(inc_dec(target))]),
+	Instruction::SRL(target) => ("srl", vec![arithmetic(target, byte)]),
+	Instruction::RR(target) => ("rr", vec![arithmetic(target, byte)]),
+	Instruction::RL(target) => ("rl", vec![arithmetic(target, byte)]),
+	Instruction::RRC(target) => ("rrc", vec![arithmetic(target, byte)]),
+	Instruction::RLC(target) => ("rlc", vec![arithmetic(target, byte)]),
+	Instruction::SRA(target) => ("sra", vec![arithmetic(target, byte)]),
+	Instruction::SLA(target) => ("sla", vec![arithmetic(target, byte)]),
+	Instruction::SWAP(target) => ("swap", vec![arithmetic(target, byte)]),
+	Instruction::BIT(target, bit) => ("bit", vec![bit_number(bit), arithmetic(target, byte)]),
+	Instruction::RESET(target, bit) => ("res", vec![bit_number(bit), arithmetic(target, byte)]),
+	Instruction::SET(target, bit) => ("set", vec![bit_number(bit), arithmetic(target, byte)]),
+	Instruction::CCF => ("ccf", vec![]),
+	Instruction::SCF => ("scf", vec![]),
+	Instruction::RRA => ("rra", vec![]),
+	Instruction::RLA => ("rla", vec![]),
+	Instruction::RRCA => ("rrca", vec![]),
+	Instruction::RLCA => ("rlca", vec![]),
+	Instruction::CPL => ("cpl", vec![]),
+	Instruction::DAA => ("daa", vec![]),
+	Instruction::JPI => ("jp", vec![Operand::Fixed("hl")]),
+	Instruction::JP(condition) => ("jp", conditional(condition, Operand::Target(word))),
+	Instruction::JR(condition) => {
+	    let destination = next.wrapping_add_signed(byte as i8 as i16);
+	    ("jr", conditional(condition, Operand::Target(destination)))
+	}
+	Instruction::CALL(condition) => ("call", conditional(condition, Operand::Target(word))),
+	Instruction::RET(JumpCondition::Always) => ("ret", vec![]),
+	Instruction::RET(condition) => ("ret", vec![Operand::Fixed(condition_name(condition))]),
+	Instruction::RETI => ("reti", vec![]),
+	Instruction::RST(vector) => ("rst", vec![Operand::Target(vector as u16)]),
+	Instruction::PUSH(target) => ("push", vec![Operand::Fixed(stack(target))]),
+	Instruction::POP(target) => ("pop", vec![Operand::Fixed(stack(target))]),
+	Instruction::LD(load_type) => load(load_type, byte, word),
+	Instruction::NOP => ("nop", vec![]),
+	Instruction::HALT => ("halt", vec![]),
+	Instruction::STOP => ("stop", vec![]),
+	Instruction::DI => ("di", vec![]),
+	Instruction::EI => ("ei", vec![]),
+    };
+
+    Disassembly {
+	address,
+	bytes,
+	mnemonic,
+	operands,
+    }
+}
+
+fn load(load_type: LoadType, byte: u8, word: u16) -> (&'static str, Vec<Operand>) {
+    match load_type {
+	LoadType::Byte(target, source) => {
+	    let target = match target {
+		LoadByteTarget::A => "a",
+		LoadByteTarget::B => "b",
+		LoadByteTarget::C => "c",
+		LoadByteTarget::D => "d",
+		LoadByteTarget::E => "e",
+		LoadByteTarget::H => "h",
+		LoadByteTarget::L => "l",
+		LoadByteTarget::HLI => "[hl]",
+	    };
+	    let source = match source {
+		LoadByteSrc::A => Operand::Fixed("a"),
+		LoadByteSrc::B => Operand::Fixed("b"),
+		LoadByteSrc::C => Operand::Fixed("c"),
+		LoadByteSrc::D => Operand::Fixed("d"),
+		LoadByteSrc::E => Operand::Fixed("e"),
+		LoadByteSrc::H => Operand::Fixed("h"),
+		LoadByteSrc::L => Operand::Fixed("l"),
+		LoadByteSrc::D8 => Operand::Byte(byte),
+		LoadByteSrc::HLI => Operand::Fixed("[hl]"),
+	    };
+	    ("ld", vec![Operand::Fixed(target), source])
+	}
+	LoadType::Word(target) => {
+	    let target = match target {
+		LoadWordTarget::BC => "bc",
+		LoadWordTarget::DE => "de",
+		LoadWordTarget::HL => "hl",
+		LoadWordTarget::SP => "sp",
+	    };
+	    ("ld", vec![Operand::Fixed(target), Operand::Word(word)])
+	}
+	LoadType::AFromIndirect(IndirectSrc::IOPortC) => ("ldh", vec![Operand::Fixed("a"), Operand::Fixed("[c]")]),
+	LoadType::IndirectFromA(IndirectSrc::IOPortC) => ("ldh", vec![Operand::Fixed("[c]"), Operand::Fixed("a")]),
+	LoadType::AFromIndirect(source) => ("ld", vec![Operand::Fixed("a"), indirect(source, word)]),
+	LoadType::IndirectFromA(target) => ("ld", vec![indirect(target, word), Operand::Fixed("a")]),
+	LoadType::AFromByteAddress => ("ldh", vec![Operand::Fixed("a"), Operand::Address(0xFF00 | byte as u16)]),
+	LoadType::ByteAddressFromA => ("ldh", vec![Operand::Address(0xFF00 | byte as u16), Operand::Fixed("a")]),
+	LoadType::SPFromHL => ("ld", vec![Operand::Fixed("sp"), Operand::Fixed("hl")]),
+	LoadType::HLFromSPOffset => ("ld", vec![Operand::Fixed("hl"), Operand::SPOffset(byte as i8)]),
+	LoadType::IndirectFromSP => ("ld", vec![Operand::Address(word), Operand::Fixed("sp")]),
+    }
+}
+
+fn arithmetic(target: ArithmeticTarget, byte: u8) -> Operand {
+    match target {
+	ArithmeticTarget::A => Operand::Fixed("a"),
+	ArithmeticTarget::B => Operand::Fixed("b"),
+	ArithmeticTarget::C => Operand::Fixed("c"),
+	ArithmeticTarget::D => Operand::Fixed("d"),
+	ArithmeticTarget::E => Operand::Fixed("e"),
+	ArithmeticTarget::H => Operand::Fixed("h"),
+	ArithmeticTarget::L => Operand::Fixed("l"),
+	ArithmeticTarget::HLI => Operand::Fixed("[hl]"),
+	ArithmeticTarget::D8 => Operand::Byte(byte),
+    }
+}
+
+fn inc_dec(target: IncDecTarget) -> &'static str {
+    match target {
+	IncDecTarget::A => "a",
+	IncDecTarget::B => "b",
+	IncDecTarget::C => "c",
+	IncDecTarget::D => "d",
+	IncDecTarget::E => "e",
+	IncDecTarget::H => "h",
+	IncDecTarget::L => "l",
+	IncDecTarget::HLI => "[hl]",
+	IncDecTarget::BC => "bc",
+	IncDecTarget::DE => "de",
+	IncDecTarget::HL => "hl",
+	IncDecTarget::SP => "sp",
+    }
+}
+
+fn indirect(source: IndirectSrc, word: u16) -> Operand {
+    match source {
+	IndirectSrc::BC => Operand::Fixed("[bc]"),
+	IndirectSrc::DE => Operand::Fixed("[de]"),
+	IndirectSrc::HLMinus => Operand::Fixed("[hl-]"),
+	IndirectSrc::HLPlus => Operand::Fixed("[hl+]"),
+	IndirectSrc::D8 => Operand::Address(word),
+	IndirectSrc::IOPortC => Operand::Fixed("[c]"),
+    }
+}
+
+fn stack(target: StackTarget) -> &'static str {
+    match target {
+	StackTarget::AF => "af",
+	StackTarget::BC => "bc",
+	StackTarget::DE => "de",
+	StackTarget::HL => "hl",
+    }
+}
+
+fn bit_number(bit: BitPosition) -> Operand {
+    const NUMBERS: [&str; 8] = ["0", "1", "2", "3", "4", "5", "6", "7"];
+    Operand::Fixed(NUMBERS[u8::from(bit) as usize])
+}
+
+fn condition_name(condition: JumpCondition) -> &'static str {
+    match condition {
+	JumpCondition::NotZero => "nz",
+	JumpCondition::Zero => "z",
+	JumpCondition::NotCarry => "nc",
+	JumpCondition::Carry => "c",
+	JumpCondition::Always => "",
+    }
+}
+
+fn conditional(condition: JumpCondition, destination: Operand) -> Vec<Operand> {
+    match condition {
+	JumpCondition::Always => vec![destination],
+	condition => vec![Operand::Fixed(condition_name(condition)), destination],
+    }
+}
@@ -17,10 +17,10 @@ impl std::convert::From<FlagsRegister> for u8 {
 
 impl std::convert::From<u8> for FlagsRegister {
     fn from(byte: u8) -> Self {
-        let zero: bool = (byte >> 7) != 0;
-        let subtraction: bool = (byte >> 6) != 0;
-        let half_carry: bool = (byte >> 5) != 0;
-        let carry: bool = (byte >> 4) != 0;
+	let zero: bool = ((byte >> 7) & 0b1) != 0;
+	let subtraction: bool = ((byte >> 6) & 0b1) != 0;
+	let half_carry: bool = ((byte >> 5) & 0b1) != 0;
+	let carry: bool = ((byte >> 4) & 0b1) != 0;
 
         FlagsRegister {
             zero,
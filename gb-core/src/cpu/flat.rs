@@ -0,0 +1,94 @@
+use std::cell::RefCell;
+
+use crate::cpu::{gameboy::RegisterState, memory::Bus, watchpoints::Access, CPU};
+
+// 64KB of RAM with nothing else mapped, no hardware runs between instructions
+struct FlatMemory {
+    memory: Vec<u8>,
+    // Every read and write of the CPU in order, polling IE and IF isn't one
+    accesses: RefCell<Vec<(u16, u8, Access)>>,
+}
+
+impl Bus for FlatMemory {
+    fn read_byte(&self, address: u16) -> u8 {
+	let value = self.memory[address as usize];
+	self.accesses.borrow_mut().push((address, value, Access::Read));
+	value
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+	self.accesses.get_mut().push((address, value, Access::Write));
+	self.memory[address as usize] = value;
+    }
+
+    fn peek_byte(&self, address: u16) -> u8 {
+	self.memory[address as usize]
+    }
+
+    fn tick(&mut self, cycles: u32) -> u32 {
+	cycles
+    }
+}
+
+// The CPU on its own over flat memory, used to check instructions in isolation
+// against single-step test vectors
+pub struct FlatCpu {
+    cpu: CPU<FlatMemory>,
+}
+
+impl FlatCpu {
+    pub fn new() -> Self {
+	FlatCpu {
+	    cpu: CPU::new(FlatMemory {
+		memory: vec![0; 0x10000],
+		accesses: RefCell::new(Vec::new()),
+	    }),
+	}
+    }
+
+    pub fn registers(&self) -> RegisterState {
+	self.cpu.register_state()
+    }
+
+    pub fn set_registers(&mut self, state: RegisterState) {
+	self.cpu.set_register_state(state);
+    }
+
+    pub fn ime(&self) -> bool {
+	self.cpu.ime
+    }
+
+    pub fn set_ime(&mut self, enabled: bool) {
+	self.cpu.ime = enabled;
+    }
+
+    // Whether an EI is waiting for the next instruction to enable interrupts
+    pub fn ime_scheduled(&self) -> bool {
+	self.cpu.ime_scheduled
+    }
+
+    // Unlike the accesses of the CPU, these aren't recorded
+    pub fn read_memory(&self, address: u16) -> u8 {
+	self.cpu.bus.memory[address as usize]
+    }
+
+    pub fn write_memory(&mut self, address: u16, value: u8) {
+	self.cpu.bus.memory[address as usize] = value;
+    }
+
+    // What the CPU read and wrote since the last call, in order
+    pub fn take_accesses(&mut self) -> Vec<(u16, u8, Access)> {
+	self.cpu.bus.accesses.take()
+    }
+
+    // Runs a single instruction and returns the elapsed T-cycles
+    pub fn step(&mut self) -> u32 {
+	self.cpu.step()
+    }
+}
+
+impl Default for FlatCpu {
+    fn default() -> Self {
+	FlatCpu::new()
+    }
+}
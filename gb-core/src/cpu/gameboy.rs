@@ -0,0 +1,478 @@
+use crate::cpu::{
+    bess::{self, BessWriter, Block, Core},
+    boot::{self, BootRom},
+    cartridge::Cartridge,
+    compatibility::ManualPalette,
+    joypad::Button,
+    memory::MemoryBus,
+    model::Model,
+    savestate::{SaveState, StateReader, StateWriter},
+    watchpoints::{Access, WatchHit},
+    CPU,
+};
+
+const DOTS_PER_LINE: u64 = 456;
+
+// Sections a save state can't be loaded without, the SGB one is also needed for SGB games
+const REQUIRED_SECTIONS: [&[u8; 4]; 10] =
+    [b"INFO", b"CPU ", b"MEM ", b"PPU ", b"APU ", b"TIMR", b"SERL", b"JOYP", b"HDMA", b"CLCK"];
+
+// Our own state inside BESS files, other emulators skip it
+const NATIVE_BLOCK: &[u8; 4] = b"LBEM";
+const BESS_BLOCKS: [&[u8; 4]; 5] = [b"NAME", b"INFO", b"CORE", b"SGB ", NATIVE_BLOCK];
+
+// How the console is powered on. Without a boot ROM it starts from the state
+// the boot ROM of the model would leave behind.
+pub struct Config {
+    pub model: Model,
+    pub boot_rom: Option<Vec<u8>>,
+    pub palette: Option<ManualPalette>,
+    pub color_correction: bool,
+}
+
+impl Config {
+    pub fn new(model: Model) -> Self {
+	Config {
+	    model,
+	    boot_rom: None,
+	    palette: None,
+	    color_correction: false,
+	}
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RegisterState {
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub pc: u16,
+}
+
+impl RegisterState {
+    // By the names the debuggers use, single registers take a byte and pairs a word
+    pub fn set(&mut self, name: &str, value: u16) -> Result<(), &'static str> {
+	let (high, low) = ((value >> 8) as u8, value as u8);
+	if value > 0xFF && name.len() == 1 {
+	    return Err("Single registers hold one byte");
+	}
+
+	match name.to_lowercase().as_str() {
+	    "a" => self.a = low,
+	    "f" => self.f = low & 0xF0,
+	    "b" => self.b = low,
+	    "c" => self.c = low,
+	    "d" => self.d = low,
+	    "e" => self.e = low,
+	    "h" => self.h = low,
+	    "l" => self.l = low,
+	    "af" => (self.a, self.f) = (high, low & 0xF0),
+	    "bc" => (self.b, self.c) = (high, low),
+	    "de" => (self.d, self.e) = (high, low),
+	    "hl" => (self.h, self.l) = (high, low),
+	    "sp" => self.sp = value,
+	    "pc" => self.pc = value,
+	    _ => return Err("Unknown register"),
+	}
+	Ok(())
+    }
+}
+
+// What a state file restored. Files from other emulators only carry what the
+// BESS blocks describe, the rest of the machine keeps going from where it was.
+pub struct LoadReport {
+    pub emulator: Option<String>,
+    pub restored: Vec<&'static str>,
+    pub skipped: Vec<String>,
+}
+
+// Entry point for frontends and tools embedding the emulator
+pub struct GameBoy {
+    cpu: CPU,
+    model: Model,
+    cycles: u64,
+    // BESS blocks this emulator doesn't understand, written back when saving
+    foreign_blocks: Vec<Block>,
+}
+
+impl GameBoy {
+    pub fn new(rom: Vec<u8>, config: &Config) -> Result<GameBoy, &'static str> {
+	GameBoy::with_cartridge(Cartridge::new(rom)?, config)
+    }
+
+    pub fn from_file(path: &str, config: &Config) -> Result<GameBoy, &'static str> {
+	GameBoy::with_cartridge(Cartridge::from_file(path)?, config)
+    }
+
+    fn with_cartridge(cartridge: Cartridge, config: &Config) -> Result<GameBoy, &'static str> {
+	let mut bus = MemoryBus::new(cartridge, config.model);
+	bus.ppu.set_color_correction(config.color_correction);
+
+	let mut cpu = CPU::new(bus);
+	match &config.boot_rom {
+	    Some(data) => cpu.bus.map_boot_rom(BootRom::new(data.clone(), config.model)?),
+	    None => boot::skip_boot(&mut cpu, config.model, config.palette),
+	}
+
+	Ok(GameBoy {
+	    cpu,
+	    model: config.model,
+	    cycles: 0,
+	    foreign_blocks: Vec::new(),
+	})
+    }
+
+    pub fn model(&self) -> Model {
+	self.model
+    }
+
+    // Runs a single instruction and returns the elapsed T-cycles
+    pub fn step(&mut self) -> u32 {
+	let cycles = self.cpu.step();
+	self.cycles += cycles as u64;
+	cycles
+    }
+
+    // Runs until LY changes, or for a line worth of dots with the LCD off
+    pub fn step_scanline(&mut self) -> u64 {
+	let line = self.cpu.bus.read_byte(0xFF44);
+	let mut cycles = 0;
+
+	while self.cpu.bus.read_byte(0xFF44) == line && cycles < DOTS_PER_LINE {
+	    cycles += self.step() as u64;
+	}
+
+	cycles
+    }
+
+    pub fn step_frame(&mut self) -> u64 {
+	let cycles = self.cpu.run_frame();
+	self.cycles += cycles;
+	cycles
+    }
+
+    // T-cycles elapsed since power on
+    pub fn cycles(&self) -> u64 {
+	self.cycles
+    }
+
+    pub fn frames(&self) -> u64 {
+	self.cpu.bus.ppu.frames()
+    }
+
+    // 160x144 pixels as 0x00RRGGBB
+    pub fn framebuffer(&self) -> &[u32] {
+	self.cpu.bus.ppu.framebuffer()
+    }
+
+    // Width, height and pixels of the displayed image, which is 256x224 with the SGB border
+    pub fn screen(&self) -> (usize, usize, &[u32]) {
+	self.cpu.bus.screen()
+    }
+
+    // Takes effect from the next line drawn
+    pub fn set_color_correction(&mut self, enabled: bool) {
+	self.cpu.bus.ppu.set_color_correction(enabled);
+    }
+
+    pub fn color_correction(&self) -> bool {
+	self.cpu.bus.ppu.color_correction()
+    }
+
+    // For comparing against Gameboy Doctor logs, the rest of the hardware still sees the real LY
+    pub fn set_doctor_mode(&mut self, enabled: bool) {
+	self.cpu.bus.set_doctor_ly(enabled);
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+	self.cpu.bus.apu.set_sample_rate(sample_rate);
+    }
+
+    // Stereo samples generated since the last call, left and right interleaved
+    pub fn take_audio_samples(&mut self) -> Vec<f32> {
+	self.cpu.bus.apu.take_samples()
+    }
+
+    pub fn press(&mut self, button: Button) {
+	self.cpu.bus.press_button(0, button);
+    }
+
+    pub fn release(&mut self, button: Button) {
+	self.cpu.bus.release_button(0, button);
+    }
+
+    // Extra controllers are only read by SGB games that enabled them with MLT_REQ
+    pub fn press_player(&mut self, player: usize, button: Button) {
+	self.cpu.bus.press_button(player, button);
+    }
+
+    pub fn release_player(&mut self, player: usize, button: Button) {
+	self.cpu.bus.release_button(player, button);
+    }
+
+    pub fn is_pressed(&self, player: usize, button: Button) -> bool {
+	self.cpu.bus.is_pressed(player, button)
+    }
+
+    // Whether LD B,B ran since the last call
+    pub fn take_breakpoint(&mut self) -> bool {
+	std::mem::take(&mut self.cpu.breakpoint)
+    }
+
+    // Address and value of the invalid opcode the CPU hung on, if it did
+    pub fn invalid_opcode(&self) -> Option<(u16, u8)> {
+	self.cpu.locked
+    }
+
+    pub fn watch(&mut self, address: u16, access: Access) {
+	self.cpu.bus.watchpoints.add(address, access);
+    }
+
+    pub fn unwatch(&mut self, address: u16) -> bool {
+	self.cpu.bus.watchpoints.remove(address)
+    }
+
+    // The last watched access by the CPU since the previous call
+    pub fn take_watch_hit(&mut self) -> Option<WatchHit> {
+	self.cpu.bus.watchpoints.take_hit()
+    }
+
+    pub fn serial_output(&self) -> &[u8] {
+	self.cpu.bus.serial_output()
+    }
+
+    pub fn registers(&self) -> RegisterState {
+	self.cpu.register_state()
+    }
+
+    pub fn set_registers(&mut self, state: RegisterState) {
+	self.cpu.set_register_state(state);
+    }
+
+    // Whether the next step runs an instruction, for traces logging one line per instruction
+    pub fn next_step_is_instruction(&self) -> bool {
+	self.cpu.next_step_is_instruction()
+    }
+
+    pub fn ime(&self) -> bool {
+	self.cpu.ime
+    }
+
+    pub fn halted(&self) -> bool {
+	self.cpu.halted
+    }
+
+    // Reads and writes go through the bus, with the same side effects as the CPU ones
+    pub fn read_memory(&self, address: u16) -> u8 {
+	self.cpu.bus.read_byte(address)
+    }
+
+    pub fn write_memory(&mut self, address: u16, value: u8) {
+	self.cpu.bus.write_byte(address, value);
+    }
+
+    // Bank mapped at the address, as used by symbol files. Without a mapper
+    // 0x4000-0x7FFF always holds bank 1.
+    pub fn bank(&self, address: u16) -> u16 {
+	match address {
+	    0x4000..=0x7FFF => 1,
+	    _ => 0,
+	}
+    }
+
+    // Snapshot of the whole machine, breakpoints and watchpoints aren't part of it
+    pub fn save_state(&self) -> Vec<u8> {
+	let mut writer = StateWriter::new();
+	writer.section(b"INFO", |writer| {
+	    writer.u8(self.model as u8);
+	    writer.u32(self.cpu.bus.cartridge().crc32());
+	});
+	writer.section(b"CPU ", |writer| self.cpu.save_state(writer));
+	self.cpu.bus.save_sections(&mut writer);
+	writer.section(b"CLCK", |writer| writer.u64(self.cycles));
+	writer.finish()
+    }
+
+    // The state must come from the same ROM and model. Nothing changes when it fails to load.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), &'static str> {
+	let sections = StateReader::sections(data)?;
+	let has = |tag: &[u8; 4]| sections.iter().any(|(section, _)| section == tag);
+	if REQUIRED_SECTIONS.iter().any(|tag| !has(tag)) || (self.cpu.bus.has_sgb() && !has(b"SGB ")) {
+	    return Err("The save state is missing parts of the machine");
+	}
+
+	let mut info = sections.iter().find(|(tag, _)| tag == b"INFO").unwrap().1.clone();
+	if info.u8()? != self.model as u8 {
+	    return Err("The save state is for another model");
+	}
+	if info.u32()? != self.cpu.bus.cartridge().crc32() {
+	    return Err("The save state is for another ROM");
+	}
+
+	let backup = self.save_state();
+	if let Err(error) = self.load_sections(sections) {
+	    self.load_sections(StateReader::sections(&backup)?)?;
+	    return Err(error);
+	}
+	Ok(())
+    }
+
+    // Unknown sections are skipped, they may come from a newer version
+    fn load_sections(&mut self, sections: Vec<([u8; 4], StateReader)>) -> Result<(), &'static str> {
+	for (tag, mut reader) in sections {
+	    match &tag {
+		b"CPU " => self.cpu.load_state(&mut reader)?,
+		b"CLCK" => self.cycles = reader.u64()?,
+		_ => {
+		    self.cpu.bus.load_section(&tag, &mut reader)?;
+		}
+	    }
+	}
+	Ok(())
+    }
+
+    // FNV-1a of the save state, to check that two runs ended the same way
+    pub fn state_hash(&self) -> u64 {
+	self.save_state()
+	    .iter()
+	    .fold(0xCBF2_9CE4_8422_2325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01B3))
+    }
+
+    pub fn rom_crc32(&self) -> u32 {
+	self.cpu.bus.cartridge().crc32()
+    }
+
+    // Our state followed by the BESS blocks, so that other emulators can load it too
+    pub fn save_state_file(&self) -> Vec<u8> {
+	let mut writer = BessWriter::new();
+	writer.block(b"NAME", format!("lb-emu {}", env!("CARGO_PKG_VERSION")).as_bytes());
+	writer.block(b"INFO", &self.bess_info());
+
+	let registers = self.registers();
+	writer.core(&Core {
+	    pc: registers.pc,
+	    af: (registers.a as u16) << 8 | registers.f as u16,
+	    bc: (registers.b as u16) << 8 | registers.c as u16,
+	    de: (registers.d as u16) << 8 | registers.e as u16,
+	    hl: (registers.h as u16) << 8 | registers.l as u16,
+	    sp: registers.sp,
+	    ime: self.cpu.ime,
+	    execution_state: self.cpu.halted as u8,
+	    ..self.cpu.bus.bess_core()
+	});
+	self.cpu.bus.save_bess_sgb(&mut writer);
+	writer.block(NATIVE_BLOCK, &self.save_state());
+
+	for block in &self.foreign_blocks {
+	    writer.block(&block.id, &block.data);
+	}
+	writer.finish()
+    }
+
+    // Takes our files, BESS files from other emulators and bare native states
+    pub fn load_state_file(&mut self, data: &[u8]) -> Result<LoadReport, &'static str> {
+	let blocks = match bess::blocks(data)? {
+	    Some(blocks) => blocks,
+	    None => {
+		self.load_state(data)?;
+		self.foreign_blocks.clear();
+		return Ok(self.native_report(None));
+	    }
+	};
+
+	let find = |id: &[u8; 4]| blocks.iter().find(|block| &block.id == id);
+	let emulator = find(b"NAME").map(|block| String::from_utf8_lossy(&block.data).into_owned());
+	if let Some(info) = find(b"INFO") {
+	    if info.data.len() >= 0x12 && info.data[..0x12] != self.bess_info()[..] {
+		return Err("The save state is for another ROM");
+	    }
+	}
+	let core = bess::read_core(data, &find(b"CORE").ok_or("The BESS save state has no CORE block")?.data)?;
+	if !bess::same_family(core.model, self.model) {
+	    return Err("The save state is for another model");
+	}
+
+	// Falls back to the BESS blocks when our own state is from an incompatible version
+	let native = find(NATIVE_BLOCK).is_some_and(|block| self.load_state(&block.data).is_ok());
+	let mut report = if native {
+	    self.native_report(emulator)
+	} else {
+	    let backup = self.save_state();
+	    match self.load_bess(data, &core, find(b"SGB ")) {
+		Ok(report) => LoadReport { emulator, ..report },
+		Err(error) => {
+		    self.load_state(&backup)?;
+		    return Err(error);
+		}
+	    }
+	};
+
+	self.foreign_blocks = blocks.into_iter().filter(|block| !BESS_BLOCKS.contains(&&block.id)).collect();
+	for block in &self.foreign_blocks {
+	    report.skipped.push(format!("{} block, kept for saving", String::from_utf8_lossy(&block.id).trim_end()));
+	}
+	Ok(report)
+    }
+
+    fn native_report(&self, emulator: Option<String>) -> LoadReport {
+	let mut restored = vec!["CPU", "memory", "PPU", "APU", "timer", "serial", "joypad", "HDMA"];
+	if self.cpu.bus.has_sgb() {
+	    restored.push("SGB");
+	}
+	LoadReport { emulator, restored, skipped: Vec::new() }
+    }
+
+    fn load_bess(&mut self, file: &[u8], core: &Core, sgb: Option<&Block>) -> Result<LoadReport, &'static str> {
+	let mut report = LoadReport {
+	    emulator: None,
+	    restored: vec!["CPU registers", "interrupts", "hardware registers", "work RAM", "high RAM", "video RAM", "OAM"],
+	    skipped: vec!["PPU, APU and timer internal state, BESS doesn't have it".to_string()],
+	};
+
+	self.set_registers(RegisterState {
+	    a: (core.af >> 8) as u8,
+	    f: core.af as u8,
+	    b: (core.bc >> 8) as u8,
+	    c: core.bc as u8,
+	    d: (core.de >> 8) as u8,
+	    e: core.de as u8,
+	    h: (core.hl >> 8) as u8,
+	    l: core.hl as u8,
+	    sp: core.sp,
+	    pc: core.pc,
+	});
+	self.cpu.ime = core.ime;
+	self.cpu.ime_scheduled = false;
+	// There is no STOP mode, a stopped CPU waits for an interrupt like a halted one
+	self.cpu.halted = core.execution_state != 0;
+	self.cpu.halt_bug = false;
+	self.cpu.locked = None;
+	self.cpu.bus.load_bess_core(core);
+
+	if self.model.is_cgb() && !core.bg_palettes.is_empty() {
+	    report.restored.push("CGB palettes");
+	}
+	if !core.mbc_ram.is_empty() {
+	    report.skipped.push("cartridge RAM, the cartridge has none".to_string());
+	}
+	if let Some(block) = sgb {
+	    match self.cpu.bus.load_bess_sgb(file, &block.data)? {
+		true => report.restored.push("SGB"),
+		false => report.skipped.push("SGB block, the game isn't running on an SGB".to_string()),
+	    }
+	}
+	Ok(report)
+    }
+
+    // Title and global checksum from the cartridge header
+    fn bess_info(&self) -> Vec<u8> {
+	let cartridge = self.cpu.bus.cartridge();
+	(0x134..0x144).chain(0x14E..0x150).map(|address| cartridge.read_byte(address)).collect()
+    }
+}
@@ -0,0 +1,109 @@
+use crate::cpu::savestate::{SaveState, StateReader, StateWriter};
+
+pub const HDMA_BLOCK_LENGTH: u16 = 0x10;
+// The CPU is halted for 8 M-cycles while every block is copied, 16 in double speed
+pub const HDMA_BLOCK_DOTS: u32 = 32;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum HdmaMode {
+    GeneralPurpose,
+    HBlank,
+}
+
+// VRAM DMA controller, programmed through HDMA1-HDMA5 (0xFF51-0xFF55)
+pub struct Hdma {
+    source: u16,
+    destination: u16,
+    blocks_left: u8,
+    hblank_active: bool,
+}
+
+impl Hdma {
+    pub fn new() -> Self {
+	Hdma {
+	    source: 0,
+	    destination: 0x8000,
+	    blocks_left: 0,
+	    hblank_active: false,
+	}
+    }
+
+    pub fn hblank_active(&self) -> bool {
+	self.hblank_active
+    }
+
+    pub fn blocks_left(&self) -> u8 {
+	self.blocks_left
+    }
+
+    pub fn read_register(&self, address: u16) -> u8 {
+	match address {
+	    0xFF55 => {
+		let length = self.blocks_left.wrapping_sub(1) & 0x7F;
+		if self.hblank_active { length } else { 0x80 | length }
+	    }
+	    _ => 0xFF,
+	}
+    }
+
+    // Returns the kind of transfer started by a write to HDMA5
+    pub fn write_register(&mut self, address: u16, value: u8) -> Option<HdmaMode> {
+	match address {
+	    0xFF51 => self.source = (value as u16) << 8 | (self.source & 0xF0),
+	    0xFF52 => self.source = (self.source & 0xFF00) | (value & 0xF0) as u16,
+	    0xFF53 => self.destination = 0x8000 | ((value & 0x1F) as u16) << 8 | (self.destination & 0xF0),
+	    0xFF54 => self.destination = (self.destination & 0xFF00) | (value & 0xF0) as u16,
+	    0xFF55 => {
+		// Writing with bit 7 cleared while an HBlank transfer is running cancels it
+		if self.hblank_active && (value & 0x80) == 0 {
+		    self.hblank_active = false;
+		    return None;
+		}
+
+		self.blocks_left = (value & 0x7F) + 1;
+
+		return if (value & 0x80) == 0x80 {
+		    self.hblank_active = true;
+		    Some(HdmaMode::HBlank)
+		} else {
+		    Some(HdmaMode::GeneralPurpose)
+		};
+	    }
+	    _ => {}
+	}
+
+	None
+    }
+
+    // Returns the source and destination of the next block and advances past it
+    pub fn next_block(&mut self) -> (u16, u16) {
+	let block = (self.source, self.destination);
+
+	self.source = self.source.wrapping_add(HDMA_BLOCK_LENGTH);
+	self.destination = 0x8000 | (self.destination.wrapping_add(HDMA_BLOCK_LENGTH) & 0x1FF0);
+	self.blocks_left -= 1;
+
+	if self.blocks_left == 0 {
+	    self.hblank_active = false;
+	}
+
+	block
+    }
+}
+
+impl SaveState for Hdma {
+    fn save_state(&self, writer: &mut StateWriter) {
+	writer.u16(self.source);
+	writer.u16(self.destination);
+	writer.u8(self.blocks_left);
+	writer.bool(self.hblank_active);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), &'static str> {
+	self.source = reader.u16()?;
+	self.destination = reader.u16()?;
+	self.blocks_left = reader.u8()?;
+	self.hblank_active = reader.bool()?;
+	Ok(())
+    }
+}
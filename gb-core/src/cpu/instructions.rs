@@ -0,0 +1,845 @@
+#[allow(dead_code)]
+pub enum Instruction {
+    ADDHL(GroupedArithmeticTarget),
+    ADDSP,
+
+    ADD(ArithmeticTarget),
+    ADC(ArithmeticTarget),
+    SUB(ArithmeticTarget),
+    SBC(ArithmeticTarget),
+    AND(ArithmeticTarget),
+    OR(ArithmeticTarget),
+    XOR(ArithmeticTarget),
+    CP(ArithmeticTarget),
+    SRL(ArithmeticTarget),
+    RR(ArithmeticTarget),
+    RL(ArithmeticTarget),
+    RRC(ArithmeticTarget),
+    RLC(ArithmeticTarget),
+    SRA(ArithmeticTarget),
+    SLA(ArithmeticTarget),
+    SWAP(ArithmeticTarget),
+
+    INC(IncDecTarget),
+    DEC(IncDecTarget),
+
+    CCF,
+    SCF,
+    RRA,
+    RLA,
+    RRCA,
+    RLCA,
+    CPL,
+    DAA,
+    JPI,
+
+    BIT(ArithmeticTarget, BitPosition),
+    RESET(ArithmeticTarget, BitPosition),
+    SET(ArithmeticTarget, BitPosition),
+
+    JP(JumpCondition),
+    JR(JumpCondition),
+    CALL(JumpCondition),
+    RET(JumpCondition),
+    RETI,
+    RST(u8),
+
+    PUSH(StackTarget),
+    POP(StackTarget),
+
+    LD(LoadType),
+
+    NOP,
+    HALT,
+    STOP,
+    DI,
+    EI,
+}
+
+// D8 is only an operand of the arithmetic instructions, not of the prefixed ones
+#[derive(Copy, Clone)]
+pub enum ArithmeticTarget {
+    A,
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    HLI,
+    D8,
+}
+
+pub enum GroupedArithmeticTarget {
+    BC,
+    DE,
+    HL,
+    SP,
+}
+
+pub enum IncDecTarget {
+    A,
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    HLI,
+    BC,
+    DE,
+    HL,
+    SP,
+}
+
+#[derive(Copy, Clone)]
+pub enum BitPosition {
+    B0,
+    B1,
+    B2,
+    B3,
+    B4,
+    B5,
+    B6,
+    B7,
+}
+
+pub enum JumpCondition {
+    NotZero,
+    Zero,
+    NotCarry,
+    Carry,
+    Always,
+}
+
+pub enum LoadType {
+    Byte(LoadByteTarget, LoadByteSrc),
+    Word(LoadWordTarget),
+    AFromIndirect(IndirectSrc),
+    IndirectFromA(IndirectSrc),
+    AFromByteAddress,
+    ByteAddressFromA,
+    SPFromHL,
+    HLFromSPOffset,
+    IndirectFromSP,
+}
+
+pub enum LoadByteTarget {
+    A,
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    HLI,
+}
+
+pub enum LoadByteSrc {
+    A,
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    D8,
+    HLI,
+}
+
+pub enum LoadWordTarget {
+    BC,
+    DE,
+    HL,
+    SP,
+}
+
+pub enum StackTarget {
+    AF,
+    BC,
+    DE,
+    HL,
+}
+
+// D8 is the 16bit address following the opcode
+pub enum IndirectSrc {
+    BC,
+    DE,
+    HLMinus,
+    HLPlus,
+    D8,
+    IOPortC,
+}
+
+// Duration of every unprefixed opcode in T-cycles, conditional branches are not taken
+const OPCODE_CYCLES: [u8; 256] = [
+    4, 12, 8, 8, 4, 4, 8, 4, 20, 8, 8, 8, 4, 4, 8, 4,
+    4, 12, 8, 8, 4, 4, 8, 4, 12, 8, 8, 8, 4, 4, 8, 4,
+    8, 12, 8, 8, 4, 4, 8, 4, 8, 8, 8, 8, 4, 4, 8, 4,
+    8, 12, 8, 8, 12, 12, 12, 4, 8, 8, 8, 8, 4, 4, 8, 4,
+    4, 4, 4, 4, 4, 4, 8, 4, 4, 4, 4, 4, 4, 4, 8, 4,
+    4, 4, 4, 4, 4, 4, 8, 4, 4, 4, 4, 4, 4, 4, 8, 4,
+    4, 4, 4, 4, 4, 4, 8, 4, 4, 4, 4, 4, 4, 4, 8, 4,
+    8, 8, 8, 8, 8, 8, 4, 8, 4, 4, 4, 4, 4, 4, 8, 4,
+    4, 4, 4, 4, 4, 4, 8, 4, 4, 4, 4, 4, 4, 4, 8, 4,
+    4, 4, 4, 4, 4, 4, 8, 4, 4, 4, 4, 4, 4, 4, 8, 4,
+    4, 4, 4, 4, 4, 4, 8, 4, 4, 4, 4, 4, 4, 4, 8, 4,
+    4, 4, 4, 4, 4, 4, 8, 4, 4, 4, 4, 4, 4, 4, 8, 4,
+    8, 12, 12, 16, 12, 16, 8, 16, 8, 16, 12, 4, 12, 24, 8, 16,
+    8, 12, 12, 0, 12, 16, 8, 16, 8, 16, 12, 0, 12, 0, 8, 16,
+    12, 12, 8, 0, 0, 16, 8, 16, 16, 4, 16, 0, 0, 0, 8, 16,
+    12, 12, 8, 4, 0, 16, 8, 16, 12, 8, 16, 4, 0, 0, 8, 16,
+];
+
+impl std::convert::From<BitPosition> for u8 {
+    fn from(bit: BitPosition) -> u8 {
+	match bit {
+	    BitPosition::B0 => 0,
+	    BitPosition::B1 => 1,
+	    BitPosition::B2 => 2,
+	    BitPosition::B3 => 3,
+	    BitPosition::B4 => 4,
+	    BitPosition::B5 => 5,
+	    BitPosition::B6 => 6,
+	    BitPosition::B7 => 7,
+	}
+    }
+}
+
+impl Instruction {
+    // Prefixed opcodes include the 4 T-cycles spent fetching 0xCB
+    pub fn cycles(instruction_byte: u8, is_prefixed: bool) -> u32 {
+	if !is_prefixed {
+	    return OPCODE_CYCLES[instruction_byte as usize] as u32;
+	}
+
+	match (instruction_byte & 0x7, instruction_byte >> 6) {
+	    (6, 1) => 12,
+	    (6, _) => 16,
+	    _ => 8,
+	}
+    }
+
+    // Size in bytes, including the 0xCB prefix and the immediate operands
+    pub fn length(&self, is_prefixed: bool) -> u16 {
+	if is_prefixed {
+	    return 2;
+	}
+
+	match self {
+	    Instruction::ADD(ArithmeticTarget::D8)
+	    | Instruction::ADC(ArithmeticTarget::D8)
+	    | Instruction::SUB(ArithmeticTarget::D8)
+	    | Instruction::SBC(ArithmeticTarget::D8)
+	    | Instruction::AND(ArithmeticTarget::D8)
+	    | Instruction::OR(ArithmeticTarget::D8)
+	    | Instruction::XOR(ArithmeticTarget::D8)
+	    | Instruction::CP(ArithmeticTarget::D8)
+	    | Instruction::ADDSP
+	    | Instruction::JR(_)
+	    | Instruction::STOP
+	    | Instruction::LD(LoadType::Byte(_, LoadByteSrc::D8))
+	    | Instruction::LD(LoadType::AFromByteAddress)
+	    | Instruction::LD(LoadType::ByteAddressFromA)
+	    | Instruction::LD(LoadType::HLFromSPOffset) => 2,
+	    Instruction::JP(_)
+	    | Instruction::CALL(_)
+	    | Instruction::LD(LoadType::Word(_))
+	    | Instruction::LD(LoadType::AFromIndirect(IndirectSrc::D8))
+	    | Instruction::LD(LoadType::IndirectFromA(IndirectSrc::D8))
+	    | Instruction::LD(LoadType::IndirectFromSP) => 3,
+	    _ => 1,
+	}
+    }
+
+    // Extra T-cycles of a conditional JR, JP, CALL or RET when the branch is taken
+    pub fn taken_branch_cycles(instruction_byte: u8) -> u32 {
+	match instruction_byte {
+	    0x20 | 0x28 | 0x30 | 0x38 | 0xC2 | 0xCA | 0xD2 | 0xDA => 4,
+	    0xC0 | 0xC8 | 0xD0 | 0xD8 | 0xC4 | 0xCC | 0xD4 | 0xDC => 12,
+	    _ => 0,
+	}
+    }
+
+    pub fn from_byte(instruction_address: u8, is_prefixed: bool) -> Option<Instruction> {
+	if is_prefixed {
+	    Instruction::from_byte_prefixed(instruction_address)
+	} else {
+	    Instruction::from_byte_not_prefixed(instruction_address)
+	}
+    }
+
+    fn from_byte_prefixed(instruction_address: u8) -> Option<Instruction> {
+	match instruction_address {
+            0x00 => Some(Instruction::RLC(ArithmeticTarget::B)),
+            0x01 => Some(Instruction::RLC(ArithmeticTarget::C)),
+            0x02 => Some(Instruction::RLC(ArithmeticTarget::D)),
+            0x03 => Some(Instruction::RLC(ArithmeticTarget::E)),
+            0x04 => Some(Instruction::RLC(ArithmeticTarget::H)),
+            0x05 => Some(Instruction::RLC(ArithmeticTarget::L)),
+	    0x06 => Some(Instruction::RLC(ArithmeticTarget::HLI)),
+            0x07 => Some(Instruction::RLC(ArithmeticTarget::A)),
+
+            0x08 => Some(Instruction::RRC(ArithmeticTarget::B)),
+            0x09 => Some(Instruction::RRC(ArithmeticTarget::C)),
+            0x0a => Some(Instruction::RRC(ArithmeticTarget::D)),
+            0x0b => Some(Instruction::RRC(ArithmeticTarget::E)),
+            0x0c => Some(Instruction::RRC(ArithmeticTarget::H)),
+            0x0d => Some(Instruction::RRC(ArithmeticTarget::L)),
+	    0x0e => Some(Instruction::RRC(ArithmeticTarget::HLI)),
+            0x0f => Some(Instruction::RRC(ArithmeticTarget::A)),
+
+            0x10 => Some(Instruction::RL(ArithmeticTarget::B)),
+            0x11 => Some(Instruction::RL(ArithmeticTarget::C)),
+            0x12 => Some(Instruction::RL(ArithmeticTarget::D)),
+            0x13 => Some(Instruction::RL(ArithmeticTarget::E)),
+            0x14 => Some(Instruction::RL(ArithmeticTarget::H)),
+            0x15 => Some(Instruction::RL(ArithmeticTarget::L)),
+	    0x16 => Some(Instruction::RL(ArithmeticTarget::HLI)),
+            0x17 => Some(Instruction::RL(ArithmeticTarget::A)),
+
+            0x18 => Some(Instruction::RR(ArithmeticTarget::B)),
+            0x19 => Some(Instruction::RR(ArithmeticTarget::C)),
+            0x1a => Some(Instruction::RR(ArithmeticTarget::D)),
+            0x1b => Some(Instruction::RR(ArithmeticTarget::E)),
+            0x1c => Some(Instruction::RR(ArithmeticTarget::H)),
+            0x1d => Some(Instruction::RR(ArithmeticTarget::L)),
+	    0x1e => Some(Instruction::RR(ArithmeticTarget::HLI)),
+            0x1f => Some(Instruction::RR(ArithmeticTarget::A)),
+
+            0x20 => Some(Instruction::SLA(ArithmeticTarget::B)),
+            0x21 => Some(Instruction::SLA(ArithmeticTarget::C)),
+            0x22 => Some(Instruction::SLA(ArithmeticTarget::D)),
+            0x23 => Some(Instruction::SLA(ArithmeticTarget::E)),
+            0x24 => Some(Instruction::SLA(ArithmeticTarget::H)),
+            0x25 => Some(Instruction::SLA(ArithmeticTarget::L)),
+	    0x26 => Some(Instruction::SLA(ArithmeticTarget::HLI)),
+            0x27 => Some(Instruction::SLA(ArithmeticTarget::A)),
+
+            0x28 => Some(Instruction::SRA(ArithmeticTarget::B)),
+            0x29 => Some(Instruction::SRA(ArithmeticTarget::C)),
+            0x2a => Some(Instruction::SRA(ArithmeticTarget::D)),
+            0x2b => Some(Instruction::SRA(ArithmeticTarget::E)),
+            0x2c => Some(Instruction::SRA(ArithmeticTarget::H)),
+            0x2d => Some(Instruction::SRA(ArithmeticTarget::L)),
+	    0x2e => Some(Instruction::SRA(ArithmeticTarget::HLI)),
+            0x2f => Some(Instruction::SRA(ArithmeticTarget::A)),
+
+            0x30 => Some(Instruction::SWAP(ArithmeticTarget::B)),
+            0x31 => Some(Instruction::SWAP(ArithmeticTarget::C)),
+            0x32 => Some(Instruction::SWAP(ArithmeticTarget::D)),
+            0x33 => Some(Instruction::SWAP(ArithmeticTarget::E)),
+            0x34 => Some(Instruction::SWAP(ArithmeticTarget::H)),
+            0x35 => Some(Instruction::SWAP(ArithmeticTarget::L)),
+	    0x36 => Some(Instruction::SWAP(ArithmeticTarget::HLI)),
+            0x37 => Some(Instruction::SWAP(ArithmeticTarget::A)),
+
+            0x38 => Some(Instruction::SRL(ArithmeticTarget::B)),
+            0x39 => Some(Instruction::SRL(ArithmeticTarget::C)),
+            0x3a => Some(Instruction::SRL(ArithmeticTarget::D)),
+            0x3b => Some(Instruction::SRL(ArithmeticTarget::E)),
+            0x3c => Some(Instruction::SRL(ArithmeticTarget::H)),
+            0x3d => Some(Instruction::SRL(ArithmeticTarget::L)),
+	    0x3e => Some(Instruction::SRL(ArithmeticTarget::HLI)),
+            0x3f => Some(Instruction::SRL(ArithmeticTarget::A)),
+
+            0x40 => Some(Instruction::BIT(ArithmeticTarget::B, BitPosition::B0)),
+            0x41 => Some(Instruction::BIT(ArithmeticTarget::C, BitPosition::B0)),
+            0x42 => Some(Instruction::BIT(ArithmeticTarget::D, BitPosition::B0)),
+            0x43 => Some(Instruction::BIT(ArithmeticTarget::E, BitPosition::B0)),
+            0x44 => Some(Instruction::BIT(ArithmeticTarget::H, BitPosition::B0)),
+            0x45 => Some(Instruction::BIT(ArithmeticTarget::L, BitPosition::B0)),
+	    0x46 => Some(Instruction::BIT(ArithmeticTarget::HLI, BitPosition::B0)),
+            0x47 => Some(Instruction::BIT(ArithmeticTarget::A, BitPosition::B0)),
+
+            0x48 => Some(Instruction::BIT(ArithmeticTarget::B, BitPosition::B1)),
+            0x49 => Some(Instruction::BIT(ArithmeticTarget::C, BitPosition::B1)),
+            0x4a => Some(Instruction::BIT(ArithmeticTarget::D, BitPosition::B1)),
+            0x4b => Some(Instruction::BIT(ArithmeticTarget::E, BitPosition::B1)),
+            0x4c => Some(Instruction::BIT(ArithmeticTarget::H, BitPosition::B1)),
+            0x4d => Some(Instruction::BIT(ArithmeticTarget::L, BitPosition::B1)),
+	    0x4e => Some(Instruction::BIT(ArithmeticTarget::HLI, BitPosition::B1)),
+            0x4f => Some(Instruction::BIT(ArithmeticTarget::A, BitPosition::B1)),
+
+            0x50 => Some(Instruction::BIT(ArithmeticTarget::B, BitPosition::B2)),
+            0x51 => Some(Instruction::BIT(ArithmeticTarget::C, BitPosition::B2)),
+            0x52 => Some(Instruction::BIT(ArithmeticTarget::D, BitPosition::B2)),
+            0x53 => Some(Instruction::BIT(ArithmeticTarget::E, BitPosition::B2)),
+            0x54 => Some(Instruction::BIT(ArithmeticTarget::H, BitPosition::B2)),
+            0x55 => Some(Instruction::BIT(ArithmeticTarget::L, BitPosition::B2)),
+	    0x56 => Some(Instruction::BIT(ArithmeticTarget::HLI, BitPosition::B2)),
+            0x57 => Some(Instruction::BIT(ArithmeticTarget::A, BitPosition::B2)),
+
+            0x58 => Some(Instruction::BIT(ArithmeticTarget::B, BitPosition::B3)),
+            0x59 => Some(Instruction::BIT(ArithmeticTarget::C, BitPosition::B3)),
+            0x5a => Some(Instruction::BIT(ArithmeticTarget::D, BitPosition::B3)),
+            0x5b => Some(Instruction::BIT(ArithmeticTarget::E, BitPosition::B3)),
+            0x5c => Some(Instruction::BIT(ArithmeticTarget::H, BitPosition::B3)),
+            0x5d => Some(Instruction::BIT(ArithmeticTarget::L, BitPosition::B3)),
+	    0x5e => Some(Instruction::BIT(ArithmeticTarget::HLI, BitPosition::B3)),
+            0x5f => Some(Instruction::BIT(ArithmeticTarget::A, BitPosition::B3)),
+
+            0x60 => Some(Instruction::BIT(ArithmeticTarget::B, BitPosition::B4)),
+            0x61 => Some(Instruction::BIT(ArithmeticTarget::C, BitPosition::B4)),
+            0x62 => Some(Instruction::BIT(ArithmeticTarget::D, BitPosition::B4)),
+            0x63 => Some(Instruction::BIT(ArithmeticTarget::E, BitPosition::B4)),
+            0x64 => Some(Instruction::BIT(ArithmeticTarget::H, BitPosition::B4)),
+            0x65 => Some(Instruction::BIT(ArithmeticTarget::L, BitPosition::B4)),
+	    0x66 => Some(Instruction::BIT(ArithmeticTarget::HLI, BitPosition::B4)),
+            0x67 => Some(Instruction::BIT(ArithmeticTarget::A, BitPosition::B4)),
+
+            0x68 => Some(Instruction::BIT(ArithmeticTarget::B, BitPosition::B5)),
+            0x69 => Some(Instruction::BIT(ArithmeticTarget::C, BitPosition::B5)),
+            0x6a => Some(Instruction::BIT(ArithmeticTarget::D, BitPosition::B5)),
+            0x6b => Some(Instruction::BIT(ArithmeticTarget::E, BitPosition::B5)),
+            0x6c => Some(Instruction::BIT(ArithmeticTarget::H, BitPosition::B5)),
+            0x6d => Some(Instruction::BIT(ArithmeticTarget::L, BitPosition::B5)),
+	    0x6e => Some(Instruction::BIT(ArithmeticTarget::HLI, BitPosition::B5)),
+            0x6f => Some(Instruction::BIT(ArithmeticTarget::A, BitPosition::B5)),
+
+            0x70 => Some(Instruction::BIT(ArithmeticTarget::B, BitPosition::B6)),
+            0x71 => Some(Instruction::BIT(ArithmeticTarget::C, BitPosition::B6)),
+            0x72 => Some(Instruction::BIT(ArithmeticTarget::D, BitPosition::B6)),
+            0x73 => Some(Instruction::BIT(ArithmeticTarget::E, BitPosition::B6)),
+            0x74 => Some(Instruction::BIT(ArithmeticTarget::H, BitPosition::B6)),
+            0x75 => Some(Instruction::BIT(ArithmeticTarget::L, BitPosition::B6)),
+	    0x76 => Some(Instruction::BIT(ArithmeticTarget::HLI, BitPosition::B6)),
+            0x77 => Some(Instruction::BIT(ArithmeticTarget::A, BitPosition::B6)),
+
+            0x78 => Some(Instruction::BIT(ArithmeticTarget::B, BitPosition::B7)),
+            0x79 => Some(Instruction::BIT(ArithmeticTarget::C, BitPosition::B7)),
+            0x7a => Some(Instruction::BIT(ArithmeticTarget::D, BitPosition::B7)),
+            0x7b => Some(Instruction::BIT(ArithmeticTarget::E, BitPosition::B7)),
+            0x7c => Some(Instruction::BIT(ArithmeticTarget::H, BitPosition::B7)),
+            0x7d => Some(Instruction::BIT(ArithmeticTarget::L, BitPosition::B7)),
+	    0x7e => Some(Instruction::BIT(ArithmeticTarget::HLI, BitPosition::B7)),
+            0x7f => Some(Instruction::BIT(ArithmeticTarget::A, BitPosition::B7)),
+
+            0x80 => Some(Instruction::RESET(ArithmeticTarget::B, BitPosition::B0)),
+            0x81 => Some(Instruction::RESET(ArithmeticTarget::C, BitPosition::B0)),
+            0x82 => Some(Instruction::RESET(ArithmeticTarget::D, BitPosition::B0)),
+            0x83 => Some(Instruction::RESET(ArithmeticTarget::E, BitPosition::B0)),
+            0x84 => Some(Instruction::RESET(ArithmeticTarget::H, BitPosition::B0)),
+            0x85 => Some(Instruction::RESET(ArithmeticTarget::L, BitPosition::B0)),
+	    0x86 => Some(Instruction::RESET(ArithmeticTarget::HLI, BitPosition::B0)),
+            0x87 => Some(Instruction::RESET(ArithmeticTarget::A, BitPosition::B0)),
+
+            0x88 => Some(Instruction::RESET(ArithmeticTarget::B, BitPosition::B1)),
+            0x89 => Some(Instruction::RESET(ArithmeticTarget::C, BitPosition::B1)),
+            0x8a => Some(Instruction::RESET(ArithmeticTarget::D, BitPosition::B1)),
+            0x8b => Some(Instruction::RESET(ArithmeticTarget::E, BitPosition::B1)),
+            0x8c => Some(Instruction::RESET(ArithmeticTarget::H, BitPosition::B1)),
+            0x8d => Some(Instruction::RESET(ArithmeticTarget::L, BitPosition::B1)),
+	    0x8e => Some(Instruction::RESET(ArithmeticTarget::HLI, BitPosition::B1)),
+            0x8f => Some(Instruction::RESET(ArithmeticTarget::A, BitPosition::B1)),
+
+            0x90 => Some(Instruction::RESET(ArithmeticTarget::B, BitPosition::B2)),
+            0x91 => Some(Instruction::RESET(ArithmeticTarget::C, BitPosition::B2)),
+            0x92 => Some(Instruction::RESET(ArithmeticTarget::D, BitPosition::B2)),
+            0x93 => Some(Instruction::RESET(ArithmeticTarget::E, BitPosition::B2)),
+            0x94 => Some(Instruction::RESET(ArithmeticTarget::H, BitPosition::B2)),
+            0x95 => Some(Instruction::RESET(ArithmeticTarget::L, BitPosition::B2)),
+	    0x96 => Some(Instruction::RESET(ArithmeticTarget::HLI, BitPosition::B2)),
+            0x97 => Some(Instruction::RESET(ArithmeticTarget::A, BitPosition::B2)),
+
+            0x98 => Some(Instruction::RESET(ArithmeticTarget::B, BitPosition::B3)),
+            0x99 => Some(Instruction::RESET(ArithmeticTarget::C, BitPosition::B3)),
+            0x9a => Some(Instruction::RESET(ArithmeticTarget::D, BitPosition::B3)),
+            0x9b => Some(Instruction::RESET(ArithmeticTarget::E, BitPosition::B3)),
+            0x9c => Some(Instruction::RESET(ArithmeticTarget::H, BitPosition::B3)),
+            0x9d => Some(Instruction::RESET(ArithmeticTarget::L, BitPosition::B3)),
+	    0x9e => Some(Instruction::RESET(ArithmeticTarget::HLI, BitPosition::B3)),
+            0x9f => Some(Instruction::RESET(ArithmeticTarget::A, BitPosition::B3)),
+
+            0xa0 => Some(Instruction::RESET(ArithmeticTarget::B, BitPosition::B4)),
+            0xa1 => Some(Instruction::RESET(ArithmeticTarget::C, BitPosition::B4)),
+            0xa2 => Some(Instruction::RESET(ArithmeticTarget::D, BitPosition::B4)),
+            0xa3 => Some(Instruction::RESET(ArithmeticTarget::E, BitPosition::B4)),
+            0xa4 => Some(Instruction::RESET(ArithmeticTarget::H, BitPosition::B4)),
+            0xa5 => Some(Instruction::RESET(ArithmeticTarget::L, BitPosition::B4)),
+	    0xa6 => Some(Instruction::RESET(ArithmeticTarget::HLI, BitPosition::B4)),
+            0xa7 => Some(Instruction::RESET(ArithmeticTarget::A, BitPosition::B4)),
+
+            0xa8 => Some(Instruction::RESET(ArithmeticTarget::B, BitPosition::B5)),
+            0xa9 => Some(Instruction::RESET(ArithmeticTarget::C, BitPosition::B5)),
+            0xaa => Some(Instruction::RESET(ArithmeticTarget::D, BitPosition::B5)),
+            0xab => Some(Instruction::RESET(ArithmeticTarget::E, BitPosition::B5)),
+            0xac => Some(Instruction::RESET(ArithmeticTarget::H, BitPosition::B5)),
+            0xad => Some(Instruction::RESET(ArithmeticTarget::L, BitPosition::B5)),
+	    0xae => Some(Instruction::RESET(ArithmeticTarget::HLI, BitPosition::B5)),
+            0xaf => Some(Instruction::RESET(ArithmeticTarget::A, BitPosition::B5)),
+
+            0xb0 => Some(Instruction::RESET(ArithmeticTarget::B, BitPosition::B6)),
+            0xb1 => Some(Instruction::RESET(ArithmeticTarget::C, BitPosition::B6)),
+            0xb2 => Some(Instruction::RESET(ArithmeticTarget::D, BitPosition::B6)),
+            0xb3 => Some(Instruction::RESET(ArithmeticTarget::E, BitPosition::B6)),
+            0xb4 => Some(Instruction::RESET(ArithmeticTarget::H, BitPosition::B6)),
+            0xb5 => Some(Instruction::RESET(ArithmeticTarget::L, BitPosition::B6)),
+	    0xb6 => Some(Instruction::RESET(ArithmeticTarget::HLI, BitPosition::B6)),
+            0xb7 => Some(Instruction::RESET(ArithmeticTarget::A, BitPosition::B6)),
+
+            0xb8 => Some(Instruction::RESET(ArithmeticTarget::B, BitPosition::B7)),
+            0xb9 => Some(Instruction::RESET(ArithmeticTarget::C, BitPosition::B7)),
+            0xba => Some(Instruction::RESET(ArithmeticTarget::D, BitPosition::B7)),
+            0xbb => Some(Instruction::RESET(ArithmeticTarget::E, BitPosition::B7)),
+            0xbc => Some(Instruction::RESET(ArithmeticTarget::H, BitPosition::B7)),
+            0xbd => Some(Instruction::RESET(ArithmeticTarget::L, BitPosition::B7)),
+	    0xbe => Some(Instruction::RESET(ArithmeticTarget::HLI, BitPosition::B7)),
+            0xbf => Some(Instruction::RESET(ArithmeticTarget::A, BitPosition::B7)),
+
+            0xc0 => Some(Instruction::SET(ArithmeticTarget::B, BitPosition::B0)),
+            0xc1 => Some(Instruction::SET(ArithmeticTarget::C, BitPosition::B0)),
+            0xc2 => Some(Instruction::SET(ArithmeticTarget::D, BitPosition::B0)),
+            0xc3 => Some(Instruction::SET(ArithmeticTarget::E, BitPosition::B0)),
+            0xc4 => Some(Instruction::SET(ArithmeticTarget::H, BitPosition::B0)),
+            0xc5 => Some(Instruction::SET(ArithmeticTarget::L, BitPosition::B0)),
+	    0xc6 => Some(Instruction::SET(ArithmeticTarget::HLI, BitPosition::B0)),
+            0xc7 => Some(Instruction::SET(ArithmeticTarget::A, BitPosition::B0)),
+
+            0xc8 => Some(Instruction::SET(ArithmeticTarget::B, BitPosition::B1)),
+            0xc9 => Some(Instruction::SET(ArithmeticTarget::C, BitPosition::B1)),
+            0xca => Some(Instruction::SET(ArithmeticTarget::D, BitPosition::B1)),
+            0xcb => Some(Instruction::SET(ArithmeticTarget::E, BitPosition::B1)),
+            0xcc => Some(Instruction::SET(ArithmeticTarget::H, BitPosition::B1)),
+            0xcd => Some(Instruction::SET(ArithmeticTarget::L, BitPosition::B1)),
+	    0xce => Some(Instruction::SET(ArithmeticTarget::HLI, BitPosition::B1)),
+            0xcf => Some(Instruction::SET(ArithmeticTarget::A, BitPosition::B1)),
+
+            0xd0 => Some(Instruction::SET(ArithmeticTarget::B, BitPosition::B2)),
+            0xd1 => Some(Instruction::SET(ArithmeticTarget::C, BitPosition::B2)),
+            0xd2 => Some(Instruction::SET(ArithmeticTarget::D, BitPosition::B2)),
+            0xd3 => Some(Instruction::SET(ArithmeticTarget::E, BitPosition::B2)),
+            0xd4 => Some(Instruction::SET(ArithmeticTarget::H, BitPosition::B2)),
+            0xd5 => Some(Instruction::SET(ArithmeticTarget::L, BitPosition::B2)),
+	    0xd6 => Some(Instruction::SET(ArithmeticTarget::HLI, BitPosition::B2)),
+            0xd7 => Some(Instruction::SET(ArithmeticTarget::A, BitPosition::B2)),
+
+            0xd8 => Some(Instruction::SET(ArithmeticTarget::B, BitPosition::B3)),
+            0xd9 => Some(Instruction::SET(ArithmeticTarget::C, BitPosition::B3)),
+            0xda => Some(Instruction::SET(ArithmeticTarget::D, BitPosition::B3)),
+            0xdb => Some(Instruction::SET(ArithmeticTarget::E, BitPosition::B3)),
+            0xdc => Some(Instruction::SET(ArithmeticTarget::H, BitPosition::B3)),
+            0xdd => Some(Instruction::SET(ArithmeticTarget::L, BitPosition::B3)),
+	    0xde => Some(Instruction::SET(ArithmeticTarget::HLI, BitPosition::B3)),
+            0xdf => Some(Instruction::SET(ArithmeticTarget::A, BitPosition::B3)),
+
+            0xe0 => Some(Instruction::SET(ArithmeticTarget::B, BitPosition::B4)),
+            0xe1 => Some(Instruction::SET(ArithmeticTarget::C, BitPosition::B4)),
+            0xe2 => Some(Instruction::SET(ArithmeticTarget::D, BitPosition::B4)),
+            0xe3 => Some(Instruction::SET(ArithmeticTarget::E, BitPosition::B4)),
+            0xe4 => Some(Instruction::SET(ArithmeticTarget::H, BitPosition::B4)),
+            0xe5 => Some(Instruction::SET(ArithmeticTarget::L, BitPosition::B4)),
+	    0xe6 => Some(Instruction::SET(ArithmeticTarget::HLI, BitPosition::B4)),
+            0xe7 => Some(Instruction::SET(ArithmeticTarget::A, BitPosition::B4)),
+
+            0xe8 => Some(Instruction::SET(ArithmeticTarget::B, BitPosition::B5)),
+            0xe9 => Some(Instruction::SET(ArithmeticTarget::C, BitPosition::B5)),
+            0xea => Some(Instruction::SET(ArithmeticTarget::D, BitPosition::B5)),
+            0xeb => Some(Instruction::SET(ArithmeticTarget::E, BitPosition::B5)),
+            0xec => Some(Instruction::SET(ArithmeticTarget::H, BitPosition::B5)),
+            0xed => Some(Instruction::SET(ArithmeticTarget::L, BitPosition::B5)),
+	    0xee => Some(Instruction::SET(ArithmeticTarget::HLI, BitPosition::B5)),
+            0xef => Some(Instruction::SET(ArithmeticTarget::A, BitPosition::B5)),
+
+            0xf0 => Some(Instruction::SET(ArithmeticTarget::B, BitPosition::B6)),
+            0xf1 => Some(Instruction::SET(ArithmeticTarget::C, BitPosition::B6)),
+            0xf2 => Some(Instruction::SET(ArithmeticTarget::D, BitPosition::B6)),
+            0xf3 => Some(Instruction::SET(ArithmeticTarget::E, BitPosition::B6)),
+            0xf4 => Some(Instruction::SET(ArithmeticTarget::H, BitPosition::B6)),
+            0xf5 => Some(Instruction::SET(ArithmeticTarget::L, BitPosition::B6)),
+	    0xf6 => Some(Instruction::SET(ArithmeticTarget::HLI, BitPosition::B6)),
+            0xf7 => Some(Instruction::SET(ArithmeticTarget::A, BitPosition::B6)),
+
+            0xf8 => Some(Instruction::SET(ArithmeticTarget::B, BitPosition::B7)),
+            0xf9 => Some(Instruction::SET(ArithmeticTarget::C, BitPosition::B7)),
+            0xfa => Some(Instruction::SET(ArithmeticTarget::D, BitPosition::B7)),
+            0xfb => Some(Instruction::SET(ArithmeticTarget::E, BitPosition::B7)),
+            0xfc => Some(Instruction::SET(ArithmeticTarget::H, BitPosition::B7)),
+            0xfd => Some(Instruction::SET(ArithmeticTarget::L, BitPosition::B7)),
+	    0xfe => Some(Instruction::SET(ArithmeticTarget::HLI, BitPosition::B7)),
+            0xff => Some(Instruction::SET(ArithmeticTarget::A, BitPosition::B7)),
+        }
+    }
+
+    fn from_byte_not_prefixed(instruction_address: u8) -> Option<Instruction> {
+	match instruction_address {
+	    0x00 => Some(Instruction::NOP),
+	    0x01 => Some(Instruction::LD(LoadType::Word(LoadWordTarget::BC))),
+	    0x02 => Some(Instruction::LD(LoadType::IndirectFromA(IndirectSrc::BC))),
+	    0x03 => Some(Instruction::INC(IncDecTarget::BC)),
+	    0x04 => Some(Instruction::INC(IncDecTarget::B)),
+	    0x05 => Some(Instruction::DEC(IncDecTarget::B)),
+	    0x06 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::B, LoadByteSrc::D8))),
+	    0x07 => Some(Instruction::RLCA),
+
+	    0x08 => Some(Instruction::LD(LoadType::IndirectFromSP)),
+	    0x09 => Some(Instruction::ADDHL(GroupedArithmeticTarget::BC)),
+	    0x0a => Some(Instruction::LD(LoadType::AFromIndirect(IndirectSrc::BC))),
+	    0x0b => Some(Instruction::DEC(IncDecTarget::BC)),
+	    0x0c => Some(Instruction::INC(IncDecTarget::C)),
+	    0x0d => Some(Instruction::DEC(IncDecTarget::C)),
+	    0x0e => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::C, LoadByteSrc::D8))),
+	    0x0f => Some(Instruction::RRCA),
+
+	    0x10 => Some(Instruction::STOP),
+	    0x11 => Some(Instruction::LD(LoadType::Word(LoadWordTarget::DE))),
+	    0x12 => Some(Instruction::LD(LoadType::IndirectFromA(IndirectSrc::DE))),
+	    0x13 => Some(Instruction::INC(IncDecTarget::DE)),
+	    0x14 => Some(Instruction::INC(IncDecTarget::D)),
+	    0x15 => Some(Instruction::DEC(IncDecTarget::D)),
+	    0x16 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::D, LoadByteSrc::D8))),
+	    0x17 => Some(Instruction::RLA),
+
+	    0x18 => Some(Instruction::JR(JumpCondition::Always)),
+	    0x19 => Some(Instruction::ADDHL(GroupedArithmeticTarget::DE)),
+	    0x1a => Some(Instruction::LD(LoadType::AFromIndirect(IndirectSrc::DE))),
+	    0x1b => Some(Instruction::DEC(IncDecTarget::DE)),
+	    0x1c => Some(Instruction::INC(IncDecTarget::E)),
+	    0x1d => Some(Instruction::DEC(IncDecTarget::E)),
+	    0x1e => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::E, LoadByteSrc::D8))),
+	    0x1f => Some(Instruction::RRA),
+
+	    0x20 => Some(Instruction::JR(JumpCondition::NotZero)),
+	    0x21 => Some(Instruction::LD(LoadType::Word(LoadWordTarget::HL))),
+	    0x22 => Some(Instruction::LD(LoadType::IndirectFromA(IndirectSrc::HLPlus))),
+	    0x23 => Some(Instruction::INC(IncDecTarget::HL)),
+	    0x24 => Some(Instruction::INC(IncDecTarget::H)),
+	    0x25 => Some(Instruction::DEC(IncDecTarget::H)),
+	    0x26 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::H, LoadByteSrc::D8))),
+	    0x27 => Some(Instruction::DAA),
+
+	    0x28 => Some(Instruction::JR(JumpCondition::Zero)),
+	    0x29 => Some(Instruction::ADDHL(GroupedArithmeticTarget::HL)),
+	    0x2a => Some(Instruction::LD(LoadType::AFromIndirect(IndirectSrc::HLPlus))),
+	    0x2b => Some(Instruction::DEC(IncDecTarget::HL)),
+	    0x2c => Some(Instruction::INC(IncDecTarget::L)),
+	    0x2d => Some(Instruction::DEC(IncDecTarget::L)),
+	    0x2e => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::L, LoadByteSrc::D8))),
+	    0x2f => Some(Instruction::CPL),
+
+	    0x30 => Some(Instruction::JR(JumpCondition::NotCarry)),
+	    0x31 => Some(Instruction::LD(LoadType::Word(LoadWordTarget::SP))),
+	    0x32 => Some(Instruction::LD(LoadType::IndirectFromA(IndirectSrc::HLMinus))),
+	    0x33 => Some(Instruction::INC(IncDecTarget::SP)),
+	    0x34 => Some(Instruction::INC(IncDecTarget::HLI)),
+	    0x35 => Some(Instruction::DEC(IncDecTarget::HLI)),
+	    0x36 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::HLI, LoadByteSrc::D8))),
+	    0x37 => Some(Instruction::SCF),
+
+	    0x38 => Some(Instruction::JR(JumpCondition::Carry)),
+	    0x39 => Some(Instruction::ADDHL(GroupedArithmeticTarget::SP)),
+	    0x3a => Some(Instruction::LD(LoadType::AFromIndirect(IndirectSrc::HLMinus))),
+	    0x3b => Some(Instruction::DEC(IncDecTarget::SP)),
+	    0x3c => Some(Instruction::INC(IncDecTarget::A)),
+	    0x3d => Some(Instruction::DEC(IncDecTarget::A)),
+	    0x3e => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A, LoadByteSrc::D8))),
+	    0x3f => Some(Instruction::CCF),
+
+	    0x40 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::B, LoadByteSrc::B))),
+	    0x41 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::B, LoadByteSrc::C))),
+	    0x42 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::B, LoadByteSrc::D))),
+	    0x43 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::B, LoadByteSrc::E))),
+	    0x44 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::B, LoadByteSrc::H))),
+	    0x45 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::B, LoadByteSrc::L))),
+	    0x46 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::B, LoadByteSrc::HLI))),
+	    0x47 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::B, LoadByteSrc::A))),
+
+	    0x48 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::C, LoadByteSrc::B))),
+	    0x49 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::C, LoadByteSrc::C))),
+	    0x4a => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::C, LoadByteSrc::D))),
+	    0x4b => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::C, LoadByteSrc::E))),
+	    0x4c => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::C, LoadByteSrc::H))),
+	    0x4d => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::C, LoadByteSrc::L))),
+	    0x4e => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::C, LoadByteSrc::HLI))),
+	    0x4f => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::C, LoadByteSrc::A))),
+
+	    0x50 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::D, LoadByteSrc::B))),
+	    0x51 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::D, LoadByteSrc::C))),
+	    0x52 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::D, LoadByteSrc::D))),
+	    0x53 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::D, LoadByteSrc::E))),
+	    0x54 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::D, LoadByteSrc::H))),
+	    0x55 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::D, LoadByteSrc::L))),
+	    0x56 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::D, LoadByteSrc::HLI))),
+	    0x57 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::D, LoadByteSrc::A))),
+
+	    0x58 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::E, LoadByteSrc::B))),
+	    0x59 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::E, LoadByteSrc::C))),
+	    0x5a => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::E, LoadByteSrc::D))),
+	    0x5b => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::E, LoadByteSrc::E))),
+	    0x5c => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::E, LoadByteSrc::H))),
+	    0x5d => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::E, LoadByteSrc::L))),
+	    0x5e => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::E, LoadByteSrc::HLI))),
+	    0x5f => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::E, LoadByteSrc::A))),
+
+	    0x60 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::H, LoadByteSrc::B))),
+	    0x61 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::H, LoadByteSrc::C))),
+	    0x62 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::H, LoadByteSrc::D))),
+	    0x63 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::H, LoadByteSrc::E))),
+	    0x64 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::H, LoadByteSrc::H))),
+	    0x65 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::H, LoadByteSrc::L))),
+	    0x66 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::H, LoadByteSrc::HLI))),
+	    0x67 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::H, LoadByteSrc::A))),
+
+	    0x68 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::L, LoadByteSrc::B))),
+	    0x69 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::L, LoadByteSrc::C))),
+	    0x6a => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::L, LoadByteSrc::D))),
+	    0x6b => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::L, LoadByteSrc::E))),
+	    0x6c => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::L, LoadByteSrc::H))),
+	    0x6d => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::L, LoadByteSrc::L))),
+	    0x6e => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::L, LoadByteSrc::HLI))),
+	    0x6f => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::L, LoadByteSrc::A))),
+
+	    0x70 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::HLI, LoadByteSrc::B))),
+	    0x71 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::HLI, LoadByteSrc::C))),
+	    0x72 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::HLI, LoadByteSrc::D))),
+	    0x73 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::HLI, LoadByteSrc::E))),
+	    0x74 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::HLI, LoadByteSrc::H))),
+	    0x75 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::HLI, LoadByteSrc::L))),
+	    0x76 => Some(Instruction::HALT),
+	    0x77 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::HLI, LoadByteSrc::A))),
+
+	    0x78 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A, LoadByteSrc::B))),
+	    0x79 => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A, LoadByteSrc::C))),
+	    0x7a => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A, LoadByteSrc::D))),
+	    0x7b => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A, LoadByteSrc::E))),
+	    0x7c => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A, LoadByteSrc::H))),
+	    0x7d => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A, LoadByteSrc::L))),
+	    0x7e => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A, LoadByteSrc::HLI))),
+	    0x7f => Some(Instruction::LD(LoadType::Byte(LoadByteTarget::A, LoadByteSrc::A))),
+
+	    0x80 => Some(Instruction::ADD(ArithmeticTarget::B)),
+	    0x81 => Some(Instruction::ADD(ArithmeticTarget::C)),
+	    0x82 => Some(Instruction::ADD(ArithmeticTarget::D)),
+	    0x83 => Some(Instruction::ADD(ArithmeticTarget::E)),
+	    0x84 => Some(Instruction::ADD(ArithmeticTarget::H)),
+	    0x85 => Some(Instruction::ADD(ArithmeticTarget::L)),
+	    0x86 => Some(Instruction::ADD(ArithmeticTarget::HLI)),
+	    0x87 => Some(Instruction::ADD(ArithmeticTarget::A)),
+
+	    0x88 => Some(Instruction::ADC(ArithmeticTarget::B)),
+	    0x89 => Some(Instruction::ADC(ArithmeticTarget::C)),
+	    0x8a => Some(Instruction::ADC(ArithmeticTarget::D)),
+	    0x8b => Some(Instruction::ADC(ArithmeticTarget::E)),
+	    0x8c => Some(Instruction::ADC(ArithmeticTarget::H)),
+	    0x8d => Some(Instruction::ADC(ArithmeticTarget::L)),
+	    0x8e => Some(Instruction::ADC(ArithmeticTarget::HLI)),
+	    0x8f => Some(Instruction::ADC(ArithmeticTarget::A)),
+
+	    0x90 => Some(Instruction::SUB(ArithmeticTarget::B)),
+	    0x91 => Some(Instruction::SUB(ArithmeticTarget::C)),
+	    0x92 => Some(Instruction::SUB(ArithmeticTarget::D)),
+	    0x93 => Some(Instruction::SUB(ArithmeticTarget::E)),
+	    0x94 => Some(Instruction::SUB(ArithmeticTarget::H)),
+	    0x95 => Some(Instruction::SUB(ArithmeticTarget::L)),
+	    0x96 => Some(Instruction::SUB(ArithmeticTarget::HLI)),
+	    0x97 => Some(Instruction::SUB(ArithmeticTarget::A)),
+
+	    0x98 => Some(Instruction::SBC(ArithmeticTarget::B)),
+	    0x99 => Some(Instruction::SBC(ArithmeticTarget::C)),
+	    0x9a => Some(Instruction::SBC(ArithmeticTarget::D)),
+	    0x9b => Some(Instruction::SBC(ArithmeticTarget::E)),
+	    0x9c => Some(Instruction::SBC(ArithmeticTarget::H)),
+	    0x9d => Some(Instruction::SBC(ArithmeticTarget::L)),
+	    0x9e => Some(Instruction::SBC(ArithmeticTarget::HLI)),
+	    0x9f => Some(Instruction::SBC(ArithmeticTarget::A)),
+
+	    0xa0 => Some(Instruction::AND(ArithmeticTarget::B)),
+	    0xa1 => Some(Instruction::AND(ArithmeticTarget::C)),
+	    0xa2 => Some(Instruction::AND(ArithmeticTarget::D)),
+	    0xa3 => Some(Instruction::AND(ArithmeticTarget::E)),
+	    0xa4 => Some(Instruction::AND(ArithmeticTarget::H)),
+	    0xa5 => Some(Instruction::AND(ArithmeticTarget::L)),
+	    0xa6 => Some(Instruction::AND(ArithmeticTarget::HLI)),
+	    0xa7 => Some(Instruction::AND(ArithmeticTarget::A)),
+
+	    0xa8 => Some(Instruction::XOR(ArithmeticTarget::B)),
+	    0xa9 => Some(Instruction::XOR(ArithmeticTarget::C)),
+	    0xaa => Some(Instruction::XOR(ArithmeticTarget::D)),
+	    0xab => Some(Instruction::XOR(ArithmeticTarget::E)),
+	    0xac => Some(Instruction::XOR(ArithmeticTarget::H)),
+	    0xad => Some(Instruction::XOR(ArithmeticTarget::L)),
+	    0xae => Some(Instruction::XOR(ArithmeticTarget::HLI)),
+	    0xaf => Some(Instruction::XOR(ArithmeticTarget::A)),
+
+	    0xb0 => Some(Instruction::OR(ArithmeticTarget::B)),
+	    0xb1 => Some(Instruction::OR(ArithmeticTarget::C)),
+	    0xb2 => Some(Instruction::OR(ArithmeticTarget::D)),
+	    0xb3 => Some(Instruction::OR(ArithmeticTarget::E)),
+	    0xb4 => Some(Instruction::OR(ArithmeticTarget::H)),
+	    0xb5 => Some(Instruction::OR(ArithmeticTarget::L)),
+	    0xb6 => Some(Instruction::OR(ArithmeticTarget::HLI)),
+	    0xb7 => Some(Instruction::OR(ArithmeticTarget::A)),
+
+	    0xb8 => Some(Instruction::CP(ArithmeticTarget::B)),
+	    0xb9 => Some(Instruction::CP(ArithmeticTarget::C)),
+	    0xba => Some(Instruction::CP(ArithmeticTarget::D)),
+	    0xbb => Some(Instruction::CP(ArithmeticTarget::E)),
+	    0xbc => Some(Instruction::CP(ArithmeticTarget::H)),
+	    0xbd => Some(Instruction::CP(ArithmeticTarget::L)),
+	    0xbe => Some(Instruction::CP(ArithmeticTarget::HLI)),
+	    0xbf => Some(Instruction::CP(ArithmeticTarget::A)),
+
+	    0xc0 => Some(Instruction::RET(JumpCondition::NotZero)),
+	    0xc1 => Some(Instruction::POP(StackTarget::BC)),
+	    0xc2 => Some(Instruction::JP(JumpCondition::NotZero)),
+	    0xc3 => Some(Instruction::JP(JumpCondition::Always)),
+	    0xc4 => Some(Instruction::CALL(JumpCondition::NotZero)),
+	    0xc5 => Some(Instruction::PUSH(StackTarget::BC)),
+	    0xc6 => Some(Instruction::ADD(ArithmeticTarget::D8)),
+	    0xc7 => Some(Instruction::RST(0x00)),
+
+	    0xc8 => Some(Instruction::RET(JumpCondition::Zero)),
+	    0xc9 => Some(Instruction::RET(JumpCondition::Always)),
+	    0xca => Some(Instruction::JP(JumpCondition::Zero)),
+	    0xcc => Some(Instruction::CALL(JumpCondition::Zero)),
+	    0xcd => Some(Instruction::CALL(JumpCondition::Always)),
+	    0xce => Some(Instruction::ADC(ArithmeticTarget::D8)),
+	    0xcf => Some(Instruction::RST(0x08)),
+
+	    0xd0 => Some(Instruction::RET(JumpCondition::NotCarry)),
+	    0xd1 => Some(Instruction::POP(StackTarget::DE)),
+	    0xd2 => Some(Instruction::JP(JumpCondition::NotCarry)),
+	    0xd4 => Some(Instruction::CALL(JumpCondition::NotCarry)),
+	    0xd5 => Some(Instruction::PUSH(StackTarget::DE)),
+	    0xd6 => Some(Instruction::SUB(ArithmeticTarget::D8)),
+	    0xd7 => Some(Instruction::RST(0x10)),
+
+	    0xd8 => Some(Instruction::RET(JumpCondition::Carry)),
+	    0xd9 => Some(Instruction::RETI),
+	    0xda => Some(Instruction::JP(JumpCondition::Carry)),
+	    0xdc => Some(Instruction::CALL(JumpCondition::Carry)),
+	    0xde => Some(Instruction::SBC(ArithmeticTarget::D8)),
+	    0xdf => Some(Instruction::RST(0x18)),
+
+	    0xe0 => Some(Instruction::LD(LoadType::ByteAddressFromA)),
+	    0xe1 => Some(Instruction::POP(StackTarget::HL)),
+	    0xe2 => Some(Instruction::LD(LoadType::IndirectFromA(IndirectSrc::IOPortC))),
+	    0xe5 => Some(Instruction::PUSH(StackTarget::HL)),
+	    0xe6 => Some(Instruction::AND(ArithmeticTarget::D8)),
+	    0xe7 => Some(Instruction::RST(0x20)),
+
+	    0xe8 => Some(Instruction::ADDSP),
+	    0xe9 => Some(Instruction::JPI),
+	    0xea => Some(Instruction::LD(LoadType::IndirectFromA(IndirectSrc::D8))),
+	    0xee => Some(Instruction::XOR(ArithmeticTarget::D8)),
+	    0xef => Some(Instruction::RST(0x28)),
+
+	    0xf0 => Some(Instruction::LD(LoadType::AFromByteAddress)),
+	    0xf1 => Some(Instruction::POP(StackTarget::AF)),
+	    0xf2 => Some(Instruction::LD(LoadType::AFromIndirect(IndirectSrc::IOPortC))),
+	    0xf3 => Some(Instruction::DI),
+	    0xf5 => Some(Instruction::PUSH(StackTarget::AF)),
+	    0xf6 => Some(Instruction::OR(ArithmeticTarget::D8)),
+	    0xf7 => Some(Instruction::RST(0x30)),
+
+	    0xf8 => Some(Instruction::LD(LoadType::HLFromSPOffset)),
+	    0xf9 => Some(Instruction::LD(LoadType::SPFromHL)),
+	    0xfa => Some(Instruction::LD(LoadType::AFromIndirect(IndirectSrc::D8))),
+	    0xfb => Some(Instruction::EI),
+	    0xfe => Some(Instruction::CP(ArithmeticTarget::D8)),
+	    0xff => Some(Instruction::RST(0x38)),
+
+	    _ => None
+	}
+    }
+}
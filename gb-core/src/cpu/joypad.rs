@@ -0,0 +1,118 @@
+use crate::cpu::savestate::{SaveState, StateReader, StateWriter};
+
+pub const JOYPAD_INTERRUPT: u8 = 0b10000;
+
+// The SGB lets up to 4 controllers be read through the same register
+pub const MAX_PLAYERS: usize = 4;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Button {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+impl Button {
+    // Directions are read through P14 and buttons through P15, both in the lower nibble
+    fn mask(&self) -> u8 {
+	match self {
+	    Button::Right | Button::A => 0b0001,
+	    Button::Left | Button::B => 0b0010,
+	    Button::Up | Button::Select => 0b0100,
+	    Button::Down | Button::Start => 0b1000,
+	}
+    }
+
+    fn is_direction(&self) -> bool {
+	matches!(self, Button::Right | Button::Left | Button::Up | Button::Down)
+    }
+}
+
+// P1/JOYP (0xFF00). Bits are active low, both for the selected lines and the pressed keys.
+pub struct Joypad {
+    select: u8,
+    directions: [u8; MAX_PLAYERS],
+    buttons: [u8; MAX_PLAYERS],
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+	Joypad {
+	    select: 0x30,
+	    directions: [0; MAX_PLAYERS],
+	    buttons: [0; MAX_PLAYERS],
+	}
+    }
+
+    pub fn select(&self) -> u8 {
+	self.select
+    }
+
+    // With no line selected the SGB returns the id of the current controller,
+    // which is 0xF for the first one and what games check to detect it.
+    pub fn read(&self, player: usize) -> u8 {
+	let mut keys = 0x0F;
+
+	if (self.select & 0x10) == 0 {
+	    keys &= !self.directions[player];
+	}
+	if (self.select & 0x20) == 0 {
+	    keys &= !self.buttons[player];
+	}
+	if (self.select & 0x30) == 0x30 {
+	    keys = 0x0F - player as u8;
+	}
+
+	0xC0 | self.select | keys
+    }
+
+    pub fn write(&mut self, value: u8) {
+	self.select = value & 0x30;
+    }
+
+    // Returns true when the key was just pressed, which requests a joypad interrupt
+    pub fn press(&mut self, player: usize, button: Button) -> bool {
+	let keys = self.keys(player, button);
+	let pressed = (*keys & button.mask()) == 0;
+
+	*keys |= button.mask();
+	pressed
+    }
+
+    pub fn release(&mut self, player: usize, button: Button) {
+	let keys = self.keys(player, button);
+	*keys &= !button.mask();
+    }
+
+    pub fn is_pressed(&self, player: usize, button: Button) -> bool {
+	let keys = if button.is_direction() { self.directions } else { self.buttons };
+	(keys[player % MAX_PLAYERS] & button.mask()) != 0
+    }
+
+    fn keys(&mut self, player: usize, button: Button) -> &mut u8 {
+	if button.is_direction() {
+	    &mut self.directions[player % MAX_PLAYERS]
+	} else {
+	    &mut self.buttons[player % MAX_PLAYERS]
+	}
+    }
+}
+
+impl SaveState for Joypad {
+    fn save_state(&self, writer: &mut StateWriter) {
+	writer.u8(self.select);
+	writer.bytes(&self.directions);
+	writer.bytes(&self.buttons);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), &'static str> {
+	self.select = reader.u8()?;
+	reader.bytes(&mut self.directions)?;
+	reader.bytes(&mut self.buttons)
+    }
+}
@@ -0,0 +1,477 @@
+use crate::cpu::{
+    apu::Apu,
+    bess::{self, BessWriter, Core},
+    boot::BootRom,
+    cartridge::Cartridge,
+    hdma::{Hdma, HdmaMode, HDMA_BLOCK_DOTS, HDMA_BLOCK_LENGTH},
+    joypad::{Button, Joypad, JOYPAD_INTERRUPT},
+    model::Model,
+    oam_dma::OamDma,
+    ppu::{Ppu, PpuMode, SCREEN_HEIGHT, SCREEN_WIDTH},
+    serial::Serial,
+    sgb::{Sgb, SGB_SCREEN_HEIGHT, SGB_SCREEN_WIDTH},
+    timer::Timer,
+    savestate::{copy_prefix, SaveState, StateReader, StateWriter},
+    watchpoints::{Access, Watchpoints},
+};
+
+// 2050 M-cycles
+const SPEED_SWITCH_CYCLES: u32 = 8200;
+
+// What the CPU sees of the rest of the console
+pub trait Bus {
+    fn read_byte(&self, address: u16) -> u8;
+    fn write_byte(&mut self, address: u16, value: u8);
+    // For the CPU polling IE and IF, which shouldn't count as a program read
+    fn peek_byte(&self, address: u16) -> u8 {
+	self.read_byte(address)
+    }
+    // Advances the hardware and returns the T-cycles that actually elapsed
+    fn tick(&mut self, cycles: u32) -> u32;
+    // STOP switches between the CGB speeds once KEY1 has been armed
+    fn switch_speed(&mut self) {}
+}
+
+pub struct MemoryBus {
+    memory: [u8; 0x10000],
+    cartridge: Cartridge,
+    boot_rom: Option<BootRom>,
+    key0: u8,
+    model: Model,
+    pub ppu: Ppu,
+    pub apu: Apu,
+    hdma: Hdma,
+    oam_dma: OamDma,
+    joypad: Joypad,
+    serial: Serial,
+    timer: Timer,
+    sgb: Option<Sgb>,
+    cgb_mode: bool,
+    double_speed: bool,
+    speed_switch_armed: bool,
+    // T-cycles the CPU is halted for by HDMA or a speed switch
+    stall: u32,
+    pub watchpoints: Watchpoints,
+    // LY reads 0x90 to the CPU, as in the emulator the Gameboy Doctor logs come from
+    doctor_ly: bool,
+}
+
+impl MemoryBus {
+    // A CGB starts in CGB mode, it is up to the boot ROM to switch to DMG compatibility
+    pub fn new(cartridge: Cartridge, model: Model) -> Self {
+	let sgb = if model.is_sgb() && cartridge.supports_sgb() { Some(Sgb::new()) } else { None };
+
+	MemoryBus {
+	    memory: [0; 0x10000],
+	    cartridge,
+	    boot_rom: None,
+	    key0: 0,
+	    model,
+	    ppu: Ppu::new(model),
+	    apu: Apu::new(),
+	    hdma: Hdma::new(),
+	    oam_dma: OamDma::new(),
+	    joypad: Joypad::new(),
+	    serial: Serial::new(),
+	    timer: Timer::new(),
+	    sgb,
+	    cgb_mode: model.is_cgb(),
+	    double_speed: false,
+	    speed_switch_armed: false,
+	    stall: 0,
+	    watchpoints: Watchpoints::new(),
+	    doctor_ly: false,
+	}
+    }
+
+    pub fn cartridge(&self) -> &Cartridge {
+	&self.cartridge
+    }
+
+    // Width, height and pixels of what is displayed, the SGB adds its border around the LCD
+    pub fn screen(&self) -> (usize, usize, &[u32]) {
+	match &self.sgb {
+	    Some(sgb) => (SGB_SCREEN_WIDTH, SGB_SCREEN_HEIGHT, sgb.frame()),
+	    None => (SCREEN_WIDTH, SCREEN_HEIGHT, self.ppu.framebuffer()),
+	}
+    }
+
+    pub fn has_sgb(&self) -> bool {
+	self.sgb.is_some()
+    }
+
+    // The bus and each component get their own section
+    pub fn save_sections(&self, writer: &mut StateWriter) {
+	writer.section(b"MEM ", |writer| self.save_state(writer));
+	writer.section(b"PPU ", |writer| self.ppu.save_state(writer));
+	writer.section(b"APU ", |writer| self.apu.save_state(writer));
+	writer.section(b"TIMR", |writer| self.timer.save_state(writer));
+	writer.section(b"SERL", |writer| self.serial.save_state(writer));
+	writer.section(b"JOYP", |writer| self.joypad.save_state(writer));
+	writer.section(b"HDMA", |writer| self.hdma.save_state(writer));
+	writer.section(b"ODMA", |writer| self.oam_dma.save_state(writer));
+	if let Some(sgb) = &self.sgb {
+	    writer.section(b"SGB ", |writer| sgb.save_state(writer));
+	}
+	// The cartridge is plain ROM, there are no mapper registers or RTC to save
+    }
+
+    // Returns false for sections that don't belong to the bus
+    pub fn load_section(&mut self, tag: &[u8; 4], reader: &mut StateReader) -> Result<bool, &'static str> {
+	match (tag, &mut self.sgb) {
+	    (b"MEM ", _) => self.load_state(reader)?,
+	    (b"PPU ", _) => self.ppu.load_state(reader)?,
+	    (b"APU ", _) => self.apu.load_state(reader)?,
+	    (b"TIMR", _) => self.timer.load_state(reader)?,
+	    (b"SERL", _) => self.serial.load_state(reader)?,
+	    (b"JOYP", _) => self.joypad.load_state(reader)?,
+	    (b"HDMA", _) => self.hdma.load_state(reader)?,
+	    (b"ODMA", _) => self.oam_dma.load_state(reader)?,
+	    (b"SGB ", Some(sgb)) => sgb.load_state(reader)?,
+	    _ => return Ok(false),
+	}
+	Ok(true)
+    }
+
+    // The CORE block of a BESS save state, without the CPU registers
+    pub fn bess_core(&self) -> Core {
+	let mut io = [0; 0x80];
+	for (offset, register) in io.iter_mut().enumerate() {
+	    *register = self.read_byte(0xFF00 + offset as u16);
+	}
+
+	// BESS expects the full 32KB of work RAM on CGB, which has no banking here
+	let cgb = self.model.is_cgb();
+	let mut ram = self.memory[0xC000..0xE000].to_vec();
+	let (banks, bg_palettes, obj_palettes) = if cgb {
+	    io[0x4C] = if self.cgb_mode { 0x80 } else { 0x04 };
+	    ram.resize(0x8000, 0);
+	    let (bg, obj) = self.ppu.palette_data();
+	    (2, bg.to_vec(), obj.to_vec())
+	} else {
+	    (1, Vec::new(), Vec::new())
+	};
+
+	Core {
+	    model: bess::model_id(self.model),
+	    pc: 0,
+	    af: 0,
+	    bc: 0,
+	    de: 0,
+	    hl: 0,
+	    sp: 0,
+	    ime: false,
+	    ie: self.memory[0xFFFF],
+	    execution_state: 0,
+	    io,
+	    ram,
+	    vram: self.ppu.vram_banks()[..banks].concat(),
+	    mbc_ram: Vec::new(),
+	    oam: self.ppu.oam().to_vec(),
+	    hram: self.memory[0xFF80..0xFFFF].to_vec(),
+	    bg_palettes,
+	    obj_palettes,
+	}
+    }
+
+    // Registers are written through the bus, except the ones that would start
+    // a transfer or only make sense together with the internal state BESS lacks
+    pub fn load_bess_core(&mut self, core: &Core) {
+	self.boot_rom = None;
+	self.stall = 0;
+	self.double_speed = self.cgb_mode && (core.io[0x4D] & 0x80) == 0x80;
+	copy_prefix(&mut self.memory[0xC000..0xE000], &core.ram);
+	copy_prefix(&mut self.memory[0xFF80..0xFFFF], &core.hram);
+	self.ppu.load_memory(&core.vram, &core.oam, &core.bg_palettes, &core.obj_palettes);
+
+	if self.cgb_mode && (core.io[0x4C] & 0x04) == 0x04 {
+	    self.enter_dmg_compatibility();
+	}
+
+	let nr52 = core.io[0x26];
+	self.write_byte(0xFF26, nr52);
+	for (offset, value) in core.io.iter().enumerate() {
+	    let address = 0xFF00 + offset as u16;
+	    match address {
+		0xFF04 | 0xFF0F | 0xFF26 | 0xFF41 | 0xFF44 | 0xFF46 | 0xFF4C | 0xFF50 | 0xFF55 | 0xFF69 | 0xFF6B => {}
+		// Channels are restarted below, triggering them here would play them from a stale state
+		0xFF14 | 0xFF19 | 0xFF1E | 0xFF23 => self.write_byte(address, value & 0x7F),
+		_ => self.write_byte(address, *value),
+	    }
+	}
+	for (channel, address) in [0xFF14, 0xFF19, 0xFF1E, 0xFF23].into_iter().enumerate() {
+	    if nr52 & (1 << channel) != 0 {
+		self.write_byte(address, core.io[(address - 0xFF00) as usize] | 0x80);
+	    }
+	}
+
+	self.timer.restore_div(core.io[0x04]);
+	self.ppu.restore_position(core.io[0x44], core.io[0x41]);
+	self.memory[0xFF0F] = core.io[0x0F] & 0x1F;
+	self.memory[0xFFFF] = core.ie;
+    }
+
+    pub fn save_bess_sgb(&self, writer: &mut BessWriter) {
+	if let Some(sgb) = &self.sgb {
+	    sgb.save_bess(writer);
+	}
+    }
+
+    // Returns false when the console isn't running an SGB game
+    pub fn load_bess_sgb(&mut self, file: &[u8], block: &[u8]) -> Result<bool, &'static str> {
+	match &mut self.sgb {
+	    Some(sgb) => sgb.load_bess(file, block).map(|_| true),
+	    None => Ok(false),
+	}
+    }
+
+    pub fn serial_output(&self) -> &[u8] {
+	self.serial.output()
+    }
+
+    pub fn press_button(&mut self, player: usize, button: Button) {
+	if self.joypad.press(player, button) {
+	    self.memory[0xFF0F] |= JOYPAD_INTERRUPT;
+	}
+    }
+
+    pub fn release_button(&mut self, player: usize, button: Button) {
+	self.joypad.release(player, button);
+    }
+
+    pub fn is_pressed(&self, player: usize, button: Button) -> bool {
+	self.joypad.is_pressed(player, button)
+    }
+
+    // Writing DIV would reset it, this sets the counter as the boot ROM leaves it
+    pub fn restore_div(&mut self, div: u8) {
+	self.timer.restore_div(div);
+    }
+
+    pub fn map_boot_rom(&mut self, boot_rom: BootRom) {
+	self.boot_rom = Some(boot_rom);
+    }
+
+    pub fn set_doctor_ly(&mut self, enabled: bool) {
+	self.doctor_ly = enabled;
+    }
+
+    pub fn enter_dmg_compatibility(&mut self) {
+	self.cgb_mode = false;
+	self.ppu.enter_compatibility_mode();
+    }
+
+    pub fn read_byte(&self, address: u16) -> u8 {
+	if let Some(boot_rom) = &self.boot_rom {
+	    if boot_rom.maps(address) {
+		return boot_rom.read_byte(address);
+	    }
+	}
+
+	match address {
+	    0x0000..=0x7FFF => self.cartridge.read_byte(address),
+	    0x8000..=0x9FFF => self.ppu.read_vram(address),
+	    0xFE00..=0xFE9F => self.ppu.read_oam(address),
+	    0xFEA0..=0xFEFF => self.read_unusable(address),
+	    0xFF00 => self.joypad.read(self.sgb.as_ref().map_or(0, |sgb| sgb.player())),
+	    0xFF01..=0xFF02 => self.serial.read_register(address),
+	    0xFF04..=0xFF07 => self.timer.read_register(address),
+	    // The upper 3 bits of IF don't exist and read as 1
+	    0xFF0F => self.memory[0xFF0F] | 0xE0,
+	    0xFF10..=0xFF3F => self.apu.read_register(address),
+	    0xFF46 => self.oam_dma.read_register(),
+	    0xFF40..=0xFF45 | 0xFF47..=0xFF4B | 0xFF4F | 0xFF68..=0xFF6C => self.ppu.read_register(address),
+	    0xFF51..=0xFF55 if self.cgb_mode => self.hdma.read_register(address),
+	    0xFF4D if self.cgb_mode => ((self.double_speed as u8) << 7) | 0x7E | self.speed_switch_armed as u8,
+	    0xFF50 => 0xFF,
+	    _ => self.memory[address as usize],
+	}
+    }
+
+    pub fn write_byte(&mut self, address: u16, value: u8) {
+	match address {
+	    0x0000..=0x7FFF => {}
+	    0x8000..=0x9FFF => self.ppu.write_vram(address, value),
+	    0xFE00..=0xFE9F => self.ppu.write_oam(address, value),
+	    0xFEA0..=0xFEFF => {}
+	    0xFF00 => {
+		let previous = self.joypad.select();
+		self.joypad.write(value);
+		if let Some(sgb) = &mut self.sgb {
+		    sgb.write_joypad(previous, value);
+		}
+	    }
+	    0xFF01..=0xFF02 => self.serial.write_register(address, value),
+	    0xFF04..=0xFF07 => self.memory[0xFF0F] |= self.timer.write_register(address, value),
+	    0xFF10..=0xFF3F => self.apu.write_register(address, value),
+	    0xFF46 => self.oam_dma.write_register(value),
+	    0xFF40..=0xFF45 | 0xFF47..=0xFF4B | 0xFF4F | 0xFF68..=0xFF6C => self.ppu.write_register(address, value),
+	    0xFF51..=0xFF55 if self.cgb_mode => self.write_hdma(address, value),
+	    // KEY0 is written by the CGB boot ROM to select DMG compatibility
+	    0xFF4C if self.cgb_mode && self.boot_rom.is_some() => self.key0 = value,
+	    0xFF4D if self.cgb_mode => self.speed_switch_armed = (value & 0x01) == 0x01,
+	    0xFF50 if value != 0 && self.boot_rom.is_some() => self.unmap_boot_rom(),
+	    0xFF50 => {}
+	    _ => self.memory[address as usize] = value,
+	}
+    }
+
+    // Advances the hardware by the given amount of T-cycles and returns how many
+    // actually elapsed, which includes the time the CPU was stalled.
+    pub fn tick(&mut self, cycles: u32) -> u32 {
+	let mut remaining = cycles + std::mem::take(&mut self.stall);
+	let mut elapsed = 0;
+
+	while remaining > 0 {
+	    let step = remaining.min(4);
+	    let mode = self.ppu.mode();
+	    // In double speed the timer and serial keep up with the CPU, the PPU and APU don't
+	    let dots = if self.double_speed { step / 2 } else { step };
+
+	    self.memory[0xFF0F] |= self.ppu.tick(dots) | self.serial.tick(step) | self.timer.tick(step);
+	    self.apu.tick(dots);
+	    // The CPU runs whole M-cycles, so every step is one
+	    self.step_oam_dma();
+	    remaining -= step;
+	    elapsed += step;
+
+	    if mode != PpuMode::VBlank && self.ppu.mode() == PpuMode::VBlank {
+		if let Some(sgb) = &mut self.sgb {
+		    sgb.vblank(&self.ppu);
+		}
+	    }
+
+	    let hblank_started = mode != PpuMode::HBlank && self.ppu.mode() == PpuMode::HBlank;
+	    if hblank_started && self.hdma.hblank_active() {
+		self.copy_hdma_block();
+		remaining += self.hdma_block_cycles();
+	    }
+	}
+
+	elapsed
+    }
+
+    // The CPU is halted while the clock settles, DIV restarts at the new speed
+    pub fn switch_speed(&mut self) {
+	if self.cgb_mode && self.speed_switch_armed {
+	    self.double_speed = !self.double_speed;
+	    self.speed_switch_armed = false;
+	    self.memory[0xFF0F] |= self.timer.write_register(0xFF04, 0);
+	    self.stall += SPEED_SWITCH_CYCLES;
+	}
+    }
+
+    // A block takes as long in both speeds, which is twice the CPU cycles in double speed
+    fn hdma_block_cycles(&self) -> u32 {
+	if self.double_speed { HDMA_BLOCK_DOTS * 2 } else { HDMA_BLOCK_DOTS }
+    }
+
+    // The area after OAM isn't backed by memory, what it reads as depends on the model
+    fn read_unusable(&self, address: u16) -> u8 {
+	if self.model.is_cgb() {
+	    let low = address as u8;
+	    (low & 0xF0) | (low >> 4)
+	} else {
+	    0x00
+	}
+    }
+
+    fn unmap_boot_rom(&mut self) {
+	self.boot_rom = None;
+
+	if (self.key0 & 0x04) == 0x04 {
+	    self.enter_dmg_compatibility();
+	}
+    }
+
+    fn write_hdma(&mut self, address: u16, value: u8) {
+	match self.hdma.write_register(address, value) {
+	    Some(HdmaMode::GeneralPurpose) => {
+		while self.hdma.blocks_left() > 0 {
+		    self.copy_hdma_block();
+		    self.stall += self.hdma_block_cycles();
+		}
+	    }
+	    // Starting during HBlank, or with the LCD off, copies the first block right away
+	    Some(HdmaMode::HBlank) if !self.ppu.lcd_enabled() || self.ppu.mode() == PpuMode::HBlank => {
+		self.copy_hdma_block();
+		self.stall += self.hdma_block_cycles();
+	    }
+	    _ => {}
+	}
+    }
+
+    fn step_oam_dma(&mut self) {
+	if let Some((source, offset)) = self.oam_dma.tick() {
+	    let value = self.read_byte(source);
+	    self.ppu.dma_write_oam(offset, value);
+	}
+    }
+
+    fn copy_hdma_block(&mut self) {
+	let (source, destination) = self.hdma.next_block();
+
+	for offset in 0..HDMA_BLOCK_LENGTH {
+	    let value = self.read_byte(source.wrapping_add(offset));
+	    self.ppu.dma_write_vram(destination + offset, value);
+	}
+    }
+}
+
+// Only the accesses of the CPU go through the trait, and so trigger watchpoints
+impl Bus for MemoryBus {
+    fn read_byte(&self, address: u16) -> u8 {
+	let value = self.peek_byte(address);
+	self.watchpoints.check(address, Access::Read, value);
+	value
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+	self.watchpoints.check(address, Access::Write, value);
+	if !(self.oam_dma.active() && (0xFE00..=0xFE9F).contains(&address)) {
+	    MemoryBus::write_byte(self, address, value);
+	}
+    }
+
+    fn peek_byte(&self, address: u16) -> u8 {
+	match address {
+	    0xFE00..=0xFE9F if self.oam_dma.active() => 0xFF,
+	    0xFF44 if self.doctor_ly => 0x90,
+	    _ => MemoryBus::read_byte(self, address),
+	}
+    }
+
+    fn tick(&mut self, cycles: u32) -> u32 {
+	MemoryBus::tick(self, cycles)
+    }
+
+    fn switch_speed(&mut self) {
+	MemoryBus::switch_speed(self)
+    }
+}
+
+// Everything below 0xA000 is either ROM or VRAM, which the PPU saves
+impl SaveState for MemoryBus {
+    fn save_state(&self, writer: &mut StateWriter) {
+	writer.bytes(&self.memory[0xA000..]);
+	writer.u8(self.key0);
+	writer.bool(self.cgb_mode);
+	writer.bool(self.double_speed);
+	writer.bool(self.speed_switch_armed);
+	writer.u32(self.stall);
+	// An empty boot ROM when it was already unmapped
+	writer.vec(self.boot_rom.as_ref().map_or(&[], |boot_rom| boot_rom.data()));
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), &'static str> {
+	reader.bytes(&mut self.memory[0xA000..])?;
+	self.key0 = reader.u8()?;
+	self.cgb_mode = reader.bool()?;
+	self.double_speed = reader.bool()?;
+	self.speed_switch_armed = reader.bool()?;
+	self.stall = reader.u32()?;
+	let boot_rom = reader.vec()?;
+	self.boot_rom = if boot_rom.is_empty() { None } else { Some(BootRom::new(boot_rom, self.model)?) };
+	Ok(())
+    }
+}
@@ -1,27 +1,140 @@
 use self::{
+    gameboy::RegisterState,
     instructions::{
-        ArithmeticTarget, BitPosition, GroupedArithmeticTarget, IncDecTarget, Instruction, JumpCondition, LoadType, LoadByteSrc, LoadByteTarget, LoadWordTarget, IndirectSrc,
+	ArithmeticTarget, BitPosition, GroupedArithmeticTarget, IncDecTarget, IndirectSrc, Instruction, JumpCondition, LoadByteSrc,
+	LoadByteTarget, LoadType, LoadWordTarget, StackTarget,
     },
-    memory::MemoryBus,
+    memory::{Bus, MemoryBus},
     registers::Registers,
+    savestate::{SaveState, StateReader, StateWriter},
 };
 
-pub mod emulator;
+pub mod apu;
+mod bess;
+mod boot;
+mod cartridge;
+pub mod compatibility;
+pub mod disassembler;
+pub mod flat;
 mod flags;
+pub mod gameboy;
+mod hdma;
 mod instructions;
+pub mod joypad;
 mod memory;
+pub mod model;
+pub mod movie;
+pub mod movie_import;
+mod oam_dma;
+mod palette;
+pub mod ppu;
 mod registers;
+pub mod rewind;
+mod savestate;
+pub mod screenshot;
+mod serial;
+pub mod sgb;
+pub mod speed;
+pub mod symbols;
+mod timer;
+pub mod watchpoints;
+
+// Interrupt sources by priority, the bits of IE and IF
+const INTERRUPT_VECTORS: [(u8, u16); 5] = [(0x01, 0x40), (0x02, 0x48), (0x04, 0x50), (0x08, 0x58), (0x10, 0x60)];
+const INTERRUPT_DISPATCH_CYCLES: u32 = 20;
 
 #[allow(dead_code)]
-struct CPU {
+struct CPU<B: Bus = MemoryBus> {
     registers: Registers,
     pc: u16,
-    bus: MemoryBus,
+    sp: u16,
+    bus: B,
+    ime: bool,
+    // EI enables interrupts after the instruction that follows it
+    ime_scheduled: bool,
+    halted: bool,
+    halt_bug: bool,
+    branch_taken: bool,
+    breakpoint: bool,
+    // An invalid opcode hangs the CPU, its address and value are kept for debugging
+    locked: Option<(u16, u8)>,
 }
 
 #[allow(dead_code)]
-impl CPU {
-    fn step(&mut self) {
+impl<B: Bus> CPU<B> {
+    fn new(bus: B) -> CPU<B> {
+	CPU {
+	    registers: Registers::new(),
+	    pc: 0,
+	    sp: 0,
+	    bus,
+	    ime: false,
+	    ime_scheduled: false,
+	    halted: false,
+	    halt_bug: false,
+	    branch_taken: false,
+	    breakpoint: false,
+	    locked: None,
+	}
+    }
+
+    fn register_state(&self) -> RegisterState {
+	RegisterState {
+	    a: self.registers.a,
+	    f: u8::from(self.registers.f),
+	    b: self.registers.b,
+	    c: self.registers.c,
+	    d: self.registers.d,
+	    e: self.registers.e,
+	    h: self.registers.h,
+	    l: self.registers.l,
+	    sp: self.sp,
+	    pc: self.pc,
+	}
+    }
+
+    fn set_register_state(&mut self, state: RegisterState) {
+	self.registers.set_af((state.a as u16) << 8 | (state.f & 0xF0) as u16);
+	self.registers.b = state.b;
+	self.registers.c = state.c;
+	self.registers.d = state.d;
+	self.registers.e = state.e;
+	self.registers.h = state.h;
+	self.registers.l = state.l;
+	self.sp = state.sp;
+	self.pc = state.pc;
+    }
+
+    // False while halted or hung, and when the step dispatches an interrupt
+    fn next_step_is_instruction(&self) -> bool {
+	let pending = self.bus.peek_byte(0xFFFF) & self.bus.peek_byte(0xFF0F) & 0x1F;
+	self.locked.is_none() && !(self.halted && pending == 0) && !(self.ime && pending != 0)
+    }
+
+    // Returns the T-cycles elapsed, including any DMA stall
+    fn step(&mut self) -> u32 {
+	let pending = self.bus.peek_byte(0xFFFF) & self.bus.peek_byte(0xFF0F) & 0x1F;
+
+	if self.locked.is_some() {
+	    return self.bus.tick(4);
+	}
+
+	if self.halted {
+	    if pending == 0 {
+		return self.bus.tick(4);
+	    }
+	    self.halted = false;
+	}
+
+	if self.ime && pending != 0 {
+	    return self.dispatch_interrupt(pending);
+	}
+
+	if self.ime_scheduled {
+	    self.ime_scheduled = false;
+	    self.ime = true;
+	}
+
         let mut instruction_address = self.bus.read_byte(self.pc);
 	let is_prefix = instruction_address == 0xCB;
 
@@ -29,137 +142,103 @@ impl CPU {
 	    instruction_address = self.bus.read_byte(self.pc+1);
 	}
 
+	let mut cycles = Instruction::cycles(instruction_address, is_prefix);
+
+	// LD B,B does nothing, test ROMs use it as a software breakpoint
+	if !is_prefix && instruction_address == 0x40 {
+	    self.breakpoint = true;
+	}
+
+	// The byte after HALT is read twice, as if PC didn't move past the opcode
+	if std::mem::take(&mut self.halt_bug) {
+	    self.pc = self.pc.wrapping_sub(1);
+	}
+
         self.pc = if let Some(instruction) = Instruction::from_byte(instruction_address, is_prefix) {
             self.execute(instruction)
         } else {
-            panic!("Invalid instruction found at: 0x{:x}", instruction_address);
+	    self.locked = Some((self.pc, instruction_address));
+	    return self.bus.tick(4);
         };
+
+	if !is_prefix && std::mem::take(&mut self.branch_taken) {
+	    cycles += Instruction::taken_branch_cycles(instruction_address);
+	}
+
+	self.bus.tick(cycles)
+    }
+
+    // Pushes PC and jumps to the vector of the highest priority pending interrupt
+    fn dispatch_interrupt(&mut self, pending: u8) -> u32 {
+	let (bit, vector) = INTERRUPT_VECTORS.iter().find(|(bit, _)| pending & bit != 0).copied().unwrap();
+
+	self.ime = false;
+	self.ime_scheduled = false;
+	let flags = self.bus.peek_byte(0xFF0F);
+	self.bus.write_byte(0xFF0F, flags & !bit);
+	self.push(self.pc);
+	self.pc = vector;
+
+	self.bus.tick(INTERRUPT_DISPATCH_CYCLES)
     }
 
     fn execute(&mut self, instruction: Instruction) -> u16 {
         match instruction {
             Instruction::ADD(register) => {
-                let value = match register {
-                    ArithmeticTarget::A => self.registers.a,
-                    ArithmeticTarget::B => self.registers.b,
-                    ArithmeticTarget::C => self.registers.c,
-                    ArithmeticTarget::D => self.registers.d,
-                    ArithmeticTarget::E => self.registers.e,
-                    ArithmeticTarget::H => self.registers.h,
-                    ArithmeticTarget::L => self.registers.l,
-                };
-
+		let value = self.read_arithmetic_target(register);
                 self.registers.a = self.add(value);
-		self.pc.wrapping_add(1)
+		self.pc.wrapping_add(operand_length(register))
             }
             Instruction::ADDHL(grouped_register) => {
                 let value = match grouped_register {
                     GroupedArithmeticTarget::BC => self.registers.get_bc(),
                     GroupedArithmeticTarget::DE => self.registers.get_de(),
                     GroupedArithmeticTarget::HL => self.registers.get_hl(),
+		    GroupedArithmeticTarget::SP => self.sp,
                 };
 
                 let res = self.addhl(value);
                 self.registers.set_hl(res);
 		self.pc.wrapping_add(1)
             }
+	    Instruction::ADDSP => {
+		self.sp = self.add_sp_offset();
+		self.pc.wrapping_add(2)
+	    }
             Instruction::ADC(register) => {
-                let value = match register {
-                    ArithmeticTarget::A => self.registers.a,
-                    ArithmeticTarget::B => self.registers.b,
-                    ArithmeticTarget::C => self.registers.c,
-                    ArithmeticTarget::D => self.registers.d,
-                    ArithmeticTarget::E => self.registers.e,
-                    ArithmeticTarget::H => self.registers.h,
-                    ArithmeticTarget::L => self.registers.l,
-                };
-
+		let value = self.read_arithmetic_target(register);
                 self.registers.a = self.add_with_carry(value);
-		self.pc.wrapping_add(1)
+		self.pc.wrapping_add(operand_length(register))
             }
             Instruction::SUB(register) => {
-                let value = match register {
-                    ArithmeticTarget::A => self.registers.a,
-                    ArithmeticTarget::B => self.registers.b,
-                    ArithmeticTarget::C => self.registers.c,
-                    ArithmeticTarget::D => self.registers.d,
-                    ArithmeticTarget::E => self.registers.e,
-                    ArithmeticTarget::H => self.registers.h,
-                    ArithmeticTarget::L => self.registers.l,
-                };
-
+		let value = self.read_arithmetic_target(register);
                 self.registers.a = self.sub(value);
-		self.pc.wrapping_add(1)
+		self.pc.wrapping_add(operand_length(register))
             }
             Instruction::SBC(register) => {
-                let value = match register {
-                    ArithmeticTarget::A => self.registers.a,
-                    ArithmeticTarget::B => self.registers.b,
-                    ArithmeticTarget::C => self.registers.c,
-                    ArithmeticTarget::D => self.registers.d,
-                    ArithmeticTarget::E => self.registers.e,
-                    ArithmeticTarget::H => self.registers.h,
-                    ArithmeticTarget::L => self.registers.l,
-                };
-
+		let value = self.read_arithmetic_target(register);
                 self.registers.a = self.sub_with_carry(value);
-		self.pc.wrapping_add(1)
+		self.pc.wrapping_add(operand_length(register))
             }
             Instruction::AND(register) => {
-                let value = match register {
-                    ArithmeticTarget::A => self.registers.a,
-                    ArithmeticTarget::B => self.registers.b,
-                    ArithmeticTarget::C => self.registers.c,
-                    ArithmeticTarget::D => self.registers.d,
-                    ArithmeticTarget::E => self.registers.e,
-                    ArithmeticTarget::H => self.registers.h,
-                    ArithmeticTarget::L => self.registers.l,
-                };
-
+		let value = self.read_arithmetic_target(register);
                 self.registers.a = self.and(value);
-		self.pc.wrapping_add(1)
+		self.pc.wrapping_add(operand_length(register))
             }
             Instruction::OR(register) => {
-                let value = match register {
-                    ArithmeticTarget::A => self.registers.a,
-                    ArithmeticTarget::B => self.registers.b,
-                    ArithmeticTarget::C => self.registers.c,
-                    ArithmeticTarget::D => self.registers.d,
-                    ArithmeticTarget::E => self.registers.e,
-                    ArithmeticTarget::H => self.registers.h,
-                    ArithmeticTarget::L => self.registers.l,
-                };
-
+		let value = self.read_arithmetic_target(register);
                 self.registers.a = self.or(value);
-		self.pc.wrapping_add(1)
+		self.pc.wrapping_add(operand_length(register))
             }
             Instruction::XOR(register) => {
-                let value = match register {
-                    ArithmeticTarget::A => self.registers.a,
-                    ArithmeticTarget::B => self.registers.b,
-                    ArithmeticTarget::C => self.registers.c,
-                    ArithmeticTarget::D => self.registers.d,
-                    ArithmeticTarget::E => self.registers.e,
-                    ArithmeticTarget::H => self.registers.h,
-                    ArithmeticTarget::L => self.registers.l,
-                };
-
+		let value = self.read_arithmetic_target(register);
                 self.registers.a = self.xor(value);
-		self.pc.wrapping_add(1)
+		self.pc.wrapping_add(operand_length(register))
             }
             Instruction::CP(register) => {
-                let value = match register {
-                    ArithmeticTarget::A => self.registers.a,
-                    ArithmeticTarget::B => self.registers.b,
-                    ArithmeticTarget::C => self.registers.c,
-                    ArithmeticTarget::D => self.registers.d,
-                    ArithmeticTarget::E => self.registers.e,
-                    ArithmeticTarget::H => self.registers.h,
-                    ArithmeticTarget::L => self.registers.l,
-                };
-
+		let value = self.read_arithmetic_target(register);
                 self.compare(value);
-		self.pc.wrapping_add(1)
+		self.pc.wrapping_add(operand_length(register))
             }
 
             Instruction::INC(register) => {
@@ -171,6 +250,11 @@ impl CPU {
                     IncDecTarget::E => self.registers.e = self.inc_8b(self.registers.e),
                     IncDecTarget::H => self.registers.h = self.inc_8b(self.registers.h),
                     IncDecTarget::L => self.registers.l = self.inc_8b(self.registers.l),
+		    IncDecTarget::HLI => {
+			let hl = self.registers.get_hl();
+			let res = self.inc_8b(self.bus.read_byte(hl));
+			self.bus.write_byte(hl, res);
+		    }
                     IncDecTarget::BC => {
                         let res = self.inc_16b(self.registers.get_bc());
                         self.registers.set_bc(res);
@@ -183,6 +267,7 @@ impl CPU {
                         let res = self.inc_16b(self.registers.get_hl());
                         self.registers.set_hl(res);
                     }
+		    IncDecTarget::SP => self.sp = self.inc_16b(self.sp),
                 };
 
 		self.pc.wrapping_add(1)
@@ -196,6 +281,11 @@ impl CPU {
                     IncDecTarget::E => self.registers.e = self.dec_8b(self.registers.e),
                     IncDecTarget::H => self.registers.h = self.dec_8b(self.registers.h),
                     IncDecTarget::L => self.registers.l = self.dec_8b(self.registers.l),
+		    IncDecTarget::HLI => {
+			let hl = self.registers.get_hl();
+			let res = self.dec_8b(self.bus.read_byte(hl));
+			self.bus.write_byte(hl, res);
+		    }
                     IncDecTarget::BC => {
                         let res = self.dec_16b(self.registers.get_bc());
                         self.registers.set_bc(res);
@@ -208,6 +298,7 @@ impl CPU {
                         let res = self.dec_16b(self.registers.get_hl());
                         self.registers.set_hl(res);
                     }
+		    IncDecTarget::SP => self.sp = self.dec_16b(self.sp),
                 };
 
 		self.pc.wrapping_add(1)
@@ -240,245 +331,124 @@ impl CPU {
                 self.complement();
 		self.pc.wrapping_add(1)
             }
-            Instruction::BIT(register, bit) => {
-                let value = match register {
-                    ArithmeticTarget::A => self.registers.a,
-                    ArithmeticTarget::B => self.registers.b,
-                    ArithmeticTarget::C => self.registers.c,
-                    ArithmeticTarget::D => self.registers.d,
-                    ArithmeticTarget::E => self.registers.e,
-                    ArithmeticTarget::H => self.registers.h,
-                    ArithmeticTarget::L => self.registers.l,
-                };
-
-                self.test_bit(value, bit);
+	    Instruction::DAA => {
+		self.decimal_adjust();
 		self.pc.wrapping_add(1)
             }
+	    Instruction::BIT(register, bit) => {
+		let value = self.read_arithmetic_target(register);
+		self.test_bit(value, bit);
+		self.pc.wrapping_add(2)
+	    }
             Instruction::RESET(register, bit) => {
-                match register {
-                    ArithmeticTarget::A => self.registers.a = self.reset_bit(self.registers.a, bit),
-                    ArithmeticTarget::B => self.registers.b = self.reset_bit(self.registers.b, bit),
-                    ArithmeticTarget::C => self.registers.c = self.reset_bit(self.registers.c, bit),
-                    ArithmeticTarget::D => self.registers.d = self.reset_bit(self.registers.d, bit),
-                    ArithmeticTarget::E => self.registers.e = self.reset_bit(self.registers.e, bit),
-                    ArithmeticTarget::H => self.registers.h = self.reset_bit(self.registers.h, bit),
-                    ArithmeticTarget::L => self.registers.l = self.reset_bit(self.registers.l, bit),
-                };
-
-		self.pc.wrapping_add(1)
+		let value = self.read_arithmetic_target(register);
+		let res = self.reset_bit(value, bit);
+		self.write_arithmetic_target(register, res);
+		self.pc.wrapping_add(2)
             }
             Instruction::SET(register, bit) => {
-                match register {
-                    ArithmeticTarget::A => self.registers.a = self.set_bit(self.registers.a, bit),
-                    ArithmeticTarget::B => self.registers.b = self.set_bit(self.registers.b, bit),
-                    ArithmeticTarget::C => self.registers.c = self.set_bit(self.registers.c, bit),
-                    ArithmeticTarget::D => self.registers.d = self.set_bit(self.registers.d, bit),
-                    ArithmeticTarget::E => self.registers.e = self.set_bit(self.registers.e, bit),
-                    ArithmeticTarget::H => self.registers.h = self.set_bit(self.registers.h, bit),
-                    ArithmeticTarget::L => self.registers.l = self.set_bit(self.registers.l, bit),
-                };
-
-		self.pc.wrapping_add(1)
+		let value = self.read_arithmetic_target(register);
+		let res = self.set_bit(value, bit);
+		self.write_arithmetic_target(register, res);
+		self.pc.wrapping_add(2)
             }
             Instruction::SRL(register) => {
-                match register {
-                    ArithmeticTarget::A => {
-                        self.registers.a = self.shift_right_logical(self.registers.a)
-                    }
-                    ArithmeticTarget::B => {
-                        self.registers.b = self.shift_right_logical(self.registers.b)
-                    }
-                    ArithmeticTarget::C => {
-                        self.registers.c = self.shift_right_logical(self.registers.c)
-                    }
-                    ArithmeticTarget::D => {
-                        self.registers.d = self.shift_right_logical(self.registers.d)
-                    }
-                    ArithmeticTarget::E => {
-                        self.registers.e = self.shift_right_logical(self.registers.e)
-                    }
-                    ArithmeticTarget::H => {
-                        self.registers.h = self.shift_right_logical(self.registers.h)
-                    }
-                    ArithmeticTarget::L => {
-                        self.registers.l = self.shift_right_logical(self.registers.l)
-                    }
-                };
-
-		self.pc.wrapping_add(1)
+		let value = self.read_arithmetic_target(register);
+		let res = self.shift_right_logical(value);
+		self.write_arithmetic_target(register, res);
+		self.pc.wrapping_add(2)
             }
             Instruction::RR(register) => {
-                match register {
-                    ArithmeticTarget::A => {
-                        self.registers.a = self.rotate_right_with_carry(self.registers.a)
-                    }
-                    ArithmeticTarget::B => {
-                        self.registers.b = self.rotate_right_with_carry(self.registers.b)
-                    }
-                    ArithmeticTarget::C => {
-                        self.registers.c = self.rotate_right_with_carry(self.registers.c)
-                    }
-                    ArithmeticTarget::D => {
-                        self.registers.d = self.rotate_right_with_carry(self.registers.d)
-                    }
-                    ArithmeticTarget::E => {
-                        self.registers.e = self.rotate_right_with_carry(self.registers.e)
-                    }
-                    ArithmeticTarget::H => {
-                        self.registers.h = self.rotate_right_with_carry(self.registers.h)
-                    }
-                    ArithmeticTarget::L => {
-                        self.registers.l = self.rotate_right_with_carry(self.registers.l)
-                    }
-                };
-
-		self.pc.wrapping_add(1)
+		let value = self.read_arithmetic_target(register);
+		let res = self.rotate_right_with_carry(value);
+		self.write_arithmetic_target(register, res);
+		self.pc.wrapping_add(2)
             }
             Instruction::RL(register) => {
-                match register {
-                    ArithmeticTarget::A => {
-                        self.registers.a = self.rotate_left_with_carry(self.registers.a)
-                    }
-                    ArithmeticTarget::B => {
-                        self.registers.b = self.rotate_left_with_carry(self.registers.b)
-                    }
-                    ArithmeticTarget::C => {
-                        self.registers.c = self.rotate_left_with_carry(self.registers.c)
-                    }
-                    ArithmeticTarget::D => {
-                        self.registers.d = self.rotate_left_with_carry(self.registers.d)
-                    }
-                    ArithmeticTarget::E => {
-                        self.registers.e = self.rotate_left_with_carry(self.registers.e)
-                    }
-                    ArithmeticTarget::H => {
-                        self.registers.h = self.rotate_left_with_carry(self.registers.h)
-                    }
-                    ArithmeticTarget::L => {
-                        self.registers.l = self.rotate_left_with_carry(self.registers.l)
-                    }
-                };
-
-		self.pc.wrapping_add(1)
+		let value = self.read_arithmetic_target(register);
+		let res = self.rotate_left_with_carry(value);
+		self.write_arithmetic_target(register, res);
+		self.pc.wrapping_add(2)
             }
             Instruction::RRC(register) => {
-                match register {
-                    ArithmeticTarget::A => self.registers.a = self.rotate_right(self.registers.a),
-                    ArithmeticTarget::B => self.registers.b = self.rotate_right(self.registers.b),
-                    ArithmeticTarget::C => self.registers.c = self.rotate_right(self.registers.c),
-                    ArithmeticTarget::D => self.registers.d = self.rotate_right(self.registers.d),
-                    ArithmeticTarget::E => self.registers.e = self.rotate_right(self.registers.e),
-                    ArithmeticTarget::H => self.registers.h = self.rotate_right(self.registers.h),
-                    ArithmeticTarget::L => self.registers.l = self.rotate_right(self.registers.l),
-                };
-
-		self.pc.wrapping_add(1)
+		let value = self.read_arithmetic_target(register);
+		let res = self.rotate_right(value);
+		self.write_arithmetic_target(register, res);
+		self.pc.wrapping_add(2)
             }
             Instruction::RLC(register) => {
-                match register {
-                    ArithmeticTarget::A => self.registers.a = self.rotate_left(self.registers.a),
-                    ArithmeticTarget::B => self.registers.b = self.rotate_left(self.registers.b),
-                    ArithmeticTarget::C => self.registers.c = self.rotate_left(self.registers.c),
-                    ArithmeticTarget::D => self.registers.d = self.rotate_left(self.registers.d),
-                    ArithmeticTarget::E => self.registers.e = self.rotate_left(self.registers.e),
-                    ArithmeticTarget::H => self.registers.h = self.rotate_left(self.registers.h),
-                    ArithmeticTarget::L => self.registers.l = self.rotate_left(self.registers.l),
-                };
-
-		self.pc.wrapping_add(1)
+		let value = self.read_arithmetic_target(register);
+		let res = self.rotate_left(value);
+		self.write_arithmetic_target(register, res);
+		self.pc.wrapping_add(2)
             }
             Instruction::SRA(register) => {
-                match register {
-                    ArithmeticTarget::A => {
-                        self.registers.a = self.rotate_right_arithmetic(self.registers.a)
-                    }
-                    ArithmeticTarget::B => {
-                        self.registers.b = self.rotate_right_arithmetic(self.registers.b)
-                    }
-                    ArithmeticTarget::C => {
-                        self.registers.c = self.rotate_right_arithmetic(self.registers.c)
-                    }
-                    ArithmeticTarget::D => {
-                        self.registers.d = self.rotate_right_arithmetic(self.registers.d)
-                    }
-                    ArithmeticTarget::E => {
-                        self.registers.e = self.rotate_right_arithmetic(self.registers.e)
-                    }
-                    ArithmeticTarget::H => {
-                        self.registers.h = self.rotate_right_arithmetic(self.registers.h)
-                    }
-                    ArithmeticTarget::L => {
-                        self.registers.l = self.rotate_right_arithmetic(self.registers.l)
-                    }
-                };
-
-		self.pc.wrapping_add(1)
+		let value = self.read_arithmetic_target(register);
+		let res = self.rotate_right_arithmetic(value);
+		self.write_arithmetic_target(register, res);
+		self.pc.wrapping_add(2)
             }
             Instruction::SLA(register) => {
-                match register {
-                    ArithmeticTarget::A => {
-                        self.registers.a = self.rotate_left_arithmetic(self.registers.a)
-                    }
-                    ArithmeticTarget::B => {
-                        self.registers.b = self.rotate_left_arithmetic(self.registers.b)
-                    }
-                    ArithmeticTarget::C => {
-                        self.registers.c = self.rotate_left_arithmetic(self.registers.c)
-                    }
-                    ArithmeticTarget::D => {
-                        self.registers.d = self.rotate_left_arithmetic(self.registers.d)
-                    }
-                    ArithmeticTarget::E => {
-                        self.registers.e = self.rotate_left_arithmetic(self.registers.e)
-                    }
-                    ArithmeticTarget::H => {
-                        self.registers.h = self.rotate_left_arithmetic(self.registers.h)
-                    }
-                    ArithmeticTarget::L => {
-                        self.registers.l = self.rotate_left_arithmetic(self.registers.l)
-                    }
-                };
-
-		self.pc.wrapping_add(1)
+		let value = self.read_arithmetic_target(register);
+		let res = self.rotate_left_arithmetic(value);
+		self.write_arithmetic_target(register, res);
+		self.pc.wrapping_add(2)
             }
             Instruction::SWAP(register) => {
-                match register {
-                    ArithmeticTarget::A => self.registers.a = self.swap(self.registers.a),
-                    ArithmeticTarget::B => self.registers.b = self.swap(self.registers.b),
-                    ArithmeticTarget::C => self.registers.c = self.swap(self.registers.c),
-                    ArithmeticTarget::D => self.registers.d = self.swap(self.registers.d),
-                    ArithmeticTarget::E => self.registers.e = self.swap(self.registers.e),
-                    ArithmeticTarget::H => self.registers.h = self.swap(self.registers.h),
-                    ArithmeticTarget::L => self.registers.l = self.swap(self.registers.l),
-                };
-
-		self.pc.wrapping_add(1)
+		let value = self.read_arithmetic_target(register);
+		let res = self.swap(value);
+		self.write_arithmetic_target(register, res);
+		self.pc.wrapping_add(2)
             }
 	    Instruction::JP(condition) => {
-		let condition = match condition {
-		    JumpCondition::NotZero => !self.registers.f.zero,
-		    JumpCondition::Zero => self.registers.f.zero,
-		    JumpCondition::NotCarry => !self.registers.f.carry,
-		    JumpCondition::Carry => self.registers.f.carry,
-		    JumpCondition::Always => true,
-		};
-
+		let condition = self.condition(condition);
 		self.jump(condition)
 	    }
 	    Instruction::JR(condition) => {
-		let condition = match condition {
-		    JumpCondition::NotZero => !self.registers.f.zero,
-		    JumpCondition::Zero => self.registers.f.zero,
-		    JumpCondition::NotCarry => !self.registers.f.carry,
-		    JumpCondition::Carry => self.registers.f.carry,
-		    JumpCondition::Always => true,
-		};
-
+		let condition = self.condition(condition);
 		self.jump_relative(condition)
 	    }
 	    Instruction::JPI => {
 		self.registers.get_hl()
 	    }
+	    Instruction::CALL(condition) => {
+		let condition = self.condition(condition);
+		self.call(condition)
+	    }
+	    Instruction::RET(condition) => {
+		let condition = self.condition(condition);
+		self.return_from_call(condition)
+	    }
+	    Instruction::RETI => {
+		self.ime = true;
+		self.pop()
+	    }
+	    Instruction::RST(vector) => {
+		self.push(self.pc.wrapping_add(1));
+		vector as u16
+	    }
+	    Instruction::PUSH(target) => {
+		let value = match target {
+		    StackTarget::AF => self.registers.get_af(),
+		    StackTarget::BC => self.registers.get_bc(),
+		    StackTarget::DE => self.registers.get_de(),
+		    StackTarget::HL => self.registers.get_hl(),
+		};
+
+		self.push(value);
+		self.pc.wrapping_add(1)
+	    }
+	    Instruction::POP(target) => {
+		let value = self.pop();
+		match target {
+		    StackTarget::AF => self.registers.set_af(value),
+		    StackTarget::BC => self.registers.set_bc(value),
+		    StackTarget::DE => self.registers.set_de(value),
+		    StackTarget::HL => self.registers.set_hl(value),
+		}
+
+		self.pc.wrapping_add(1)
+	    }
 	    Instruction::LD(load_type) => {
 		match load_type {
 		    LoadType::Byte(target, src) => {
@@ -490,7 +460,7 @@ impl CPU {
 			    LoadByteSrc::E => self.registers.e,
 			    LoadByteSrc::H => self.registers.h,
 			    LoadByteSrc::L => self.registers.l,
-			    LoadByteSrc::D8 => self.bus.read_byte(self.pc + 1), // direct 8bit value
+			    LoadByteSrc::D8 => self.bus.read_byte(self.pc.wrapping_add(1)), // direct 8bit value
 			    LoadByteSrc::HLI => self.bus.read_byte(self.registers.get_hl()),
 			};
 
@@ -506,7 +476,7 @@ impl CPU {
 			};
 
 			match src {
-			    LoadByteSrc::D8 => self.pc.wrapping_add(3),
+			    LoadByteSrc::D8 => self.pc.wrapping_add(2),
 			    _ => self.pc.wrapping_add(1),
 			}
 		    }
@@ -516,6 +486,7 @@ impl CPU {
 			    LoadWordTarget::BC => self.registers.set_bc(value),
 			    LoadWordTarget::DE => self.registers.set_de(value),
 			    LoadWordTarget::HL => self.registers.set_hl(value),
+			    LoadWordTarget::SP => self.sp = value,
 			}
 
 			self.pc.wrapping_add(3)
@@ -568,22 +539,123 @@ impl CPU {
 			}
 		    }
 		    LoadType::AFromByteAddress => {
-			let offset = self.bus.read_byte(self.pc+1) as u16;
-			self.bus.write_byte(0xFF00+offset, self.registers.a);
+			let offset = self.bus.read_byte(self.pc.wrapping_add(1)) as u16;
+			self.registers.a = self.bus.read_byte(0xFF00+offset);
 			self.pc.wrapping_add(2)
 		    },
 		    LoadType::ByteAddressFromA => {
-			let offset = self.bus.read_byte(self.pc+1) as u16;
-			self.registers.a = self.bus.read_byte(0xFF00+offset);
+			let offset = self.bus.read_byte(self.pc.wrapping_add(1)) as u16;
+			self.bus.write_byte(0xFF00+offset, self.registers.a);
 			self.pc.wrapping_add(2)
 		    },
+		    LoadType::SPFromHL => {
+			self.sp = self.registers.get_hl();
+			self.pc.wrapping_add(1)
+		    }
+		    LoadType::HLFromSPOffset => {
+			let res = self.add_sp_offset();
+			self.registers.set_hl(res);
+			self.pc.wrapping_add(2)
+		    }
+		    LoadType::IndirectFromSP => {
+			let address = self.next_word();
+			self.bus.write_byte(address, self.sp as u8);
+			self.bus.write_byte(address.wrapping_add(1), (self.sp >> 8) as u8);
+			self.pc.wrapping_add(3)
+		    }
 		}
 	    }
+	    Instruction::NOP => self.pc.wrapping_add(1),
+	    Instruction::HALT => {
+		let pending = self.bus.peek_byte(0xFFFF) & self.bus.peek_byte(0xFF0F) & 0x1F;
+		if !self.ime && pending != 0 {
+		    self.halt_bug = true;
+		} else {
+		    self.halted = true;
+		}
+
+		self.pc.wrapping_add(1)
+	    }
+	    // Low power mode isn't emulated, STOP only switches the CGB speed and
+	    // is skipped along with the byte after it
+	    Instruction::STOP => {
+		self.bus.switch_speed();
+		self.pc.wrapping_add(2)
+	    }
+	    Instruction::DI => {
+		self.ime = false;
+		self.ime_scheduled = false;
+		self.pc.wrapping_add(1)
+	    }
+	    Instruction::EI => {
+		self.ime_scheduled = true;
+		self.pc.wrapping_add(1)
+	    }
         }
     }
 }
 
 impl CPU {
+    // Runs until the PPU completes a frame and returns the elapsed T-cycles
+    fn run_frame(&mut self) -> u64 {
+	let frame = self.bus.ppu.frames();
+	let mut cycles = 0;
+
+	while self.bus.ppu.frames() == frame {
+	    cycles += self.step() as u64;
+	}
+
+	cycles
+    }
+}
+
+impl<B: Bus> SaveState for CPU<B> {
+    fn save_state(&self, writer: &mut StateWriter) {
+	for register in [self.registers.get_af(), self.registers.get_bc(), self.registers.get_de(), self.registers.get_hl()] {
+	    writer.u16(register);
+	}
+	writer.u16(self.sp);
+	writer.u16(self.pc);
+	writer.bool(self.ime);
+	writer.bool(self.ime_scheduled);
+	writer.bool(self.halted);
+	writer.bool(self.halt_bug);
+	// The address and opcode the CPU hung on, if it did
+	writer.bool(self.locked.is_some());
+	let (address, opcode) = self.locked.unwrap_or((0, 0));
+	writer.u16(address);
+	writer.u8(opcode);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), &'static str> {
+	self.registers.set_af(reader.u16()? & 0xFFF0);
+	self.registers.set_bc(reader.u16()?);
+	self.registers.set_de(reader.u16()?);
+	self.registers.set_hl(reader.u16()?);
+	self.sp = reader.u16()?;
+	self.pc = reader.u16()?;
+	self.ime = reader.bool()?;
+	self.ime_scheduled = reader.bool()?;
+	self.halted = reader.bool()?;
+	self.halt_bug = reader.bool()?;
+	let locked = reader.bool()?;
+	let (address, opcode) = (reader.u16()?, reader.u8()?);
+	self.locked = if locked { Some((address, opcode)) } else { None };
+	self.branch_taken = false;
+	self.breakpoint = false;
+	Ok(())
+    }
+}
+
+// Bytes taken by an arithmetic instruction, immediates follow the opcode
+fn operand_length(target: ArithmeticTarget) -> u16 {
+    match target {
+	ArithmeticTarget::D8 => 2,
+	_ => 1,
+    }
+}
+
+impl<B: Bus> CPU<B> {
     fn add(&mut self, value: u8) -> u8 {
         let (res, did_overflow) = self.registers.a.overflowing_add(value);
 
@@ -598,7 +670,7 @@ impl CPU {
 
     // Half-carry occurs from bit 11 to bit 12
     fn addhl(&mut self, value: u16) -> u16 {
-        let mask = 0b111_1111_1111;
+	let mask = 0b1111_1111_1111;
         let hl = self.registers.get_hl();
         let (res, did_overflow) = hl.overflowing_add(value);
 
@@ -693,7 +765,7 @@ impl CPU {
     }
 
     fn inc_8b(&mut self, value: u8) -> u8 {
-        let res = value.wrapping_add(value);
+	let res = value.wrapping_add(1);
 
         self.registers.f.zero = res == 0;
         self.registers.f.subtraction = false;
@@ -707,7 +779,7 @@ impl CPU {
     }
 
     fn dec_8b(&mut self, value: u8) -> u8 {
-        let res = value.wrapping_sub(value);
+	let res = value.wrapping_sub(1);
 
         self.registers.f.zero = res == 0;
         self.registers.f.subtraction = true;
@@ -760,7 +832,7 @@ impl CPU {
         self.registers.f.half_carry = false;
         self.registers.f.carry = (self.registers.a & 0b1) == 0b1;
 
-        self.registers.a = self.registers.a >> 1;
+	self.registers.a = self.registers.a.rotate_right(1);
     }
 
     fn rotate_left_a(&mut self) {
@@ -769,7 +841,7 @@ impl CPU {
         self.registers.f.half_carry = false;
         self.registers.f.carry = (self.registers.a & 0x80) == 0x80;
 
-        self.registers.a = self.registers.a << 1;
+	self.registers.a = self.registers.a.rotate_left(1);
     }
 
     fn complement(&mut self) {
@@ -802,8 +874,8 @@ impl CPU {
 
         self.registers.f.zero = res == 0;
         self.registers.f.subtraction = false;
-        self.registers.f.half_carry = true;
-        self.registers.f.carry = (self.registers.a & 0b1) == 0b1;
+	self.registers.f.half_carry = false;
+	self.registers.f.carry = (value & 0b1) == 0b1;
 
         res
     }
@@ -821,10 +893,10 @@ impl CPU {
     }
 
     fn rotate_left_with_carry(&mut self, value: u8) -> u8 {
-        let carry: u8 = if self.registers.f.carry { 1 } else { 0 } << 7;
+	let carry: u8 = if self.registers.f.carry { 1 } else { 0 };
         let res = (value << 1) | carry;
 
-        self.registers.f.zero = false;
+	self.registers.f.zero = res == 0;
         self.registers.f.subtraction = false;
         self.registers.f.half_carry = false;
         self.registers.f.carry = (value & 0x80) == 0x80;
@@ -833,7 +905,7 @@ impl CPU {
     }
 
     fn rotate_right(&mut self, value: u8) -> u8 {
-        let res = value >> 1;
+	let res = value.rotate_right(1);
 
         self.registers.f.zero = res == 0;
         self.registers.f.subtraction = false;
@@ -844,9 +916,9 @@ impl CPU {
     }
 
     fn rotate_left(&mut self, value: u8) -> u8 {
-        let res = value << 1;
+	let res = value.rotate_left(1);
 
-        self.registers.f.zero = false;
+	self.registers.f.zero = res == 0;
         self.registers.f.subtraction = false;
         self.registers.f.half_carry = false;
         self.registers.f.carry = (value & 0x80) == 0x80;
@@ -857,7 +929,7 @@ impl CPU {
     fn rotate_right_arithmetic(&mut self, value: u8) -> u8 {
         let res = value & 0x80 | (value >> 1);
 
-        self.registers.f.zero = false;
+	self.registers.f.zero = res == 0;
         self.registers.f.subtraction = false;
         self.registers.f.half_carry = false;
         self.registers.f.carry = (value & 0b1) == 0b1;
@@ -866,9 +938,9 @@ impl CPU {
     }
 
     fn rotate_left_arithmetic(&mut self, value: u8) -> u8 {
-        let res = value & 0x80 | (value << 1);
+	let res = value << 1;
 
-        self.registers.f.zero = false;
+	self.registers.f.zero = res == 0;
         self.registers.f.subtraction = false;
         self.registers.f.half_carry = false;
         self.registers.f.carry = (value & 0x80) == 0x80;
@@ -877,7 +949,7 @@ impl CPU {
     }
 
     fn swap(&mut self, value: u8) -> u8 {
-        let res = (value & 0xF0 >> 4) | (value & 0xF << 4);
+	let res = ((value & 0xF0) >> 4) | ((value & 0xF) << 4);
 
         self.registers.f.zero = res == 0;
         self.registers.f.subtraction = false;
@@ -887,14 +959,112 @@ impl CPU {
         res
     }
 
+    // The 8bit operands can be registers, the byte at HL or the one after the opcode
+    fn read_arithmetic_target(&self, target: ArithmeticTarget) -> u8 {
+	match target {
+	    ArithmeticTarget::A => self.registers.a,
+	    ArithmeticTarget::B => self.registers.b,
+	    ArithmeticTarget::C => self.registers.c,
+	    ArithmeticTarget::D => self.registers.d,
+	    ArithmeticTarget::E => self.registers.e,
+	    ArithmeticTarget::H => self.registers.h,
+	    ArithmeticTarget::L => self.registers.l,
+	    ArithmeticTarget::HLI => self.bus.read_byte(self.registers.get_hl()),
+	    ArithmeticTarget::D8 => self.bus.read_byte(self.pc.wrapping_add(1)),
+	}
+    }
+
+    fn write_arithmetic_target(&mut self, target: ArithmeticTarget, value: u8) {
+	match target {
+	    ArithmeticTarget::A => self.registers.a = value,
+	    ArithmeticTarget::B => self.registers.b = value,
+	    ArithmeticTarget::C => self.registers.c = value,
+	    ArithmeticTarget::D => self.registers.d = value,
+	    ArithmeticTarget::E => self.registers.e = value,
+	    ArithmeticTarget::H => self.registers.h = value,
+	    ArithmeticTarget::L => self.registers.l = value,
+	    ArithmeticTarget::HLI => self.bus.write_byte(self.registers.get_hl(), value),
+	    ArithmeticTarget::D8 => unreachable!("Immediate values can't be written"),
+	}
+    }
+
+    // SP plus the signed byte after the opcode, the flags come from the low byte
+    fn add_sp_offset(&mut self) -> u16 {
+	let offset = self.bus.read_byte(self.pc.wrapping_add(1));
+	let res = self.sp.wrapping_add(offset as i8 as u16);
+
+	self.registers.f.zero = false;
+	self.registers.f.subtraction = false;
+	self.registers.f.half_carry = (self.sp & 0xF) + (offset as u16 & 0xF) > 0xF;
+	self.registers.f.carry = (self.sp & 0xFF) + offset as u16 > 0xFF;
+
+	res
+    }
+
+    // Turns A back into BCD after an addition or a subtraction of BCD values
+    fn decimal_adjust(&mut self) {
+	let mut a = self.registers.a;
+	let mut carry = self.registers.f.carry;
+
+	if self.registers.f.subtraction {
+	    if self.registers.f.half_carry {
+		a = a.wrapping_sub(0x06);
+	    }
+	    if carry {
+		a = a.wrapping_sub(0x60);
+	    }
+	} else {
+	    if carry || a > 0x99 {
+		a = a.wrapping_add(0x60);
+		carry = true;
+	    }
+	    if self.registers.f.half_carry || (a & 0xF) > 0x9 {
+		a = a.wrapping_add(0x06);
+	    }
+	}
+
+	self.registers.f.zero = a == 0;
+	self.registers.f.half_carry = false;
+	self.registers.f.carry = carry;
+	self.registers.a = a;
+    }
+
+    fn condition(&self, condition: JumpCondition) -> bool {
+	match condition {
+	    JumpCondition::NotZero => !self.registers.f.zero,
+	    JumpCondition::Zero => self.registers.f.zero,
+	    JumpCondition::NotCarry => !self.registers.f.carry,
+	    JumpCondition::Carry => self.registers.f.carry,
+	    JumpCondition::Always => true,
+	}
+    }
+
+    fn push(&mut self, value: u16) {
+	self.sp = self.sp.wrapping_sub(1);
+	self.bus.write_byte(self.sp, (value >> 8) as u8);
+	self.sp = self.sp.wrapping_sub(1);
+	self.bus.write_byte(self.sp, value as u8);
+    }
+
+    fn pop(&mut self) -> u16 {
+	let low = self.bus.read_byte(self.sp) as u16;
+	self.sp = self.sp.wrapping_add(1);
+	let high = self.bus.read_byte(self.sp) as u16;
+	self.sp = self.sp.wrapping_add(1);
+
+	(high << 8) | low
+    }
+
     fn next_word(&self) -> u16 {
-	let lower_nibble = self.bus.read_byte(self.pc+1) as u16;
-	let higher_nibble = self.bus.read_byte(self.pc+2) as u16;
+	let lower_nibble = self.bus.read_byte(self.pc.wrapping_add(1)) as u16;
+	let higher_nibble = self.bus.read_byte(self.pc.wrapping_add(2)) as u16;
 	
 	(higher_nibble << 8) | lower_nibble
     }
 
-    fn jump(&self, condition: bool) -> u16 {
+    fn jump(&mut self, condition: bool) -> u16 {
+	self.branch_taken = condition;
+
 	if condition {
 	    self.next_word()
 	} else {
@@ -902,21 +1072,37 @@ impl CPU {
 	}
     }
 
-    fn jump_relative(&self, condition: bool) -> u16 {
+    fn jump_relative(&mut self, condition: bool) -> u16 {
 	let next = self.pc.wrapping_add(2);
+	self.branch_taken = condition;
 
 	if condition {
-	    let offset = self.bus.read_byte(self.pc+1) as i8;
+	    let offset = self.bus.read_byte(self.pc.wrapping_add(1)) as i8;
+	    next.wrapping_add_signed(offset as i16)
+	} else {
+	    next
+	}
+    }
 
-	    let pc = if offset >= 0 {
-		next.wrapping_add(offset as u16)
-	    } else {
-		next.wrapping_sub(offset.abs() as u16)
-	    };
+    fn call(&mut self, condition: bool) -> u16 {
+	let next = self.pc.wrapping_add(3);
+	self.branch_taken = condition;
 
-	    pc
+	if condition {
+	    self.push(next);
+	    self.next_word()
 	} else {
 	    next
 	}
     }
+
+    fn return_from_call(&mut self, condition: bool) -> u16 {
+	self.branch_taken = condition;
+
+	if condition {
+	    self.pop()
+	} else {
+	    self.pc.wrapping_add(1)
+	}
+    }
 }
@@ -0,0 +1,51 @@
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Model {
+    Dmg0,
+    Dmg,
+    Mgb,
+    Sgb,
+    Sgb2,
+    Cgb,
+    Agb,
+}
+
+impl Model {
+    // The AGB runs CGB software in its Game Boy compatibility mode
+    pub fn is_cgb(&self) -> bool {
+	matches!(self, Model::Cgb | Model::Agb)
+    }
+
+    pub fn is_sgb(&self) -> bool {
+	matches!(self, Model::Sgb | Model::Sgb2)
+    }
+
+    // As accepted by TryFrom
+    pub fn name(&self) -> &'static str {
+	match self {
+	    Model::Dmg0 => "dmg0",
+	    Model::Dmg => "dmg",
+	    Model::Mgb => "mgb",
+	    Model::Sgb => "sgb",
+	    Model::Sgb2 => "sgb2",
+	    Model::Cgb => "cgb",
+	    Model::Agb => "agb",
+	}
+    }
+}
+
+impl std::convert::TryFrom<&str> for Model {
+    type Error = &'static str;
+
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+	match name.to_lowercase().as_str() {
+	    "dmg0" => Ok(Model::Dmg0),
+	    "dmg" => Ok(Model::Dmg),
+	    "mgb" => Ok(Model::Mgb),
+	    "sgb" => Ok(Model::Sgb),
+	    "sgb2" => Ok(Model::Sgb2),
+	    "cgb" => Ok(Model::Cgb),
+	    "agb" => Ok(Model::Agb),
+	    _ => Err("Unknown model, expected one of: dmg0, dmg, mgb, sgb, sgb2, cgb, agb"),
+	}
+    }
+}
@@ -0,0 +1,229 @@
+use std::fmt;
+
+use crate::cpu::{
+    cartridge,
+    gameboy::{Config, GameBoy},
+    joypad::{Button, MAX_PLAYERS},
+    model::Model,
+};
+
+const HEADER: &str = "lb-emu movie 1";
+
+// Order and letters of the buttons on each input line, the same as BizHawk uses.
+// Bit n of a frame's input is the n-th button.
+pub const MOVIE_BUTTONS: [(Button, char); 8] = [
+    (Button::Up, 'U'),
+    (Button::Down, 'D'),
+    (Button::Left, 'L'),
+    (Button::Right, 'R'),
+    (Button::Select, 's'),
+    (Button::Start, 'S'),
+    (Button::B, 'B'),
+    (Button::A, 'A'),
+];
+
+// Input of every frame since power on, and what is needed to start the same
+// console again. Playing it back on that console gives a bit-identical run.
+//
+// The text format is a header line, `key value` settings, then `input` and one
+// line per frame with a group of 8 letters or dots per player, split by `|`.
+pub struct Movie {
+    pub rom_crc32: u32,
+    pub model: Model,
+    // CRC-32 of the boot ROM, None when the boot sequence was skipped
+    pub boot_rom_crc32: Option<u32>,
+    // Where the cartridge clock would start. No RTC is emulated, so recordings
+    // always write 0 and playback ignores it, imported movies keep their own.
+    pub rtc_seed: u64,
+    pub frames: Vec<[u8; MAX_PLAYERS]>,
+    // State hash after the last frame, to verify the playback
+    pub final_hash: Option<u64>,
+}
+
+impl Movie {
+    // Recording has to start from power on
+    pub fn new(gameboy: &GameBoy, config: &Config) -> Movie {
+	Movie {
+	    rom_crc32: gameboy.rom_crc32(),
+	    model: config.model,
+	    boot_rom_crc32: config.boot_rom.as_deref().map(cartridge::crc32),
+	    rtc_seed: 0,
+	    frames: Vec::new(),
+	    final_hash: None,
+	}
+    }
+
+    // For movies converted from other emulators, before any console is started
+    pub fn for_rom(rom: &[u8], model: Model, boot_rom: Option<&[u8]>) -> Movie {
+	Movie {
+	    rom_crc32: cartridge::crc32(rom),
+	    model,
+	    boot_rom_crc32: boot_rom.map(cartridge::crc32),
+	    rtc_seed: 0,
+	    frames: Vec::new(),
+	    final_hash: None,
+	}
+    }
+
+    pub fn len(&self) -> usize {
+	self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+	self.frames.is_empty()
+    }
+
+    pub fn check(&self, gameboy: &GameBoy, config: &Config) -> Result<(), &'static str> {
+	if gameboy.rom_crc32() != self.rom_crc32 {
+	    return Err("The movie was recorded with another ROM");
+	}
+	if config.model != self.model {
+	    return Err("The movie was recorded on another model");
+	}
+	if config.boot_rom.as_deref().map(cartridge::crc32) != self.boot_rom_crc32 {
+	    return Err(match self.boot_rom_crc32 {
+		Some(_) => "The movie was recorded with another boot ROM, pass it with --boot-rom",
+		None => "The movie was recorded without a boot ROM",
+	    });
+	}
+	Ok(())
+    }
+
+    // Stores the buttons held at the start of the frame. Frames after it are
+    // dropped, they are from before a rewind.
+    pub fn record(&mut self, gameboy: &GameBoy) {
+	let frame = gameboy.frames() as usize;
+	let mut input = [0; MAX_PLAYERS];
+	for (player, keys) in input.iter_mut().enumerate() {
+	    for (bit, (button, _)) in MOVIE_BUTTONS.iter().enumerate() {
+		if gameboy.is_pressed(player, *button) {
+		    *keys |= 1 << bit;
+		}
+	    }
+	}
+
+	self.frames.truncate(frame);
+	self.frames.resize(frame, [0; MAX_PLAYERS]);
+	self.frames.push(input);
+    }
+
+    // Holds the buttons of the current frame, returns false once the movie is over
+    pub fn play(&self, gameboy: &mut GameBoy) -> bool {
+	let input = match self.frames.get(gameboy.frames() as usize) {
+	    Some(input) => input,
+	    None => return false,
+	};
+
+	for (player, keys) in input.iter().enumerate() {
+	    for (bit, (button, _)) in MOVIE_BUTTONS.iter().enumerate() {
+		let pressed = keys & (1 << bit) != 0;
+		if pressed != gameboy.is_pressed(player, *button) {
+		    if pressed {
+			gameboy.press_player(player, *button);
+		    } else {
+			gameboy.release_player(player, *button);
+		    }
+		}
+	    }
+	}
+	true
+    }
+
+    pub fn parse(text: &str) -> Result<Movie, &'static str> {
+	let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#'));
+	if lines.next() != Some(HEADER) {
+	    return Err("Not a movie file, or from an incompatible version");
+	}
+
+	let mut rom_crc32 = None;
+	let mut model = None;
+	let mut boot_rom_crc32 = None;
+	let mut rtc_seed = 0;
+	let mut final_hash = None;
+	for line in lines.by_ref() {
+	    if line == "input" {
+		break;
+	    }
+	    let (key, value) = line.split_once(' ').ok_or("Invalid movie setting")?;
+	    let hex = |value: &str| u32::from_str_radix(value, 16).map_err(|_| "Invalid movie setting");
+	    match key {
+		"rom" => rom_crc32 = Some(hex(value)?),
+		"model" => model = Some(Model::try_from(value)?),
+		"boot" if value == "skip" => boot_rom_crc32 = None,
+		"boot" => boot_rom_crc32 = Some(hex(value)?),
+		"rtc" => rtc_seed = value.parse().map_err(|_| "Invalid movie setting")?,
+		"final" => final_hash = Some(u64::from_str_radix(value, 16).map_err(|_| "Invalid movie setting")?),
+		_ => return Err("Unknown movie setting"),
+	    }
+	}
+
+	let mut frames = Vec::new();
+	for line in lines {
+	    let mut input = [0; MAX_PLAYERS];
+	    let groups: Vec<&str> = line.split('|').collect();
+	    if groups.len() > MAX_PLAYERS {
+		return Err("Movie input for more than 4 players");
+	    }
+	    for (keys, group) in input.iter_mut().zip(groups) {
+		*keys = parse_buttons(group)?;
+	    }
+	    frames.push(input);
+	}
+
+	Ok(Movie {
+	    rom_crc32: rom_crc32.ok_or("The movie doesn't say which ROM it is for")?,
+	    model: model.ok_or("The movie doesn't say which model it is for")?,
+	    boot_rom_crc32,
+	    rtc_seed,
+	    frames,
+	    final_hash,
+	})
+    }
+}
+
+fn parse_buttons(group: &str) -> Result<u8, &'static str> {
+    if group.chars().count() != MOVIE_BUTTONS.len() {
+	return Err("Movie input lines have 8 buttons per player");
+    }
+
+    let mut keys = 0;
+    for (bit, (letter, (_, expected))) in group.chars().zip(MOVIE_BUTTONS).enumerate() {
+	match letter {
+	    '.' => {}
+	    letter if letter == expected => keys |= 1 << bit,
+	    _ => return Err("Unexpected letter in the movie input"),
+	}
+    }
+    Ok(keys)
+}
+
+impl fmt::Display for Movie {
+    // Only the players that pressed something are written
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	writeln!(f, "{}", HEADER)?;
+	writeln!(f, "rom {:08X}", self.rom_crc32)?;
+	writeln!(f, "model {}", self.model.name())?;
+	match self.boot_rom_crc32 {
+	    Some(crc32) => writeln!(f, "boot {:08X}", crc32)?,
+	    None => writeln!(f, "boot skip")?,
+	}
+	writeln!(f, "rtc {}", self.rtc_seed)?;
+	if let Some(hash) = self.final_hash {
+	    writeln!(f, "final {:016X}", hash)?;
+	}
+	writeln!(f, "input")?;
+
+	let players = (1..MAX_PLAYERS).rev().find(|player| self.frames.iter().any(|input| input[*player] != 0)).unwrap_or(0) + 1;
+	for input in &self.frames {
+	    let groups: Vec<String> = input[..players]
+		.iter()
+		.map(|keys| {
+		    let letter = |(bit, (_, letter)): (usize, &(Button, char))| if keys & (1 << bit) != 0 { *letter } else { '.' };
+		    MOVIE_BUTTONS.iter().enumerate().map(letter).collect()
+		})
+		.collect();
+	    writeln!(f, "{}", groups.join("|"))?;
+	}
+	Ok(())
+    }
+}
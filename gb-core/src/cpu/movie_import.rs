@@ -0,0 +1,419 @@
+// Converts the input logs of other emulators into our movies: BizHawk .bk2
+// files, zip archives with a text input log, and VisualBoyAdvance .vbm files.
+// Their sync settings are followed as far as this emulator can, the rest is
+// reported as warnings since the playback will likely desync.
+use serde_json::Value;
+
+use crate::cpu::{
+    joypad::{Button, MAX_PLAYERS},
+    model::Model,
+    movie::{Movie, MOVIE_BUTTONS},
+};
+
+const VBM_SIGNATURE: &[u8; 4] = b"VBM\x1A";
+// Bits of a .vbm controller word, the higher ones are GBA buttons, reset and tilt
+const VBM_BUTTONS: [Button; 8] = [
+    Button::A,
+    Button::B,
+    Button::Select,
+    Button::Start,
+    Button::Right,
+    Button::Left,
+    Button::Up,
+    Button::Down,
+];
+const VBM_RESET: u16 = 0x0C00;
+const VBM_TILT: u16 = 0xF000;
+// Options at 0x17: the BIOS file was used, unless its intro was skipped
+const VBM_USE_BIOS: u8 = 0x01;
+const VBM_SKIP_BIOS: u8 = 0x02;
+
+// A column of the BizHawk input log
+enum Column {
+    Button(usize, Button),
+    Power,
+    Ignored,
+}
+
+// Reads one of our movies, or converts one from another emulator along with
+// the warnings about what it needs that can't be followed
+pub fn import_movie(data: &[u8], rom: &[u8], boot_rom: Option<&[u8]>) -> Result<(Movie, Vec<String>), &'static str> {
+    if data.starts_with(b"PK\x03\x04") {
+	bk2(data, rom, boot_rom)
+    } else if data.starts_with(VBM_SIGNATURE) {
+	vbm(data, rom, boot_rom)
+    } else {
+	let text = std::str::from_utf8(data).map_err(|_| "Not a movie file")?;
+	Ok((Movie::parse(text)?, Vec::new()))
+    }
+}
+
+fn bk2(data: &[u8], rom: &[u8], boot_rom: Option<&[u8]>) -> Result<(Movie, Vec<String>), &'static str> {
+    let header = text(zip_entry(data, "Header.txt")?.ok_or("The .bk2 file has no Header.txt")?);
+    let input_log = text(zip_entry(data, "Input Log.txt")?.ok_or("The .bk2 file has no Input Log.txt")?);
+    let settings = match zip_entry(data, "SyncSettings.json")? {
+	Some(json) => serde_json::from_str(&text(json)).map_err(|_| "Invalid SyncSettings.json in the .bk2 file")?,
+	None => Value::Null,
+    };
+    // The settings of the core are wrapped with their type
+    let settings = settings.get("o").unwrap_or(&settings);
+    let value = |key: &str| header.lines().find_map(|line| line.trim().strip_prefix(key)?.strip_prefix(' ').map(str::trim));
+    let enabled = |key: &str| settings.get(key).and_then(Value::as_bool).unwrap_or(false);
+    let mut warnings = Vec::new();
+
+    match value("Platform") {
+	Some("GB" | "GBC" | "SGB") | None => {}
+	Some(_) => return Err("The .bk2 movie isn't for a single Game Boy"),
+    }
+    if value("StartsFromSavestate").is_some_and(|value| value.eq_ignore_ascii_case("true")) {
+	return Err("The movie starts from a BizHawk save state, only movies from power on can be played");
+    }
+    if value("StartsFromSaveRam").is_some_and(|value| value.eq_ignore_ascii_case("true")) {
+	return Err("The movie starts from saved cartridge RAM, only movies from power on can be played");
+    }
+    if let Some(hash) = value("SHA1") {
+	if !hash.trim_start_matches("SHA1:").eq_ignore_ascii_case(&sha1(rom)) {
+	    return Err("The movie was recorded with another ROM");
+	}
+    }
+
+    let model = match settings.get("ConsoleMode").map(console_mode) {
+	Some(Ok(Some(model))) => model,
+	Some(Ok(None)) | None => {
+	    // Automatic, IsCGBMode tells what the core picked
+	    match value("IsCGBMode") {
+		Some("1" | "True" | "true") => Model::Cgb,
+		Some(_) => Model::Dmg,
+		None if value("Platform") == Some("SGB") => Model::Sgb,
+		None => automatic_model(rom),
+	    }
+	}
+	Some(Err(mode)) => {
+	    warnings.push(format!("Unknown console mode {}, using the model the ROM asks for", mode));
+	    automatic_model(rom)
+	}
+    };
+
+    let boot_rom = if enabled("EnableBIOS") {
+	if boot_rom.is_none() {
+	    warnings.push("The movie was recorded with the boot ROM, pass it with --boot-rom".to_string());
+	}
+	boot_rom
+    } else {
+	None
+    };
+
+    let mut movie = Movie::for_rom(rom, model, boot_rom);
+    // Carried over for the record only, nothing reads it without an emulated RTC
+    movie.rtc_seed = ["RTCInitialTime", "InitialTime"].iter().find_map(|key| settings.get(*key)?.as_u64()).unwrap_or(0);
+    if has_rtc(rom) {
+	warnings.push("The cartridge clock isn't emulated, the movie desyncs once the game reads it".to_string());
+    }
+    if enabled("UseRealTime") {
+	warnings.push("The movie followed the clock of the host, which can't be replayed".to_string());
+    }
+    if enabled("EqualLengthFrames") {
+	warnings.push("The movie was recorded with frames of equal length, here they end at VBlank".to_string());
+    }
+
+    read_input_log(&input_log, &mut movie, &mut warnings)?;
+    Ok((movie, warnings))
+}
+
+// The LogKey line names the columns, `#` starting a group and `|` ending a column.
+// Frame lines have the same groups between `|`, a letter or a dot per button.
+fn read_input_log(log: &str, movie: &mut Movie, warnings: &mut Vec<String>) -> Result<(), &'static str> {
+    let mut groups: Option<Vec<Vec<Column>>> = None;
+    let mut analog = false;
+    let mut power = false;
+
+    for line in log.lines().map(str::trim) {
+	if let Some(key) = line.strip_prefix("LogKey:") {
+	    let mut columns = Vec::new();
+	    for group in key.split('#').skip(1) {
+		let names = group.split('|').filter(|name| !name.is_empty());
+		columns.push(names.map(|name| column(name, warnings)).collect());
+	    }
+	    groups = Some(columns);
+	    continue;
+	}
+	if !line.starts_with('|') {
+	    continue;
+	}
+
+	let groups = groups.as_ref().ok_or("The .bk2 input log has no LogKey")?;
+	let frame = movie.frames.len();
+	let mut input = [0; MAX_PLAYERS];
+	for (columns, text) in groups.iter().zip(line.trim_matches('|').split('|')) {
+	    // Analog values come first, each followed by a comma
+	    let letters = text.rsplit(',').next().unwrap_or_default();
+	    analog |= letters.len() != text.len();
+	    let columns = columns.get(columns.len().checked_sub(letters.chars().count()).ok_or("The .bk2 input log doesn't match its LogKey")?..);
+
+	    for (letter, column) in letters.chars().zip(columns.into_iter().flatten()) {
+		if letter == '.' || letter == ' ' {
+		    continue;
+		}
+		match column {
+		    Column::Button(player, button) => input[*player] |= button_bit(*button),
+		    Column::Power if frame > 0 && !power => {
+			power = true;
+			warnings.push(format!("The movie power cycles the console at frame {}, which isn't supported", frame));
+		    }
+		    Column::Power | Column::Ignored => {}
+		}
+	    }
+	}
+	movie.frames.push(input);
+    }
+
+    if analog {
+	warnings.push("The analog input of the movie is ignored".to_string());
+    }
+    Ok(())
+}
+
+// Multiplayer cores start the names with the player, as in "P2 A"
+fn column(name: &str, warnings: &mut Vec<String>) -> Column {
+    let (player, button) = match name.strip_prefix('P').and_then(|rest| rest.split_once(' ')) {
+	Some((number, button)) => match number.parse::<usize>() {
+	    Ok(number) if (1..=MAX_PLAYERS).contains(&number) => (number - 1, button),
+	    _ => (0, name),
+	},
+	None => (0, name),
+    };
+
+    let button = match button {
+	"Up" => Button::Up,
+	"Down" => Button::Down,
+	"Left" => Button::Left,
+	"Right" => Button::Right,
+	"Select" => Button::Select,
+	"Start" => Button::Start,
+	"B" => Button::B,
+	"A" => Button::A,
+	"Power" | "Reset" => return Column::Power,
+	_ => {
+	    warnings.push(format!("The {} input of the movie is ignored", name));
+	    return Column::Ignored;
+	}
+    };
+    Column::Button(player, button)
+}
+
+// Gambatte numbers the modes, other cores write their names. Ok(None) is automatic.
+fn console_mode(mode: &Value) -> Result<Option<Model>, String> {
+    let name = match mode {
+	Value::Number(number) => match number.as_i64() {
+	    Some(-1 | 0) => "auto",
+	    Some(1) => "gb",
+	    Some(2) => "gbc",
+	    Some(3) => "gba",
+	    _ => "",
+	}
+	.to_string(),
+	Value::String(name) => name.to_ascii_lowercase(),
+	_ => String::new(),
+    };
+
+    let model = [
+	("auto", None),
+	("default", None),
+	("sgb2", Some(Model::Sgb2)),
+	("sgb", Some(Model::Sgb)),
+	("gba", Some(Model::Agb)),
+	("agb", Some(Model::Agb)),
+	("gbc", Some(Model::Cgb)),
+	("cgb", Some(Model::Cgb)),
+	("mgb", Some(Model::Mgb)),
+	("dmg", Some(Model::Dmg)),
+	("gb", Some(Model::Dmg)),
+    ]
+    .into_iter()
+    .find(|(part, _)| name.contains(part));
+
+    match model {
+	Some((_, model)) => Ok(model),
+	None => Err(mode.to_string()),
+    }
+}
+
+fn vbm(data: &[u8], rom: &[u8], boot_rom: Option<&[u8]>) -> Result<(Movie, Vec<String>), &'static str> {
+    if data.len() < 0x40 {
+	return Err("Truncated .vbm file");
+    }
+    let u32_at = |position: usize| u32::from_le_bytes(data[position..position + 4].try_into().unwrap());
+    if u32_at(0x04) != 1 {
+	return Err("Unsupported .vbm version");
+    }
+    if data[0x14] & 1 != 0 {
+	return Err("The movie starts from a VisualBoyAdvance save state, only movies from power on can be played");
+    }
+    if data[0x14] & 2 != 0 {
+	return Err("The movie starts from saved cartridge RAM, only movies from power on can be played");
+    }
+
+    // The system flags, refined by the emulator type option: 4 is GBA, 5 SGB2
+    let (system, emulator_type) = (data[0x16], u32_at(0x20));
+    let model = if system & 1 != 0 {
+	return Err("The movie is for the Game Boy Advance");
+    } else if system & 2 != 0 {
+	if emulator_type == 4 { Model::Agb } else { Model::Cgb }
+    } else if system & 4 != 0 {
+	if emulator_type == 5 { Model::Sgb2 } else { Model::Sgb }
+    } else {
+	Model::Dmg
+    };
+
+    // Only the title and header checksum of the ROM are kept
+    if rom.len() < 0x150 || rom[0x134..0x140] != data[0x24..0x30] || rom[0x14D] != data[0x31] {
+	return Err("The movie was recorded with another ROM");
+    }
+
+    let mut warnings = Vec::new();
+    let options = data[0x17];
+    if has_rtc(rom) {
+	warnings.push("The cartridge clock isn't emulated, the movie desyncs once the game reads it".to_string());
+    }
+    if model.is_cgb() && options & 0x20 == 0 {
+	warnings.push("The movie was recorded with the old HDMA timing of VisualBoyAdvance".to_string());
+    }
+    let boot_rom = if options & VBM_USE_BIOS != 0 && options & VBM_SKIP_BIOS == 0 {
+	if boot_rom.is_none() {
+	    warnings.push("The movie was recorded with the boot ROM, pass it with --boot-rom".to_string());
+	}
+	boot_rom
+    } else {
+	None
+    };
+
+    let players: Vec<usize> = (0..MAX_PLAYERS).filter(|player| data[0x15] & (1 << player) != 0).collect();
+    let frames = u32_at(0x0C) as usize;
+    let start = u32_at(0x3C) as usize;
+    let input = frames
+	.checked_mul(players.len() * 2)
+	.and_then(|length| data.get(start..start.checked_add(length)?))
+	.ok_or("Truncated .vbm file")?;
+
+    let mut movie = Movie::for_rom(rom, model, boot_rom);
+    let (mut reset, mut tilt) = (false, false);
+    for (frame, words) in input.chunks(players.len() * 2).enumerate() {
+	let mut keys = [0; MAX_PLAYERS];
+	for (player, word) in players.iter().zip(words.chunks(2)) {
+	    let word = u16::from_le_bytes([word[0], word[1]]);
+	    for (bit, button) in VBM_BUTTONS.iter().enumerate() {
+		if word & (1 << bit) != 0 {
+		    keys[*player] |= button_bit(*button);
+		}
+	    }
+	    if word & VBM_RESET != 0 && frame > 0 && !reset {
+		reset = true;
+		warnings.push(format!("The movie resets the console at frame {}, which isn't supported", frame));
+	    }
+	    tilt |= word & VBM_TILT != 0;
+	}
+	movie.frames.push(keys);
+    }
+    if tilt {
+	warnings.push("The tilt input of the movie is ignored".to_string());
+    }
+    Ok((movie, warnings))
+}
+
+fn button_bit(button: Button) -> u8 {
+    let bit = MOVIE_BUTTONS.iter().position(|(candidate, _)| *candidate == button).unwrap();
+    1 << bit
+}
+
+// As Gambatte picks it on automatic
+fn automatic_model(rom: &[u8]) -> Model {
+    match rom.get(0x143) {
+	Some(flags) if flags & 0x80 != 0 => Model::Cgb,
+	_ => Model::Dmg,
+    }
+}
+
+// MBC3 with a timer
+fn has_rtc(rom: &[u8]) -> bool {
+    matches!(rom.get(0x147), Some(0x0F | 0x10))
+}
+
+// BizHawk writes the text files with a byte order mark
+fn text(data: Vec<u8>) -> String {
+    let data = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&data);
+    String::from_utf8_lossy(data).into_owned()
+}
+
+// A file of the zip archive, found through the central directory at its end
+fn zip_entry(zip: &[u8], name: &str) -> Result<Option<Vec<u8>>, &'static str> {
+    const INVALID: &str = "Invalid .bk2 archive";
+    let u16_at = |position: usize| zip.get(position..position + 2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as usize).ok_or(INVALID);
+    let u32_at = |position: usize| zip.get(position..position + 4).map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()) as usize).ok_or(INVALID);
+
+    let end = (0..zip.len().saturating_sub(21)).rev().find(|position| zip[*position..].starts_with(b"PK\x05\x06")).ok_or(INVALID)?;
+    let mut position = u32_at(end + 16)?;
+    for _ in 0..u16_at(end + 10)? {
+	if !zip.get(position..).is_some_and(|entry| entry.starts_with(b"PK\x01\x02")) {
+	    return Err(INVALID);
+	}
+	let method = u16_at(position + 10)?;
+	let size = u32_at(position + 20)?;
+	let name_length = u16_at(position + 28)?;
+	let local = u32_at(position + 42)?;
+	let entry_name = zip.get(position + 46..position + 46 + name_length).ok_or(INVALID)?;
+	position += 46 + name_length + u16_at(position + 30)? + u16_at(position + 32)?;
+	if entry_name != name.as_bytes() {
+	    continue;
+	}
+
+	let start = local + 30 + u16_at(local + 26)? + u16_at(local + 28)?;
+	let data = zip.get(start..start + size).ok_or(INVALID)?;
+	return match method {
+	    0 => Ok(Some(data.to_vec())),
+	    8 => miniz_oxide::inflate::decompress_to_vec(data).map(Some).map_err(|_| INVALID),
+	    _ => Err("Unsupported compression in the .bk2 archive"),
+	};
+    }
+    Ok(None)
+}
+
+// BizHawk identifies the ROM by its SHA-1, in hex
+pub fn sha1(data: &[u8]) -> String {
+    let mut hash: [u32; 5] = [0x6745_2301, 0xEFCD_AB89, 0x98BA_DCFE, 0x1032_5476, 0xC3D2_E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+	message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+	let mut words = [0u32; 80];
+	for (word, bytes) in words.iter_mut().zip(block.chunks(4)) {
+	    *word = u32::from_be_bytes(bytes.try_into().unwrap());
+	}
+	for index in 16..80 {
+	    words[index] = (words[index - 3] ^ words[index - 8] ^ words[index - 14] ^ words[index - 16]).rotate_left(1);
+	}
+
+	let [mut a, mut b, mut c, mut d, mut e] = hash;
+	for (index, word) in words.iter().enumerate() {
+	    let (f, k) = match index {
+		0..=19 => ((b & c) | (!b & d), 0x5A82_7999),
+		20..=39 => (b ^ c ^ d, 0x6ED9_EBA1),
+		40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1B_BCDC),
+		_ => (b ^ c ^ d, 0xCA62_C1D6),
+	    };
+	    let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*word);
+	    e = d;
+	    d = c;
+	    c = b.rotate_left(30);
+	    b = a;
+	    a = temp;
+	}
+	for (value, add) in hash.iter_mut().zip([a, b, c, d, e]) {
+	    *value = value.wrapping_add(add);
+	}
+    }
+    hash.iter().map(|value| format!("{:08X}", value)).collect()
+}
@@ -0,0 +1,81 @@
+use crate::cpu::savestate::{SaveState, StateReader, StateWriter};
+
+pub const OAM_DMA_LENGTH: u8 = 0xA0;
+
+// OAM DMA, started by writing the source page to DMA (0xFF46). After an
+// M-cycle of setup a byte is copied every M-cycle, 160 of them in all.
+pub struct OamDma {
+    register: u8,
+    source: u16,
+    // Set by a write, the transfer (re)starts on the next M-cycle
+    starting: bool,
+    // Offset of the next byte, None when no transfer is running
+    position: Option<u8>,
+}
+
+impl OamDma {
+    pub fn new() -> Self {
+	OamDma {
+	    register: 0xFF,
+	    source: 0,
+	    starting: false,
+	    position: None,
+	}
+    }
+
+    // OAM is out of reach of the CPU while the bytes are copied
+    pub fn active(&self) -> bool {
+	self.position.is_some()
+    }
+
+    pub fn read_register(&self) -> u8 {
+	self.register
+    }
+
+    // A transfer already running goes on until the new one starts
+    pub fn write_register(&mut self, value: u8) {
+	self.register = value;
+	self.starting = true;
+    }
+
+    // Called every M-cycle, returns the source address and OAM offset of the byte to copy
+    pub fn tick(&mut self) -> Option<(u16, u8)> {
+	let copy = self.position.map(|position| {
+	    self.position = if position + 1 < OAM_DMA_LENGTH { Some(position + 1) } else { None };
+	    (self.source + position as u16, position)
+	});
+
+	if self.starting {
+	    self.starting = false;
+	    // Pages from 0xE0 up read work RAM, as its echo does
+	    let page = if self.register >= 0xE0 { self.register - 0x20 } else { self.register };
+	    self.source = (page as u16) << 8;
+	    self.position = Some(0);
+	}
+
+	copy
+    }
+}
+
+impl SaveState for OamDma {
+    fn save_state(&self, writer: &mut StateWriter) {
+	writer.u8(self.register);
+	writer.u16(self.source);
+	writer.bool(self.starting);
+	writer.bool(self.position.is_some());
+	writer.u8(self.position.unwrap_or(0));
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), &'static str> {
+	self.register = reader.u8()?;
+	self.source = reader.u16()?;
+	self.starting = reader.bool()?;
+	let running = reader.bool()?;
+	let position = reader.u8()?;
+	if position >= OAM_DMA_LENGTH {
+	    return Err("Invalid OAM DMA position");
+	}
+	self.position = if running { Some(position) } else { None };
+	Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+use crate::cpu::savestate::{SaveState, StateReader, StateWriter};
+
+// CGB palette memory, accessed through BCPS/BCPD (background) and OCPS/OCPD (objects).
+// Each of the 8 palettes holds 4 little-endian RGB555 colors.
+pub struct PaletteRam {
+    data: [u8; 64],
+    index: u8,
+    auto_increment: bool,
+}
+
+impl PaletteRam {
+    pub fn new() -> Self {
+	PaletteRam {
+	    data: [0xFF; 64],
+	    index: 0,
+	    auto_increment: false,
+	}
+    }
+
+    pub fn read_spec(&self) -> u8 {
+	(if self.auto_increment { 1 } else { 0 }) << 7 | 0x40 | self.index
+    }
+
+    pub fn write_spec(&mut self, value: u8) {
+	self.index = value & 0x3F;
+	self.auto_increment = (value & 0x80) == 0x80;
+    }
+
+    pub fn read_data(&self) -> u8 {
+	self.data[self.index as usize]
+    }
+
+    // Writes are dropped while the PPU is drawing, but the index still advances.
+    pub fn write_data(&mut self, value: u8, accessible: bool) {
+	if accessible {
+	    self.data[self.index as usize] = value;
+	}
+
+	if self.auto_increment {
+	    self.index = (self.index + 1) & 0x3F;
+	}
+    }
+
+    pub fn data(&self) -> &[u8; 64] {
+	&self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8; 64] {
+	&mut self.data
+    }
+
+    pub fn color(&self, palette: u8, color_id: u8) -> u16 {
+	let offset = ((palette & 0x7) * 8 + color_id * 2) as usize;
+	(self.data[offset + 1] as u16) << 8 | self.data[offset] as u16
+    }
+
+    pub fn set_color(&mut self, palette: u8, color_id: u8, color: u16) {
+	let offset = ((palette & 0x7) * 8 + color_id * 2) as usize;
+	self.data[offset] = (color & 0xFF) as u8;
+	self.data[offset + 1] = (color >> 8) as u8;
+    }
+}
+
+// Scales every 5bit channel to 8bit, no color correction.
+pub fn rgb555_to_rgb888(color: u16) -> u32 {
+    let expand = |channel: u16| -> u32 {
+	let channel = (channel & 0x1F) as u32;
+	(channel << 3) | (channel >> 2)
+    };
+
+    expand(color) << 16 | expand(color >> 5) << 8 | expand(color >> 10)
+}
+
+// Approximates how the CGB LCD displays a color: channels bleed into each
+// other and the brightest values are less saturated than raw RGB555.
+pub fn correct_color(color: u16) -> u32 {
+    let r = (color & 0x1F) as u32;
+    let g = ((color >> 5) & 0x1F) as u32;
+    let b = ((color >> 10) & 0x1F) as u32;
+
+    let red = (r * 13 + g * 2 + b) >> 1;
+    let green = (g * 3 + b) << 1;
+    let blue = (r * 3 + g * 2 + b * 11) >> 1;
+
+    red << 16 | green << 8 | blue
+}
+
+// The AGB screen is a lot darker than the CGB one when playing CGB games,
+// mostly crushing the darker half of every channel.
+pub fn agb_color(color: u16) -> u32 {
+    let darken = |channel: u16| -> u32 {
+	let channel = (channel & 0x1F) as f32 / 31.0;
+	(channel.powf(1.8) * 255.0).round() as u32
+    };
+
+    darken(color) << 16 | darken(color >> 5) << 8 | darken(color >> 10)
+}
+
+impl SaveState for PaletteRam {
+    fn save_state(&self, writer: &mut StateWriter) {
+	writer.bytes(&self.data);
+	writer.u8(self.index);
+	writer.bool(self.auto_increment);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), &'static str> {
+	reader.bytes(&mut self.data)?;
+	self.index = reader.u8()? & 0x3F;
+	self.auto_increment = reader.bool()?;
+	Ok(())
+    }
+}
@@ -0,0 +1,703 @@
+use crate::cpu::{
+    compatibility::CompatibilityPalette,
+    model::Model,
+    palette::{agb_color, correct_color, rgb555_to_rgb888, PaletteRam},
+    savestate::{copy_prefix, SaveState, StateReader, StateWriter},
+};
+
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+
+pub const VBLANK_INTERRUPT: u8 = 0b1;
+pub const STAT_INTERRUPT: u8 = 0b10;
+
+const DOTS_PER_LINE: u32 = 456;
+const OAM_SCAN_DOTS: u32 = 80;
+const TRANSFER_DOTS: u32 = 172;
+const LINES_PER_FRAME: u8 = 154;
+const DOTS_PER_FRAME: u32 = DOTS_PER_LINE * LINES_PER_FRAME as u32;
+
+// DMG shades, from color 0 (lightest) to color 3 (darkest)
+const DMG_SHADES: [u32; 4] = [0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000];
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum PpuMode {
+    HBlank,
+    VBlank,
+    OamScan,
+    Transfer,
+}
+
+impl std::convert::From<PpuMode> for u8 {
+    fn from(mode: PpuMode) -> u8 {
+	match mode {
+	    PpuMode::HBlank => 0,
+	    PpuMode::VBlank => 1,
+	    PpuMode::OamScan => 2,
+	    PpuMode::Transfer => 3,
+	}
+    }
+}
+
+impl std::convert::From<u8> for PpuMode {
+    fn from(mode: u8) -> Self {
+	match mode & 0x3 {
+	    0 => PpuMode::HBlank,
+	    1 => PpuMode::VBlank,
+	    2 => PpuMode::OamScan,
+	    _ => PpuMode::Transfer,
+	}
+    }
+}
+
+// BG map attributes stored in VRAM bank 1, also used for the CGB bits of OAM entries.
+#[derive(Copy, Clone)]
+pub struct TileAttributes {
+    pub palette: u8,
+    pub bank: usize,
+    pub x_flip: bool,
+    pub y_flip: bool,
+    pub priority: bool,
+}
+
+impl std::convert::From<u8> for TileAttributes {
+    fn from(byte: u8) -> Self {
+	TileAttributes {
+	    palette: byte & 0x7,
+	    bank: ((byte >> 3) & 0b1) as usize,
+	    x_flip: (byte & 0x20) == 0x20,
+	    y_flip: (byte & 0x40) == 0x40,
+	    priority: (byte & 0x80) == 0x80,
+	}
+    }
+}
+
+struct Object {
+    y: i16,
+    x: i16,
+    tile: u8,
+    flags: u8,
+}
+
+pub struct Ppu {
+    vram: [[u8; 0x2000]; 2],
+    vram_bank: usize,
+    oam: [u8; 0xA0],
+
+    lcdc: u8,
+    stat: u8,
+    scy: u8,
+    scx: u8,
+    ly: u8,
+    lyc: u8,
+    bgp: u8,
+    obp0: u8,
+    obp1: u8,
+    wy: u8,
+    wx: u8,
+
+    model: Model,
+    cgb_mode: bool,
+    compatibility: bool,
+    color_correction: bool,
+    bg_palettes: PaletteRam,
+    obj_palettes: PaletteRam,
+    opri: u8,
+
+    mode: PpuMode,
+    dots: u32,
+    window_line: u8,
+    stat_line: bool,
+    pending_interrupts: u8,
+    frames: u64,
+    framebuffer: Vec<u32>,
+    shades: Vec<u8>,
+}
+
+impl Ppu {
+    pub fn new(model: Model) -> Self {
+	let cgb_mode = model.is_cgb();
+
+	Ppu {
+	    vram: [[0; 0x2000]; 2],
+	    vram_bank: 0,
+	    oam: [0; 0xA0],
+	    lcdc: 0,
+	    stat: 0,
+	    scy: 0,
+	    scx: 0,
+	    ly: 0,
+	    lyc: 0,
+	    bgp: 0,
+	    obp0: 0,
+	    obp1: 0,
+	    wy: 0,
+	    wx: 0,
+	    model,
+	    cgb_mode,
+	    compatibility: false,
+	    color_correction: false,
+	    bg_palettes: PaletteRam::new(),
+	    obj_palettes: PaletteRam::new(),
+	    opri: if cgb_mode { 0 } else { 1 },
+	    mode: PpuMode::HBlank,
+	    dots: 0,
+	    window_line: 0,
+	    stat_line: false,
+	    pending_interrupts: 0,
+	    frames: 0,
+	    framebuffer: vec![DMG_SHADES[0]; SCREEN_WIDTH * SCREEN_HEIGHT],
+	    shades: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+	}
+    }
+
+    // Frames completed since power on, they keep counting with the LCD off
+    pub fn frames(&self) -> u64 {
+	self.frames
+    }
+
+    pub fn mode(&self) -> PpuMode {
+	self.mode
+    }
+
+    pub fn lcd_enabled(&self) -> bool {
+	(self.lcdc & 0x80) == 0x80
+    }
+
+    // Used when a CGB runs a DMG game, the shades picked by BGP, OBP0 and OBP1
+    // are then looked up in these colors instead of being drawn in grayscale.
+    pub fn set_compatibility_palette(&mut self, palette: &CompatibilityPalette) {
+	for color_id in 0..4 {
+	    self.bg_palettes.set_color(0, color_id, palette.bg[color_id as usize]);
+	    self.obj_palettes.set_color(0, color_id, palette.obj0[color_id as usize]);
+	    self.obj_palettes.set_color(1, color_id, palette.obj1[color_id as usize]);
+	}
+    }
+
+    // A CGB running a DMG game renders like a DMG, except for the colors
+    pub fn enter_compatibility_mode(&mut self) {
+	self.cgb_mode = false;
+	self.compatibility = true;
+	self.opri = 1;
+    }
+
+    pub fn set_color_correction(&mut self, enabled: bool) {
+	self.color_correction = enabled;
+    }
+
+    pub fn color_correction(&self) -> bool {
+	self.color_correction
+    }
+
+    // Pixels are stored as 0x00RRGGBB
+    pub fn framebuffer(&self) -> &[u32] {
+	&self.framebuffer
+    }
+
+    // DMG shade (0-3) of every pixel, before any coloring is applied
+    pub fn shades(&self) -> &[u8] {
+	&self.shades
+    }
+
+    // The SGB receives VRAM transfers by reading the 256 tiles on screen,
+    // laid out by the game as 20 tiles per row from the top left corner.
+    pub fn screen_tile_data(&self) -> Vec<u8> {
+	let map_base = if (self.lcdc & 0x8) == 0x8 { 0x1C00 } else { 0x1800 };
+	let mut data = Vec::with_capacity(0x1000);
+
+	for index in 0..256 {
+	    let tile = self.vram[0][map_base + (index / 20) * 32 + index % 20];
+	    let tile_address = if (self.lcdc & 0x10) == 0x10 {
+		tile as usize * 16
+	    } else {
+		(0x1000 + (tile as i8 as i32) * 16) as usize
+	    };
+
+	    data.extend_from_slice(&self.vram[0][tile_address..tile_address + 16]);
+	}
+
+	data
+    }
+
+    // Video memory without the access restrictions, for BESS save states
+    pub fn vram_banks(&self) -> &[[u8; 0x2000]; 2] {
+	&self.vram
+    }
+
+    pub fn oam(&self) -> &[u8; 0xA0] {
+	&self.oam
+    }
+
+    pub fn palette_data(&self) -> (&[u8; 64], &[u8; 64]) {
+	(self.bg_palettes.data(), self.obj_palettes.data())
+    }
+
+    // Buffers of another size are copied as far as they go
+    pub fn load_memory(&mut self, vram: &[u8], oam: &[u8], bg_palettes: &[u8], obj_palettes: &[u8]) {
+	for (bank, data) in self.vram.iter_mut().zip(vram.chunks(0x2000)) {
+	    bank[..data.len()].copy_from_slice(data);
+	}
+	copy_prefix(&mut self.oam, oam);
+	copy_prefix(self.bg_palettes.data_mut(), bg_palettes);
+	copy_prefix(self.obj_palettes.data_mut(), obj_palettes);
+    }
+
+    // BESS only has LY, drawing resumes from the start of that line
+    pub fn restore_position(&mut self, ly: u8, stat: u8) {
+	self.stat = stat & 0x78;
+	self.dots = 0;
+	self.window_line = 0;
+	self.pending_interrupts = 0;
+
+	if self.lcd_enabled() {
+	    self.ly = ly % LINES_PER_FRAME;
+	    self.mode = if self.ly >= SCREEN_HEIGHT as u8 { PpuMode::VBlank } else { PpuMode::OamScan };
+	} else {
+	    self.ly = 0;
+	    self.mode = PpuMode::HBlank;
+	}
+	self.update_stat_line();
+    }
+
+    // The boot ROM hands over at the end of line 153, still in VBlank but where
+    // LY already reads 0, so STAT reads 0x85. Line 0 starts right after.
+    pub fn hand_over(&mut self) {
+	if self.lcd_enabled() {
+	    self.ly = 0;
+	    self.dots = 0;
+	    self.mode = PpuMode::VBlank;
+	    self.update_stat_line();
+	}
+    }
+
+    pub fn read_vram(&self, address: u16) -> u8 {
+	if self.mode == PpuMode::Transfer {
+	    return 0xFF;
+	}
+
+	self.vram[self.vram_bank][(address - 0x8000) as usize]
+    }
+
+    pub fn write_vram(&mut self, address: u16, value: u8) {
+	if self.mode != PpuMode::Transfer {
+	    self.vram[self.vram_bank][(address - 0x8000) as usize] = value;
+	}
+    }
+
+    // VRAM DMA isn't blocked by mode 3 like the CPU is
+    pub fn dma_write_vram(&mut self, address: u16, value: u8) {
+	self.vram[self.vram_bank][(address - 0x8000) as usize] = value;
+    }
+
+    // OAM DMA writes whatever mode the PPU is in
+    pub fn dma_write_oam(&mut self, offset: u8, value: u8) {
+	self.oam[offset as usize] = value;
+    }
+
+    pub fn read_oam(&self, address: u16) -> u8 {
+	match self.mode {
+	    PpuMode::OamScan | PpuMode::Transfer => 0xFF,
+	    _ => self.oam[(address - 0xFE00) as usize],
+	}
+    }
+
+    pub fn write_oam(&mut self, address: u16, value: u8) {
+	match self.mode {
+	    PpuMode::OamScan | PpuMode::Transfer => {}
+	    _ => self.oam[(address - 0xFE00) as usize] = value,
+	}
+    }
+
+    pub fn read_register(&self, address: u16) -> u8 {
+	match address {
+	    0xFF40 => self.lcdc,
+	    0xFF41 => {
+		let coincidence: u8 = if self.ly == self.lyc { 1 } else { 0 };
+		0x80 | (self.stat & 0x78) | coincidence << 2 | u8::from(self.mode)
+	    }
+	    0xFF42 => self.scy,
+	    0xFF43 => self.scx,
+	    0xFF44 => self.ly,
+	    0xFF45 => self.lyc,
+	    0xFF47 => self.bgp,
+	    0xFF48 => self.obp0,
+	    0xFF49 => self.obp1,
+	    0xFF4A => self.wy,
+	    0xFF4B => self.wx,
+	    0xFF4F if self.cgb_mode => 0xFE | self.vram_bank as u8,
+	    0xFF68 if self.cgb_mode => self.bg_palettes.read_spec(),
+	    0xFF69 if self.cgb_mode => self.read_palette_data(&self.bg_palettes),
+	    0xFF6A if self.cgb_mode => self.obj_palettes.read_spec(),
+	    0xFF6B if self.cgb_mode => self.read_palette_data(&self.obj_palettes),
+	    0xFF6C if self.cgb_mode => 0xFE | self.opri,
+	    _ => 0xFF,
+	}
+    }
+
+    pub fn write_register(&mut self, address: u16, value: u8) {
+	let accessible = self.mode != PpuMode::Transfer;
+
+	match address {
+	    0xFF40 => {
+		let was_enabled = self.lcd_enabled();
+		self.lcdc = value;
+
+		if was_enabled && !self.lcd_enabled() {
+		    self.ly = 0;
+		    self.dots = 0;
+		    self.window_line = 0;
+		    self.mode = PpuMode::HBlank;
+		} else if !was_enabled && self.lcd_enabled() {
+		    self.dots = 0;
+		    self.mode = PpuMode::OamScan;
+		}
+	    }
+	    0xFF41 => {
+		// On pre-CGB models the write briefly enables every STAT source
+		if !self.model.is_cgb() && self.lcd_enabled() {
+		    let blanking = matches!(self.mode, PpuMode::HBlank | PpuMode::VBlank);
+		    if blanking || self.ly == self.lyc {
+			self.pending_interrupts |= STAT_INTERRUPT;
+		    }
+		}
+
+		self.stat = value & 0x78;
+	    }
+	    0xFF42 => self.scy = value,
+	    0xFF43 => self.scx = value,
+	    0xFF45 => self.lyc = value,
+	    0xFF47 => self.bgp = value,
+	    0xFF48 => self.obp0 = value,
+	    0xFF49 => self.obp1 = value,
+	    0xFF4A => self.wy = value,
+	    0xFF4B => self.wx = value,
+	    0xFF4F if self.cgb_mode => self.vram_bank = (value & 0b1) as usize,
+	    0xFF68 if self.cgb_mode => self.bg_palettes.write_spec(value),
+	    0xFF69 if self.cgb_mode => self.bg_palettes.write_data(value, accessible),
+	    0xFF6A if self.cgb_mode => self.obj_palettes.write_spec(value),
+	    0xFF6B if self.cgb_mode => self.obj_palettes.write_data(value, accessible),
+	    0xFF6C if self.cgb_mode => self.opri = value & 0b1,
+	    _ => {}
+	}
+    }
+
+    fn read_palette_data(&self, palettes: &PaletteRam) -> u8 {
+	if self.mode == PpuMode::Transfer {
+	    0xFF
+	} else {
+	    palettes.read_data()
+	}
+    }
+
+    // Returns the interrupts requested during the elapsed dots
+    pub fn tick(&mut self, cycles: u32) -> u8 {
+	if !self.lcd_enabled() {
+	    self.dots += cycles;
+	    if self.dots >= DOTS_PER_FRAME {
+		self.dots -= DOTS_PER_FRAME;
+		self.frames += 1;
+	    }
+	    return 0;
+	}
+
+	let mut interrupts = std::mem::take(&mut self.pending_interrupts);
+	self.dots += cycles;
+
+	if self.dots >= DOTS_PER_LINE {
+	    self.dots -= DOTS_PER_LINE;
+	    self.ly = (self.ly + 1) % LINES_PER_FRAME;
+
+	    if self.ly == 0 {
+		self.window_line = 0;
+	    }
+	}
+
+	let mode = if self.ly >= SCREEN_HEIGHT as u8 {
+	    PpuMode::VBlank
+	} else if self.dots < OAM_SCAN_DOTS {
+	    PpuMode::OamScan
+	} else if self.dots < OAM_SCAN_DOTS + TRANSFER_DOTS {
+	    PpuMode::Transfer
+	} else {
+	    PpuMode::HBlank
+	};
+
+	if mode != self.mode {
+	    match mode {
+		PpuMode::HBlank => self.render_scanline(),
+		PpuMode::VBlank => {
+		    self.frames += 1;
+		    interrupts |= VBLANK_INTERRUPT;
+		}
+		_ => {}
+	    }
+
+	    self.mode = mode;
+	}
+
+	if self.update_stat_line() {
+	    interrupts |= STAT_INTERRUPT;
+	}
+
+	interrupts
+    }
+
+    // STAT interrupts fire on the rising edge of the OR of all enabled sources
+    fn update_stat_line(&mut self) -> bool {
+	let line = (self.stat & 0x40 == 0x40 && self.ly == self.lyc)
+	    || (self.stat & 0x08 == 0x08 && self.mode == PpuMode::HBlank)
+	    || (self.stat & 0x10 == 0x10 && self.mode == PpuMode::VBlank)
+	    || (self.stat & 0x20 == 0x20 && self.mode == PpuMode::OamScan);
+
+	let rising = line && !self.stat_line;
+	self.stat_line = line;
+
+	rising
+    }
+
+    fn render_scanline(&mut self) {
+	let mut color_ids = [0u8; SCREEN_WIDTH];
+	let mut bg_priority = [false; SCREEN_WIDTH];
+
+	self.render_background(&mut color_ids, &mut bg_priority);
+
+	if (self.lcdc & 0x2) == 0x2 {
+	    self.render_objects(&color_ids, &bg_priority);
+	}
+    }
+
+    fn render_background(&mut self, color_ids: &mut [u8; SCREEN_WIDTH], bg_priority: &mut [bool; SCREEN_WIDTH]) {
+	let line_start = self.ly as usize * SCREEN_WIDTH;
+
+	// On DMG, LCDC bit 0 blanks the background and window
+	if !self.cgb_mode && (self.lcdc & 0b1) == 0 {
+	    let color = self.shade_color(&self.bg_palettes, 0, 0);
+	    for x in 0..SCREEN_WIDTH {
+		self.framebuffer[line_start + x] = color;
+		self.shades[line_start + x] = 0;
+	    }
+	    return;
+	}
+
+	let window_visible = (self.lcdc & 0x20) == 0x20 && self.wy <= self.ly && self.wx <= 166;
+	let mut window_drawn = false;
+
+	for x in 0..SCREEN_WIDTH {
+	    let in_window = window_visible && x as u16 + 7 >= self.wx as u16;
+
+	    let (map_base, map_x, map_y) = if in_window {
+		window_drawn = true;
+		let map_base = if (self.lcdc & 0x40) == 0x40 { 0x1C00 } else { 0x1800 };
+		(map_base, (x as u16 + 7 - self.wx as u16) as u8, self.window_line)
+	    } else {
+		let map_base = if (self.lcdc & 0x8) == 0x8 { 0x1C00 } else { 0x1800 };
+		(map_base, (x as u8).wrapping_add(self.scx), self.ly.wrapping_add(self.scy))
+	    };
+
+	    let map_address = map_base + (map_y as usize / 8) * 32 + (map_x as usize / 8);
+	    let tile = self.vram[0][map_address];
+	    let attributes = if self.cgb_mode {
+		TileAttributes::from(self.vram[1][map_address])
+	    } else {
+		TileAttributes::from(0)
+	    };
+
+	    let tile_address = if (self.lcdc & 0x10) == 0x10 {
+		tile as usize * 16
+	    } else {
+		(0x1000 + (tile as i8 as i32) * 16) as usize
+	    };
+
+	    let color_id = self.tile_color_id(tile_address, attributes, map_x % 8, map_y % 8, 8);
+	    color_ids[x] = color_id;
+	    bg_priority[x] = attributes.priority;
+
+	    self.framebuffer[line_start + x] = if self.cgb_mode {
+		self.lcd_color(self.bg_palettes.color(attributes.palette, color_id))
+	    } else {
+		let shade = shade(self.bgp, color_id);
+		self.shades[line_start + x] = shade;
+		self.shade_color(&self.bg_palettes, 0, shade)
+	    };
+	}
+
+	if window_drawn {
+	    self.window_line += 1;
+	}
+    }
+
+    fn render_objects(&mut self, bg_color_ids: &[u8; SCREEN_WIDTH], bg_priority: &[bool; SCREEN_WIDTH]) {
+	let line_start = self.ly as usize * SCREEN_WIDTH;
+	let height: i16 = if (self.lcdc & 0x4) == 0x4 { 16 } else { 8 };
+	let ly = self.ly as i16;
+
+	// Only the first 10 objects in OAM order overlapping the line are drawn
+	let mut objects: Vec<Object> = self
+	    .oam
+	    .chunks(4)
+	    .map(|entry| Object {
+		y: entry[0] as i16 - 16,
+		x: entry[1] as i16 - 8,
+		tile: entry[2],
+		flags: entry[3],
+	    })
+	    .filter(|object| ly >= object.y && ly < object.y + height)
+	    .take(10)
+	    .collect();
+
+	// In DMG priority mode the object with the smallest X coordinate wins,
+	// while CGB priority only depends on the position in OAM.
+	if (self.opri & 0b1) == 0b1 {
+	    objects.sort_by_key(|object| object.x);
+	}
+
+	for x in 0..SCREEN_WIDTH as i16 {
+	    let hit = objects.iter().find_map(|object| {
+		if x < object.x || x >= object.x + 8 {
+		    return None;
+		}
+
+		let attributes = TileAttributes::from(object.flags);
+		let tile = if height == 16 { object.tile & 0xFE } else { object.tile };
+		let bank = if self.cgb_mode { attributes.bank } else { 0 };
+		let attributes = TileAttributes { bank, ..attributes };
+
+		let color_id = self.tile_color_id(
+		    tile as usize * 16,
+		    attributes,
+		    (x - object.x) as u8,
+		    (ly - object.y) as u8,
+		    height as u8,
+		);
+
+		if color_id == 0 {
+		    None
+		} else {
+		    Some((object, attributes, color_id))
+		}
+	    });
+
+	    let (object, attributes, color_id) = match hit {
+		Some(hit) => hit,
+		None => continue,
+	    };
+
+	    let x = x as usize;
+	    let bg_wins = if self.cgb_mode {
+		(self.lcdc & 0b1) == 0b1 && bg_color_ids[x] != 0 && (bg_priority[x] || attributes.priority)
+	    } else {
+		attributes.priority && bg_color_ids[x] != 0
+	    };
+
+	    if bg_wins {
+		continue;
+	    }
+
+	    self.framebuffer[line_start + x] = if self.cgb_mode {
+		self.lcd_color(self.obj_palettes.color(attributes.palette, color_id))
+	    } else {
+		let (palette, register) = if (object.flags & 0x10) == 0x10 { (1, self.obp1) } else { (0, self.obp0) };
+		let shade = shade(register, color_id);
+		self.shades[line_start + x] = shade;
+		self.shade_color(&self.obj_palettes, palette, shade)
+	    };
+	}
+    }
+
+    fn shade_color(&self, palettes: &PaletteRam, palette: u8, shade: u8) -> u32 {
+	if self.compatibility {
+	    self.lcd_color(palettes.color(palette, shade))
+	} else {
+	    DMG_SHADES[shade as usize]
+	}
+    }
+
+    fn lcd_color(&self, color: u16) -> u32 {
+	match (self.color_correction, self.model) {
+	    (true, Model::Agb) => agb_color(color),
+	    (true, _) => correct_color(color),
+	    (false, _) => rgb555_to_rgb888(color),
+	}
+    }
+
+    fn tile_color_id(&self, tile_address: usize, attributes: TileAttributes, x: u8, y: u8, height: u8) -> u8 {
+	let row = if attributes.y_flip { height - 1 - y } else { y } as usize;
+	let bit = if attributes.x_flip { x } else { 7 - x };
+
+	let low = self.vram[attributes.bank][tile_address + row * 2];
+	let high = self.vram[attributes.bank][tile_address + row * 2 + 1];
+
+	((high >> bit) & 0b1) << 1 | ((low >> bit) & 0b1)
+    }
+}
+
+// Resolves a DMG color id through BGP/OBP0/OBP1
+fn shade(register: u8, color_id: u8) -> u8 {
+    (register >> (color_id * 2)) & 0b11
+}
+
+// The model is checked by the caller and color correction is a frontend setting
+impl SaveState for Ppu {
+    fn save_state(&self, writer: &mut StateWriter) {
+	writer.bytes(&self.vram[0]);
+	writer.bytes(&self.vram[1]);
+	writer.u8(self.vram_bank as u8);
+	writer.bytes(&self.oam);
+	for register in [self.lcdc, self.stat, self.scy, self.scx, self.ly, self.lyc, self.bgp, self.obp0, self.obp1, self.wy, self.wx] {
+	    writer.u8(register);
+	}
+	writer.bool(self.cgb_mode);
+	writer.bool(self.compatibility);
+	self.bg_palettes.save_state(writer);
+	self.obj_palettes.save_state(writer);
+	writer.u8(self.opri);
+	writer.u8(u8::from(self.mode));
+	writer.u32(self.dots);
+	writer.u8(self.window_line);
+	writer.bool(self.stat_line);
+	writer.u8(self.pending_interrupts);
+	writer.u64(self.frames);
+	// The lines drawn so far this frame
+	for pixel in &self.framebuffer {
+	    writer.u32(*pixel);
+	}
+	writer.bytes(&self.shades);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), &'static str> {
+	reader.bytes(&mut self.vram[0])?;
+	reader.bytes(&mut self.vram[1])?;
+	self.vram_bank = (reader.u8()? & 0x1) as usize;
+	reader.bytes(&mut self.oam)?;
+	for register in [
+	    &mut self.lcdc,
+	    &mut self.stat,
+	    &mut self.scy,
+	    &mut self.scx,
+	    &mut self.ly,
+	    &mut self.lyc,
+	    &mut self.bgp,
+	    &mut self.obp0,
+	    &mut self.obp1,
+	    &mut self.wy,
+	    &mut self.wx,
+	] {
+	    *register = reader.u8()?;
+	}
+	self.cgb_mode = reader.bool()?;
+	self.compatibility = reader.bool()?;
+	self.bg_palettes.load_state(reader)?;
+	self.obj_palettes.load_state(reader)?;
+	self.opri = reader.u8()?;
+	self.mode = PpuMode::from(reader.u8()?);
+	self.dots = reader.u32()?;
+	self.window_line = reader.u8()?;
+	self.stat_line = reader.bool()?;
+	self.pending_interrupts = reader.u8()?;
+	self.frames = reader.u64()?;
+	for pixel in self.framebuffer.iter_mut() {
+	    *pixel = reader.u32()?;
+	}
+	reader.bytes(&mut self.shades)
+    }
+}
@@ -12,6 +12,19 @@ pub struct Registers {
 }
 
 impl Registers {
+    pub fn new() -> Self {
+	Registers {
+	    a: 0,
+	    b: 0,
+	    c: 0,
+	    d: 0,
+	    e: 0,
+	    f: FlagsRegister::from(0),
+	    h: 0,
+	    l: 0,
+	}
+    }
+
     pub fn get_af(&self) -> u16 {
         (self.a as u16) << 8 | u8::from(self.f) as u16
     }
@@ -0,0 +1,174 @@
+use std::collections::VecDeque;
+
+use crate::cpu::gameboy::GameBoy;
+
+// Consecutive states barely differ, so only every KEYFRAME_INTERVAL-th one is
+// stored whole. The others are XORed with it and run-length encoded.
+const KEYFRAME_INTERVAL: usize = 30;
+
+// A keyframe and the states taken after it, dropped together when the history
+// runs out of room since the deltas can't be decoded without the keyframe
+struct Group {
+    keyframe: Vec<u8>,
+    deltas: Vec<Vec<u8>>,
+}
+
+impl Group {
+    fn size(&self) -> usize {
+	self.keyframe.len() + self.deltas.iter().map(Vec::len).sum::<usize>()
+    }
+}
+
+// History of save states to step back through, one pushed per frame.
+// It holds at most `frames` states and `budget` bytes of compressed data.
+pub struct Rewind {
+    groups: VecDeque<Group>,
+    frames: usize,
+    budget: usize,
+    used: usize,
+}
+
+impl Rewind {
+    pub fn new(frames: usize, budget: usize) -> Self {
+	Rewind {
+	    groups: VecDeque::new(),
+	    frames,
+	    budget,
+	    used: 0,
+	}
+    }
+
+    pub fn len(&self) -> usize {
+	self.groups.iter().map(|group| group.deltas.len() + 1).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+	self.groups.is_empty()
+    }
+
+    // Bytes taken by the stored states
+    pub fn memory_used(&self) -> usize {
+	self.used
+    }
+
+    pub fn clear(&mut self) {
+	self.groups.clear();
+	self.used = 0;
+    }
+
+    pub fn push(&mut self, gameboy: &GameBoy) {
+	let state = gameboy.save_state();
+
+	match self.groups.back_mut() {
+	    Some(group) if group.deltas.len() + 1 < KEYFRAME_INTERVAL => {
+		let delta = encode_delta(&group.keyframe, &state);
+		self.used += delta.len();
+		group.deltas.push(delta);
+	    }
+	    _ => {
+		self.used += state.len();
+		self.groups.push_back(Group {
+		    keyframe: state,
+		    deltas: Vec::new(),
+		});
+	    }
+	}
+
+	// The oldest group goes first, even if that leaves less than the limit
+	while self.used > self.budget || self.len() > self.frames {
+	    match self.groups.pop_front() {
+		Some(group) => self.used -= group.size(),
+		None => break,
+	    }
+	}
+    }
+
+    // Loads the most recent state and forgets it, returns false once the history is empty
+    pub fn step_back(&mut self, gameboy: &mut GameBoy) -> Result<bool, &'static str> {
+	let group = match self.groups.back_mut() {
+	    Some(group) => group,
+	    None => return Ok(false),
+	};
+
+	let state = match group.deltas.pop() {
+	    Some(delta) => {
+		self.used -= delta.len();
+		decode_delta(&group.keyframe, &delta)?
+	    }
+	    None => {
+		let group = self.groups.pop_back().unwrap();
+		self.used -= group.keyframe.len();
+		group.keyframe
+	    }
+	};
+
+	gameboy.load_state(&state)?;
+	Ok(true)
+    }
+}
+
+// The length of the state, then pairs of a run of unchanged bytes and the
+// bytes XORed with the keyframe that follow it. Lengths are LEB128.
+fn encode_delta(keyframe: &[u8], state: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    write_length(&mut delta, state.len());
+
+    let xor: Vec<u8> = state
+	.iter()
+	.enumerate()
+	.map(|(index, byte)| byte ^ keyframe.get(index).copied().unwrap_or(0))
+	.collect();
+    let mut index = 0;
+    while index < xor.len() {
+	let unchanged = xor[index..].iter().take_while(|byte| **byte == 0).count();
+	index += unchanged;
+	let changed = xor[index..].iter().take_while(|byte| **byte != 0).count();
+
+	write_length(&mut delta, unchanged);
+	write_length(&mut delta, changed);
+	delta.extend_from_slice(&xor[index..index + changed]);
+	index += changed;
+    }
+    delta
+}
+
+fn decode_delta(keyframe: &[u8], delta: &[u8]) -> Result<Vec<u8>, &'static str> {
+    let mut position = 0;
+    let length = read_length(delta, &mut position)?;
+    let mut state: Vec<u8> = (0..length).map(|index| keyframe.get(index).copied().unwrap_or(0)).collect();
+
+    let mut index = 0;
+    while position < delta.len() {
+	index += read_length(delta, &mut position)?;
+	let changed = read_length(delta, &mut position)?;
+	let bytes = delta.get(position..position + changed).ok_or("Corrupted rewind state")?;
+	let destination = state.get_mut(index..index + changed).ok_or("Corrupted rewind state")?;
+	for (byte, xor) in destination.iter_mut().zip(bytes) {
+	    *byte ^= xor;
+	}
+	position += changed;
+	index += changed;
+    }
+    Ok(state)
+}
+
+fn write_length(data: &mut Vec<u8>, mut length: usize) {
+    while length >= 0x80 {
+	data.push(length as u8 | 0x80);
+	length >>= 7;
+    }
+    data.push(length as u8);
+}
+
+fn read_length(data: &[u8], position: &mut usize) -> Result<usize, &'static str> {
+    let mut length = 0;
+    for shift in (0..usize::BITS).step_by(7) {
+	let byte = *data.get(*position).ok_or("Corrupted rewind state")?;
+	*position += 1;
+	length |= ((byte & 0x7F) as usize) << shift;
+	if byte & 0x80 == 0 {
+	    return Ok(length);
+	}
+    }
+    Err("Corrupted rewind state")
+}
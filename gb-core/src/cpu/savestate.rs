@@ -0,0 +1,145 @@
+// Save state files: a header, then tagged sections so that new components can be
+// added without breaking older files. Numbers are little-endian.
+const MAGIC: &[u8; 8] = b"LBEMUSAV";
+pub const VERSION: u32 = 2;
+
+// Implemented by each component, fields are written and read back in the same order
+pub trait SaveState {
+    fn save_state(&self, writer: &mut StateWriter);
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), &'static str>;
+}
+
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+	let mut writer = StateWriter { data: Vec::new() };
+	writer.bytes(MAGIC);
+	writer.u32(VERSION);
+	writer
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+	self.data
+    }
+
+    // A four letter tag and the length of what `save` wrote
+    pub fn section<F: FnOnce(&mut StateWriter)>(&mut self, tag: &[u8; 4], save: F) {
+	self.bytes(tag);
+	let length_position = self.data.len();
+	self.u32(0);
+	save(self);
+
+	let length = (self.data.len() - length_position - 4) as u32;
+	self.data[length_position..length_position + 4].copy_from_slice(&length.to_le_bytes());
+    }
+
+    pub fn u8(&mut self, value: u8) {
+	self.data.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+	self.data.push(value as u8);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+	self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+	self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+	self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn bytes(&mut self, value: &[u8]) {
+	self.data.extend_from_slice(value);
+    }
+
+    // Variable sized data is preceded by its length
+    pub fn vec(&mut self, value: &[u8]) {
+	self.u32(value.len() as u32);
+	self.bytes(value);
+    }
+}
+
+#[derive(Clone)]
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+	StateReader { data, position: 0 }
+    }
+
+    // Checks the header and returns the sections by tag
+    pub fn sections(data: &'a [u8]) -> Result<Vec<([u8; 4], StateReader<'a>)>, &'static str> {
+	let mut reader = StateReader::new(data);
+	if reader.take(MAGIC.len())? != MAGIC {
+	    return Err("Not a save state file");
+	}
+	if reader.u32()? != VERSION {
+	    return Err("Save state from an incompatible version");
+	}
+
+	let mut sections = Vec::new();
+	while reader.position < data.len() {
+	    let mut tag = [0; 4];
+	    tag.copy_from_slice(reader.take(4)?);
+	    let length = reader.u32()? as usize;
+	    sections.push((tag, StateReader::new(reader.take(length)?)));
+	}
+	Ok(sections)
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], &'static str> {
+	if self.data.len() - self.position < length {
+	    return Err("Truncated save state");
+	}
+	let bytes = &self.data[self.position..self.position + length];
+	self.position += length;
+	Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, &'static str> {
+	Ok(self.take(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, &'static str> {
+	Ok(self.u8()? != 0)
+    }
+
+    pub fn u16(&mut self) -> Result<u16, &'static str> {
+	Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, &'static str> {
+	Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, &'static str> {
+	Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn bytes(&mut self, destination: &mut [u8]) -> Result<(), &'static str> {
+	destination.copy_from_slice(self.take(destination.len())?);
+	Ok(())
+    }
+
+    pub fn vec(&mut self) -> Result<Vec<u8>, &'static str> {
+	let length = self.u32()? as usize;
+	Ok(self.take(length)?.to_vec())
+    }
+}
+
+// For the memory of BESS blocks, which may be shorter or longer than ours
+pub fn copy_prefix(destination: &mut [u8], source: &[u8]) {
+    let length = destination.len().min(source.len());
+    destination[..length].copy_from_slice(&source[..length]);
+}
@@ -0,0 +1,23 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+// Pixels are 0x00RRGGBB, as stored in the framebuffer
+pub fn write_png(path: &Path, width: usize, height: usize, pixels: &[u32]) -> Result<(), &'static str> {
+    let file = match File::create(path) {
+	Ok(file) => file,
+	Err(_) => return Err("Couldn't create the PNG file"),
+    };
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let data: Vec<u8> = pixels
+	.iter()
+	.flat_map(|pixel| [(pixel >> 16) as u8, (pixel >> 8) as u8, *pixel as u8])
+	.collect();
+
+    match encoder.write_header().and_then(|mut writer| writer.write_image_data(&data)) {
+	Ok(()) => Ok(()),
+	Err(_) => Err("Couldn't write the PNG file"),
+    }
+}
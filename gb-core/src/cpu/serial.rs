@@ -0,0 +1,89 @@
+use crate::cpu::savestate::{SaveState, StateReader, StateWriter};
+
+pub const SERIAL_INTERRUPT: u8 = 0b1000;
+
+// With the internal clock a byte is shifted out at 8192Hz, 512 dots per bit
+const TRANSFER_DOTS: u32 = 8 * 512;
+
+// Serial port (SB 0xFF01, SC 0xFF02). Nothing is ever connected to the other
+// end, so every transfer shifts in 0xFF while the sent bytes are kept around.
+pub struct Serial {
+    data: u8,
+    control: u8,
+    dots_left: u32,
+    output: Vec<u8>,
+}
+
+impl Serial {
+    pub fn new() -> Self {
+	Serial {
+	    data: 0,
+	    control: 0,
+	    dots_left: 0,
+	    output: Vec::new(),
+	}
+    }
+
+    // Every byte sent so far, test ROMs use it to print their results
+    pub fn output(&self) -> &[u8] {
+	&self.output
+    }
+
+    pub fn read_register(&self, address: u16) -> u8 {
+	match address {
+	    0xFF01 => self.data,
+	    0xFF02 => 0x7E | self.control,
+	    _ => 0xFF,
+	}
+    }
+
+    pub fn write_register(&mut self, address: u16, value: u8) {
+	match address {
+	    0xFF01 => self.data = value,
+	    0xFF02 => {
+		self.control = value & 0x81;
+
+		// Transfers waiting for an external clock never complete
+		if (value & 0x81) == 0x81 {
+		    self.dots_left = TRANSFER_DOTS;
+		}
+	    }
+	    _ => {}
+	}
+    }
+
+    // Returns the serial interrupt once a transfer completes
+    pub fn tick(&mut self, cycles: u32) -> u8 {
+	if self.dots_left == 0 {
+	    return 0;
+	}
+
+	self.dots_left = self.dots_left.saturating_sub(cycles);
+	if self.dots_left > 0 {
+	    return 0;
+	}
+
+	self.output.push(self.data);
+	self.data = 0xFF;
+	self.control &= 0x01;
+
+	SERIAL_INTERRUPT
+    }
+}
+
+impl SaveState for Serial {
+    fn save_state(&self, writer: &mut StateWriter) {
+	writer.u8(self.data);
+	writer.u8(self.control);
+	writer.u32(self.dots_left);
+	writer.vec(&self.output);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), &'static str> {
+	self.data = reader.u8()?;
+	self.control = reader.u8()?;
+	self.dots_left = reader.u32()?;
+	self.output = reader.vec()?;
+	Ok(())
+    }
+}
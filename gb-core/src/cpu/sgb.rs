@@ -0,0 +1,609 @@
+use crate::cpu::{
+    bess::{self, BessWriter},
+    joypad::MAX_PLAYERS,
+    palette::rgb555_to_rgb888,
+    ppu::{Ppu, SCREEN_HEIGHT, SCREEN_WIDTH},
+    savestate::{SaveState, StateReader, StateWriter},
+};
+
+pub const SGB_SCREEN_WIDTH: usize = 256;
+pub const SGB_SCREEN_HEIGHT: usize = 224;
+
+// Position of the Game Boy screen inside the border
+const SCREEN_X: usize = 48;
+const SCREEN_Y: usize = 40;
+
+const PACKET_LENGTH: usize = 16;
+const ATTRIBUTE_COLUMNS: usize = SCREEN_WIDTH / 8;
+const ATTRIBUTE_ROWS: usize = SCREEN_HEIGHT / 8;
+const ATTRIBUTE_FILE_LENGTH: usize = ATTRIBUTE_COLUMNS * ATTRIBUTE_ROWS / 4;
+const ATTRIBUTE_FILES: usize = 45;
+const SYSTEM_PALETTES: usize = 512;
+const BORDER_COLUMNS: usize = SGB_SCREEN_WIDTH / 8;
+const BORDER_ROWS: usize = SGB_SCREEN_HEIGHT / 8;
+
+// What the SGB BIOS shows for games that don't send their own palettes
+const DEFAULT_PALETTE: [u16; 4] = [0x67BF, 0x265B, 0x10B5, 0x2866];
+
+#[derive(Copy, Clone, PartialEq)]
+enum Mask {
+    None,
+    Freeze,
+    Black,
+    Color0,
+}
+
+// Commands that copy 4KB from the Game Boy screen on the next frame
+#[derive(Copy, Clone)]
+enum Transfer {
+    Characters(usize),
+    Border,
+    Palettes,
+    Attributes,
+}
+
+// Super Game Boy: receives command packets sent bit by bit through P14/P15
+// and renders the colorized screen inside a 256x224 border.
+pub struct Sgb {
+    packet: [u8; PACKET_LENGTH],
+    packet_bits: usize,
+    receiving: bool,
+    waiting_release: bool,
+    command: Vec<u8>,
+
+    players: usize,
+    player: usize,
+
+    palettes: [[u16; 4]; 4],
+    system_palettes: Vec<[u16; 4]>,
+    attributes: [u8; ATTRIBUTE_COLUMNS * ATTRIBUTE_ROWS],
+    attribute_files: Vec<[u8; ATTRIBUTE_FILE_LENGTH]>,
+    mask: Mask,
+    transfer: Option<Transfer>,
+
+    border_tiles: Vec<u8>,
+    border_map: Vec<u16>,
+    border_palettes: [[u16; 16]; 4],
+
+    shades: Vec<u8>,
+    frame: Vec<u32>,
+}
+
+impl Sgb {
+    pub fn new() -> Self {
+	Sgb {
+	    packet: [0; PACKET_LENGTH],
+	    packet_bits: 0,
+	    receiving: false,
+	    waiting_release: false,
+	    command: Vec::new(),
+
+	    players: 1,
+	    player: 0,
+
+	    palettes: [DEFAULT_PALETTE; 4],
+	    system_palettes: vec![[0; 4]; SYSTEM_PALETTES],
+	    attributes: [0; ATTRIBUTE_COLUMNS * ATTRIBUTE_ROWS],
+	    attribute_files: vec![[0; ATTRIBUTE_FILE_LENGTH]; ATTRIBUTE_FILES],
+	    mask: Mask::None,
+	    transfer: None,
+
+	    border_tiles: vec![0; 256 * 32],
+	    border_map: vec![0; BORDER_COLUMNS * 32],
+	    border_palettes: [[0; 16]; 4],
+
+	    shades: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+	    frame: vec![0; SGB_SCREEN_WIDTH * SGB_SCREEN_HEIGHT],
+	}
+    }
+
+    // Controller currently read through P1, changed by MLT_REQ
+    pub fn player(&self) -> usize {
+	self.player
+    }
+
+    pub fn frame(&self) -> &[u32] {
+	&self.frame
+    }
+
+    // Called with the new P14/P15 state on every write to P1
+    pub fn write_joypad(&mut self, previous: u8, value: u8) {
+	match value & 0x30 {
+	    // Both lines low: reset pulse starting a new packet
+	    0x00 => {
+		self.receiving = true;
+		self.waiting_release = true;
+		self.packet = [0; PACKET_LENGTH];
+		self.packet_bits = 0;
+	    }
+	    0x30 => {
+		// Releasing P15 outside of a transfer moves on to the next controller
+		if !self.receiving && (previous & 0x30) == 0x10 && self.players > 1 {
+		    self.player = (self.player + 1) % self.players;
+		}
+		self.waiting_release = false;
+	    }
+	    // P14 low sends a 0, P15 low sends a 1
+	    bit_line => {
+		if self.receiving && !self.waiting_release {
+		    self.receive_bit(bit_line == 0x10);
+		    self.waiting_release = true;
+		}
+	    }
+	}
+    }
+
+    fn receive_bit(&mut self, bit: bool) {
+	if bit {
+	    self.packet[self.packet_bits / 8] |= 1 << (self.packet_bits % 8);
+	}
+	self.packet_bits += 1;
+
+	// The stop bit that follows the 128 data bits is ignored
+	if self.packet_bits == PACKET_LENGTH * 8 {
+	    self.receiving = false;
+	    self.receive_packet();
+	}
+    }
+
+    // The first packet holds the command and how many packets it spans
+    fn receive_packet(&mut self) {
+	self.command.extend_from_slice(&self.packet);
+
+	let length = ((self.command[0] & 0x7) as usize).max(1);
+	if self.command.len() >= length * PACKET_LENGTH {
+	    let command = std::mem::take(&mut self.command);
+	    self.execute(&command);
+	}
+    }
+
+    fn execute(&mut self, command: &[u8]) {
+	match command[0] >> 3 {
+	    0x00 => self.set_palettes(0, 1, &command[1..]),
+	    0x01 => self.set_palettes(2, 3, &command[1..]),
+	    0x02 => self.set_palettes(0, 3, &command[1..]),
+	    0x03 => self.set_palettes(1, 2, &command[1..]),
+	    0x04 => self.attribute_blocks(command),
+	    0x05 => self.attribute_lines(command),
+	    0x06 => self.attribute_divide(command),
+	    0x07 => self.attribute_characters(command),
+	    0x0A => self.set_system_palettes(command),
+	    0x0B => self.transfer = Some(Transfer::Palettes),
+	    0x11 => {
+		self.players = match command[1] & 0b11 {
+		    1 => 2,
+		    3 => MAX_PLAYERS,
+		    _ => 1,
+		};
+		self.player = 0;
+	    }
+	    0x13 => self.transfer = Some(Transfer::Characters((command[1] & 0b1) as usize)),
+	    0x14 => self.transfer = Some(Transfer::Border),
+	    0x15 => self.transfer = Some(Transfer::Attributes),
+	    0x16 => {
+		self.apply_attribute_file(command[1] & 0x3F);
+		if (command[1] & 0x40) == 0x40 {
+		    self.mask = Mask::None;
+		}
+	    }
+	    0x17 => {
+		self.mask = match command[1] & 0b11 {
+		    1 => Mask::Freeze,
+		    2 => Mask::Black,
+		    3 => Mask::Color0,
+		    _ => Mask::None,
+		};
+	    }
+	    _ => {}
+	}
+    }
+
+    // Color 0 is shared by the 4 palettes, the last written one wins
+    fn set_palettes(&mut self, first: usize, second: usize, data: &[u8]) {
+	let color = |index: usize| (data[index * 2 + 1] as u16) << 8 | data[index * 2] as u16;
+
+	for palette in self.palettes.iter_mut() {
+	    palette[0] = color(0);
+	}
+	for color_id in 1..4 {
+	    self.palettes[first][color_id] = color(color_id);
+	    self.palettes[second][color_id] = color(color_id + 3);
+	}
+    }
+
+    fn set_system_palettes(&mut self, command: &[u8]) {
+	for palette in 0..4 {
+	    let id = ((command[palette * 2 + 2] as usize) << 8 | command[palette * 2 + 1] as usize) % SYSTEM_PALETTES;
+	    self.palettes[palette] = self.system_palettes[id];
+	}
+
+	let color0 = self.palettes[0][0];
+	for palette in self.palettes.iter_mut() {
+	    palette[0] = color0;
+	}
+
+	if (command[9] & 0x80) == 0x80 {
+	    self.apply_attribute_file(command[9] & 0x3F);
+	}
+	if (command[9] & 0x40) == 0x40 {
+	    self.mask = Mask::None;
+	}
+    }
+
+    fn attribute_blocks(&mut self, command: &[u8]) {
+	let count = (command[1] & 0x1F) as usize;
+
+	for block in command[2..].chunks(6).take(count) {
+	    if block.len() < 6 {
+		break;
+	    }
+
+	    let (control, palettes) = (block[0], block[1]);
+	    let (left, top, right, bottom) = (block[2] as usize, block[3] as usize, block[4] as usize, block[5] as usize);
+
+	    let inside = (control & 0b001 == 0b001).then_some(palettes & 0b11);
+	    let outside = (control & 0b100 == 0b100).then_some((palettes >> 4) & 0b11);
+	    // Setting only the inside or only the outside also colors the border
+	    let border = match (control & 0b111, inside, outside) {
+		(0b001, inside, _) => inside,
+		(0b100, _, outside) => outside,
+		(control, _, _) if control & 0b010 == 0b010 => Some((palettes >> 2) & 0b11),
+		_ => None,
+	    };
+
+	    for y in 0..ATTRIBUTE_ROWS {
+		for x in 0..ATTRIBUTE_COLUMNS {
+		    let palette = if x > left && x < right && y > top && y < bottom {
+			inside
+		    } else if x >= left && x <= right && y >= top && y <= bottom {
+			border
+		    } else {
+			outside
+		    };
+
+		    if let Some(palette) = palette {
+			self.attributes[y * ATTRIBUTE_COLUMNS + x] = palette;
+		    }
+		}
+	    }
+	}
+    }
+
+    fn attribute_lines(&mut self, command: &[u8]) {
+	let count = command[1] as usize;
+
+	for &line in command[2..].iter().take(count) {
+	    let index = (line & 0x1F) as usize;
+	    let palette = (line >> 5) & 0b11;
+
+	    if (line & 0x80) == 0x80 {
+		if index < ATTRIBUTE_ROWS {
+		    self.attributes[index * ATTRIBUTE_COLUMNS..(index + 1) * ATTRIBUTE_COLUMNS].fill(palette);
+		}
+	    } else if index < ATTRIBUTE_COLUMNS {
+		for y in 0..ATTRIBUTE_ROWS {
+		    self.attributes[y * ATTRIBUTE_COLUMNS + index] = palette;
+		}
+	    }
+	}
+    }
+
+    fn attribute_divide(&mut self, command: &[u8]) {
+	let after = command[1] & 0b11;
+	let before = (command[1] >> 2) & 0b11;
+	let on_line = (command[1] >> 4) & 0b11;
+	let horizontal = (command[1] & 0x40) == 0x40;
+	let line = command[2] as usize;
+
+	for y in 0..ATTRIBUTE_ROWS {
+	    for x in 0..ATTRIBUTE_COLUMNS {
+		let position = if horizontal { y } else { x };
+		self.attributes[y * ATTRIBUTE_COLUMNS + x] = match position.cmp(&line) {
+		    std::cmp::Ordering::Less => before,
+		    std::cmp::Ordering::Equal => on_line,
+		    std::cmp::Ordering::Greater => after,
+		};
+	    }
+	}
+    }
+
+    fn attribute_characters(&mut self, command: &[u8]) {
+	let mut x = command[1] as usize % ATTRIBUTE_COLUMNS;
+	let mut y = command[2] as usize % ATTRIBUTE_ROWS;
+	let count = ((command[4] as usize) << 8 | command[3] as usize).min(self.attributes.len());
+	let vertical = command[5] == 1;
+
+	for index in 0..count {
+	    let byte = match command.get(6 + index / 4) {
+		Some(byte) => *byte,
+		None => break,
+	    };
+	    self.attributes[y * ATTRIBUTE_COLUMNS + x] = (byte >> (6 - (index % 4) * 2)) & 0b11;
+
+	    if vertical {
+		y += 1;
+		if y == ATTRIBUTE_ROWS {
+		    y = 0;
+		    x = (x + 1) % ATTRIBUTE_COLUMNS;
+		}
+	    } else {
+		x += 1;
+		if x == ATTRIBUTE_COLUMNS {
+		    x = 0;
+		    y = (y + 1) % ATTRIBUTE_ROWS;
+		}
+	    }
+	}
+    }
+
+    // Attribute files pack 4 cells per byte, most significant bits first
+    fn apply_attribute_file(&mut self, file: u8) {
+	let file = match self.attribute_files.get(file as usize) {
+	    Some(file) => *file,
+	    None => return,
+	};
+
+	for (index, attribute) in self.attributes.iter_mut().enumerate() {
+	    *attribute = (file[index / 4] >> (6 - (index % 4) * 2)) & 0b11;
+	}
+    }
+
+    // Runs once per frame when the Game Boy enters VBlank
+    pub fn vblank(&mut self, ppu: &Ppu) {
+	if let Some(transfer) = self.transfer.take() {
+	    self.receive_transfer(transfer, &ppu.screen_tile_data());
+	}
+
+	if self.mask != Mask::Freeze {
+	    self.shades.copy_from_slice(ppu.shades());
+	}
+
+	self.render_frame();
+    }
+
+    fn receive_transfer(&mut self, transfer: Transfer, data: &[u8]) {
+	let word = |index: usize| (data[index * 2 + 1] as u16) << 8 | data[index * 2] as u16;
+
+	match transfer {
+	    Transfer::Characters(half) => {
+		self.border_tiles[half * 0x1000..(half + 1) * 0x1000].copy_from_slice(&data[..0x1000]);
+	    }
+	    Transfer::Border => {
+		for (index, entry) in self.border_map.iter_mut().enumerate() {
+		    *entry = word(index);
+		}
+		for (palette, colors) in self.border_palettes.iter_mut().enumerate() {
+		    for (color_id, color) in colors.iter_mut().enumerate() {
+			*color = word(0x400 + palette * 16 + color_id);
+		    }
+		}
+	    }
+	    Transfer::Palettes => {
+		for (id, palette) in self.system_palettes.iter_mut().enumerate() {
+		    for (color_id, color) in palette.iter_mut().enumerate() {
+			*color = word(id * 4 + color_id);
+		    }
+		}
+	    }
+	    Transfer::Attributes => {
+		for (id, file) in self.attribute_files.iter_mut().enumerate() {
+		    let start = id * ATTRIBUTE_FILE_LENGTH;
+		    file.copy_from_slice(&data[start..start + ATTRIBUTE_FILE_LENGTH]);
+		}
+	    }
+	}
+    }
+
+    fn render_frame(&mut self) {
+	let backdrop = rgb555_to_rgb888(self.palettes[0][0]);
+	self.frame.fill(backdrop);
+
+	for y in 0..SCREEN_HEIGHT {
+	    for x in 0..SCREEN_WIDTH {
+		let palette = self.attributes[(y / 8) * ATTRIBUTE_COLUMNS + x / 8] as usize;
+		let color = match self.mask {
+		    Mask::Black => 0,
+		    Mask::Color0 => backdrop,
+		    _ => rgb555_to_rgb888(self.palettes[palette][self.shades[y * SCREEN_WIDTH + x] as usize]),
+		};
+
+		self.frame[(SCREEN_Y + y) * SGB_SCREEN_WIDTH + SCREEN_X + x] = color;
+	    }
+	}
+
+	self.render_border();
+    }
+
+    // The border is a SNES layer in front of the Game Boy screen, color 0 is transparent
+    fn render_border(&mut self) {
+	for row in 0..BORDER_ROWS {
+	    for column in 0..BORDER_COLUMNS {
+		let entry = self.border_map[row * BORDER_COLUMNS + column];
+		let tile = (entry & 0xFF) as usize * 32;
+		let palette = ((entry >> 10) & 0b11) as usize;
+		let x_flip = (entry & 0x4000) == 0x4000;
+		let y_flip = (entry & 0x8000) == 0x8000;
+
+		for y in 0..8 {
+		    let line = if y_flip { 7 - y } else { y };
+		    let planes = [
+			self.border_tiles[tile + line * 2],
+			self.border_tiles[tile + line * 2 + 1],
+			self.border_tiles[tile + 16 + line * 2],
+			self.border_tiles[tile + 16 + line * 2 + 1],
+		    ];
+
+		    for x in 0..8 {
+			let bit = if x_flip { x } else { 7 - x };
+			let color_id = planes
+			    .iter()
+			    .enumerate()
+			    .fold(0, |color_id, (plane, byte)| color_id | ((byte >> bit) & 0b1) << plane);
+
+			if color_id != 0 {
+			    let pixel = (row * 8 + y) * SGB_SCREEN_WIDTH + column * 8 + x;
+			    self.frame[pixel] = rgb555_to_rgb888(self.border_palettes[palette][color_id as usize]);
+			}
+		    }
+		}
+	    }
+	}
+    }
+}
+
+fn save_colors(writer: &mut StateWriter, colors: &[u16]) {
+    for color in colors {
+	writer.u16(*color);
+    }
+}
+
+fn load_colors(reader: &mut StateReader, colors: &mut [u16]) -> Result<(), &'static str> {
+    for color in colors.iter_mut() {
+	*color = reader.u16()?;
+    }
+    Ok(())
+}
+
+fn color_bytes<'a, I: IntoIterator<Item = &'a u16>>(colors: I) -> Vec<u8> {
+    colors.into_iter().flat_map(|color| color.to_le_bytes()).collect()
+}
+
+fn load_color_bytes<'a, I: IntoIterator<Item = &'a mut u16>>(colors: I, bytes: &[u8]) {
+    for (color, bytes) in colors.into_iter().zip(bytes.chunks_exact(2)) {
+	*color = u16::from_le_bytes([bytes[0], bytes[1]]);
+    }
+}
+
+impl Sgb {
+    // The BESS SGB block, its buffers are in the same layout as SameBoy keeps them
+    pub fn save_bess(&self, writer: &mut BessWriter) {
+	let mut block = Vec::new();
+	writer.buffer(&mut block, &self.border_tiles);
+	writer.buffer(&mut block, &color_bytes(&self.border_map));
+	writer.buffer(&mut block, &color_bytes(self.border_palettes.iter().flatten()));
+	writer.buffer(&mut block, &color_bytes(self.palettes.iter().flatten()));
+	writer.buffer(&mut block, &color_bytes(self.system_palettes.iter().flatten()));
+	writer.buffer(&mut block, &self.attributes);
+	writer.buffer(&mut block, &self.attribute_files.concat());
+	// Number of players in the high nibble, the current one in the low nibble
+	block.push((self.players as u8) << 4 | self.player as u8);
+	writer.block(b"SGB ", &block);
+    }
+
+    pub fn load_bess(&mut self, file: &[u8], block: &[u8]) -> Result<(), &'static str> {
+	let buffer = |index: usize| bess::buffer(file, block, index * 8);
+
+	let tiles = buffer(0)?;
+	let length = tiles.len().min(self.border_tiles.len());
+	self.border_tiles[..length].copy_from_slice(&tiles[..length]);
+	load_color_bytes(self.border_map.iter_mut(), buffer(1)?);
+	load_color_bytes(self.border_palettes.iter_mut().flatten(), buffer(2)?);
+	load_color_bytes(self.palettes.iter_mut().flatten(), buffer(3)?);
+	load_color_bytes(self.system_palettes.iter_mut().flatten(), buffer(4)?);
+
+	let attributes = buffer(5)?;
+	let length = attributes.len().min(self.attributes.len());
+	self.attributes[..length].copy_from_slice(&attributes[..length]);
+	for (file, data) in self.attribute_files.iter_mut().zip(buffer(6)?.chunks_exact(ATTRIBUTE_FILE_LENGTH)) {
+	    file.copy_from_slice(data);
+	}
+
+	let multiplayer = *block.get(7 * 8).ok_or("The BESS SGB block is too short")?;
+	self.players = match multiplayer >> 4 {
+	    2 => 2,
+	    4 => 4,
+	    _ => 1,
+	};
+	self.player = (multiplayer & 0xF) as usize % self.players;
+	Ok(())
+    }
+}
+
+impl SaveState for Sgb {
+    fn save_state(&self, writer: &mut StateWriter) {
+	writer.bytes(&self.packet);
+	writer.u32(self.packet_bits as u32);
+	writer.bool(self.receiving);
+	writer.bool(self.waiting_release);
+	writer.vec(&self.command);
+	writer.u8(self.players as u8);
+	writer.u8(self.player as u8);
+
+	for palette in self.palettes.iter().chain(&self.system_palettes) {
+	    save_colors(writer, palette);
+	}
+	writer.bytes(&self.attributes);
+	for file in &self.attribute_files {
+	    writer.bytes(file);
+	}
+	writer.u8(match self.mask {
+	    Mask::None => 0,
+	    Mask::Freeze => 1,
+	    Mask::Black => 2,
+	    Mask::Color0 => 3,
+	});
+	// 0 for none, then the kind of transfer and the character block
+	let (transfer, block) = match self.transfer {
+	    None => (0, 0),
+	    Some(Transfer::Characters(block)) => (1, block),
+	    Some(Transfer::Border) => (2, 0),
+	    Some(Transfer::Palettes) => (3, 0),
+	    Some(Transfer::Attributes) => (4, 0),
+	};
+	writer.u8(transfer);
+	writer.u8(block as u8);
+
+	writer.bytes(&self.border_tiles);
+	save_colors(writer, &self.border_map);
+	for palette in &self.border_palettes {
+	    save_colors(writer, palette);
+	}
+	writer.bytes(&self.shades);
+	for pixel in &self.frame {
+	    writer.u32(*pixel);
+	}
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), &'static str> {
+	reader.bytes(&mut self.packet)?;
+	self.packet_bits = (reader.u32()? as usize).min(PACKET_LENGTH * 8);
+	self.receiving = reader.bool()?;
+	self.waiting_release = reader.bool()?;
+	self.command = reader.vec()?;
+	self.players = (reader.u8()? as usize).clamp(1, MAX_PLAYERS);
+	self.player = reader.u8()? as usize % self.players;
+
+	for palette in self.palettes.iter_mut().chain(self.system_palettes.iter_mut()) {
+	    load_colors(reader, palette)?;
+	}
+	reader.bytes(&mut self.attributes)?;
+	for file in self.attribute_files.iter_mut() {
+	    reader.bytes(file)?;
+	}
+	self.mask = match reader.u8()? {
+	    1 => Mask::Freeze,
+	    2 => Mask::Black,
+	    3 => Mask::Color0,
+	    _ => Mask::None,
+	};
+	let (transfer, block) = (reader.u8()?, reader.u8()? as usize);
+	self.transfer = match transfer {
+	    1 => Some(Transfer::Characters(block & 1)),
+	    2 => Some(Transfer::Border),
+	    3 => Some(Transfer::Palettes),
+	    4 => Some(Transfer::Attributes),
+	    _ => None,
+	};
+
+	reader.bytes(&mut self.border_tiles)?;
+	load_colors(reader, &mut self.border_map)?;
+	for palette in self.border_palettes.iter_mut() {
+	    load_colors(reader, palette)?;
+	}
+	reader.bytes(&mut self.shades)?;
+	for pixel in self.frame.iter_mut() {
+	    *pixel = reader.u32()?;
+	}
+	Ok(())
+    }
+}
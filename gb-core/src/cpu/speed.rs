@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+// 70224 dots at 4.194304MHz
+pub const FRAME_DURATION: Duration = Duration::from_nanos(16_742_706);
+const DEFAULT_FAST_FORWARD: f64 = 4.0;
+
+// What a frontend asks of the emulation speed, usually from key presses
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpeedCommand {
+    // Fast-forward while a key is held
+    HoldFastForward(bool),
+    ToggleFastForward,
+    // Speed of the fast-forward, None runs as fast as the host can
+    SetFastForward(Option<f64>),
+    // Speed outside of fast-forward, below 1 is slow motion
+    SetSpeed(f64),
+    TogglePause,
+    // Runs a single frame and stays paused
+    FrameAdvance,
+}
+
+// Paces a frontend loop that runs one frame per iteration: whether the frame
+// runs, how long it lasts on the host, and the audio played meanwhile.
+pub struct Speed {
+    fast_forward: Option<f64>,
+    speed: f64,
+    held: bool,
+    toggled: bool,
+    paused: bool,
+    advance: bool,
+    // Otherwise the audio is sped up along with the game, raising its pitch
+    pub mute_fast_forward: bool,
+}
+
+impl Speed {
+    pub fn new() -> Self {
+	Speed {
+	    fast_forward: Some(DEFAULT_FAST_FORWARD),
+	    speed: 1.0,
+	    held: false,
+	    toggled: false,
+	    paused: false,
+	    advance: false,
+	    mute_fast_forward: false,
+	}
+    }
+
+    pub fn command(&mut self, command: SpeedCommand) {
+	match command {
+	    SpeedCommand::HoldFastForward(held) => self.held = held,
+	    SpeedCommand::ToggleFastForward => self.toggled = !self.toggled,
+	    SpeedCommand::SetFastForward(multiplier) => self.fast_forward = multiplier.filter(|multiplier| *multiplier > 0.0),
+	    SpeedCommand::SetSpeed(speed) if speed > 0.0 => self.speed = speed,
+	    SpeedCommand::SetSpeed(_) => {}
+	    SpeedCommand::TogglePause => {
+		self.paused = !self.paused;
+		self.advance = false;
+	    }
+	    SpeedCommand::FrameAdvance => {
+		self.paused = true;
+		self.advance = true;
+	    }
+	}
+    }
+
+    pub fn is_paused(&self) -> bool {
+	self.paused
+    }
+
+    pub fn is_fast_forwarding(&self) -> bool {
+	self.held || self.toggled
+    }
+
+    // Relative to the console, None when uncapped
+    pub fn multiplier(&self) -> Option<f64> {
+	if self.is_fast_forwarding() {
+	    self.fast_forward
+	} else {
+	    Some(self.speed)
+	}
+    }
+
+    // Whether the next frame runs, while paused only the one of a frame advance does
+    pub fn next_frame(&mut self) -> bool {
+	!self.paused || std::mem::take(&mut self.advance)
+    }
+
+    // How long the host should take for a frame, zero when uncapped
+    pub fn frame_duration(&self) -> Duration {
+	match self.multiplier() {
+	    _ if self.paused => FRAME_DURATION,
+	    Some(multiplier) => FRAME_DURATION.div_f64(multiplier),
+	    None => Duration::ZERO,
+	}
+    }
+
+    // Resamples the stereo samples of a frame to the time the frame lasts, which
+    // shifts their pitch. Paused, uncapped and muted fast-forward play nothing.
+    pub fn audio(&self, samples: &[f32]) -> Vec<f32> {
+	let silent = self.paused || (self.is_fast_forwarding() && self.mute_fast_forward);
+	let multiplier = match self.multiplier() {
+	    Some(multiplier) if !silent => multiplier,
+	    _ => return Vec::new(),
+	};
+
+	let frames = samples.len() / 2;
+	let length = (frames as f64 / multiplier).round() as usize;
+	(0..length)
+	    .flat_map(|index| {
+		let source = ((index as f64 * multiplier) as usize).min(frames - 1) * 2;
+		[samples[source], samples[source + 1]]
+	    })
+	    .collect()
+    }
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+	Speed::new()
+    }
+}
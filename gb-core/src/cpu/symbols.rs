@@ -0,0 +1,171 @@
+use std::{fs, path::Path};
+
+// Labels from the .sym files of RGBDS and wla-dx, or from RGBDS .map files
+pub struct Symbols {
+    // Sorted by bank and address, labels at the same address keep the file order
+    labels: Vec<(u16, u16, String)>,
+}
+
+impl Symbols {
+    // Looks for <rom>.sym, then <rom>.map, none being there isn't an error
+    pub fn for_rom(rom_path: &str) -> Result<Option<Symbols>, &'static str> {
+	let rom_path = Path::new(rom_path);
+
+	for (extension, parse) in [("sym", Symbols::parse_sym as fn(&str) -> _), ("map", Symbols::parse_map)] {
+	    let path = rom_path.with_extension(extension);
+	    if !path.exists() {
+		continue;
+	    }
+
+	    return match fs::read_to_string(&path) {
+		Ok(text) => parse(&text).map(Some),
+		Err(_) => Err("Couldn't read the symbol file"),
+	    };
+	}
+
+	Ok(None)
+    }
+
+    // Lines are `BB:AAAA Label`, wla-dx also writes other sections than [labels]
+    pub fn parse_sym(text: &str) -> Result<Symbols, &'static str> {
+	let mut labels = Vec::new();
+	let mut in_labels = true;
+
+	for line in text.lines() {
+	    let line = line.split(';').next().unwrap_or("").trim();
+	    if line.is_empty() {
+		continue;
+	    }
+	    if line.starts_with('[') {
+		in_labels = line == "[labels]";
+		continue;
+	    }
+	    if !in_labels {
+		continue;
+	    }
+
+	    let mut fields = line.split_whitespace();
+	    let location = fields.next().and_then(|location| location.split_once(':'));
+	    let (bank, address) = match location {
+		Some((bank, address)) => (u16::from_str_radix(bank, 16), u16::from_str_radix(address, 16)),
+		None => return Err("Invalid symbol file, expected `bank:address label` lines"),
+	    };
+
+	    match (bank, address, fields.next()) {
+		(Ok(bank), Ok(address), Some(name)) => labels.push((bank, address, String::from(name))),
+		_ => return Err("Invalid symbol file, expected `bank:address label` lines"),
+	    }
+	}
+
+	Ok(Symbols::new(labels))
+    }
+
+    // Only the `$AAAA = Label` lines are used, the bank comes from the `... bank #N` headings
+    pub fn parse_map(text: &str) -> Result<Symbols, &'static str> {
+	let mut labels = Vec::new();
+	let mut bank = 0;
+
+	for line in text.lines() {
+	    let line = line.trim();
+
+	    if let Some(index) = line.to_lowercase().find("bank #") {
+		let number: String = line[index + 6..].chars().take_while(char::is_ascii_digit).collect();
+		match number.parse() {
+		    Ok(number) => bank = number,
+		    Err(_) => return Err("Invalid map file, expected a number after `bank #`"),
+		}
+		continue;
+	    }
+
+	    if let Some((address, name)) = line.split_once(" = ") {
+		if let Some(address) = address.strip_prefix('$') {
+		    match u16::from_str_radix(address, 16) {
+			Ok(address) => labels.push((bank, address, String::from(name.trim()))),
+			Err(_) => return Err("Invalid map file, expected `$address = label` lines"),
+		    }
+		}
+	    }
+	}
+
+	Ok(Symbols::new(labels))
+    }
+
+    fn new(mut labels: Vec<(u16, u16, String)>) -> Symbols {
+	labels.sort_by_key(|(bank, address, _)| (*bank, *address));
+	Symbols { labels }
+    }
+
+    pub fn len(&self) -> usize {
+	self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+	self.labels.is_empty()
+    }
+
+    pub fn label(&self, bank: u16, address: u16) -> Option<&str> {
+	let index = self.labels.partition_point(|(b, a, _)| (*b, *a) < (bank, address));
+	match self.labels.get(index) {
+	    Some((b, a, name)) if (*b, *a) == (bank, address) => Some(name),
+	    _ => None,
+	}
+    }
+
+    pub fn address_of(&self, name: &str) -> Option<(u16, u16)> {
+	self.labels.iter().find(|(_, _, label)| label == name).map(|(bank, address, _)| (*bank, *address))
+    }
+
+    // Closest label at or before the address, as `Label+$offset`, for traces and call stacks
+    pub fn describe(&self, bank: u16, address: u16) -> Option<String> {
+	let index = self.labels.partition_point(|(b, a, _)| (*b, *a) <= (bank, address));
+	if index == 0 {
+	    return None;
+	}
+
+	// Labels from another memory area, such as ROM before WRAM, don't describe the address
+	let (b, a, name) = &self.labels[index - 1];
+	if *b != bank || area(*a) != area(address) {
+	    return None;
+	}
+
+	match address - a {
+	    0 => Some(name.clone()),
+	    offset => Some(format!("{}+${:x}", name, offset)),
+	}
+    }
+}
+
+// The one way addresses are typed, on the command line and in the debuggers: a label
+// first, as `Add` or `cafe` could be either, then `bank:address` or `address` in hex
+pub fn parse_location(symbols: Option<&Symbols>, text: &str) -> Result<(Option<u16>, u16), &'static str> {
+    if let Some((bank, address)) = symbols.and_then(|symbols| symbols.address_of(text)) {
+	return Ok((Some(bank), address));
+    }
+
+    let location = match text.split_once(':') {
+	Some((bank, address)) => parse_hex(bank).map(Some).and_then(|bank| Ok((bank, parse_hex(address)?))),
+	None => parse_hex(text).map(|address| (None, address)),
+    };
+    location.map_err(|_| "Not an address nor a known label")
+}
+
+// Addresses and values are $hex, 0xhex or plain hex
+pub fn parse_hex(text: &str) -> Result<u16, &'static str> {
+    let text = text.trim_start_matches("0x").trim_start_matches("0X").trim_start_matches('$');
+    match u16::from_str_radix(text, 16) {
+	Ok(value) => Ok(value),
+	Err(_) => Err("Invalid number, expected hexadecimal"),
+    }
+}
+
+fn area(address: u16) -> u8 {
+    match address {
+	0x0000..=0x3FFF => 0,
+	0x4000..=0x7FFF => 1,
+	0x8000..=0x9FFF => 2,
+	0xA000..=0xBFFF => 3,
+	0xC000..=0xDFFF => 4,
+	0xFF80..=0xFFFE => 6,
+	_ => 5,
+    }
+}
@@ -0,0 +1,112 @@
+use crate::cpu::savestate::{SaveState, StateReader, StateWriter};
+
+pub const TIMER_INTERRUPT: u8 = 0b100;
+
+// Bit of the internal counter whose falling edge increments TIMA, by TAC clock select
+const TIMA_BITS: [u16; 4] = [9, 3, 5, 7];
+
+// Timer registers (DIV 0xFF04, TIMA 0xFF05, TMA 0xFF06, TAC 0xFF07). DIV is the
+// upper byte of a counter incremented every dot, TIMA counts the falling edges
+// of one of its bits and is reloaded from TMA when it overflows.
+pub struct Timer {
+    counter: u16,
+    tima: u8,
+    tma: u8,
+    tac: u8,
+}
+
+impl Timer {
+    pub fn new() -> Self {
+	Timer {
+	    counter: 0,
+	    tima: 0,
+	    tma: 0,
+	    tac: 0,
+	}
+    }
+
+    pub fn read_register(&self, address: u16) -> u8 {
+	match address {
+	    0xFF04 => (self.counter >> 8) as u8,
+	    0xFF05 => self.tima,
+	    0xFF06 => self.tma,
+	    0xFF07 => 0xF8 | self.tac,
+	    _ => 0xFF,
+	}
+    }
+
+    // Resetting DIV or changing TAC can bring the selected bit down, which counts as an edge
+    pub fn write_register(&mut self, address: u16, value: u8) -> u8 {
+	let before = self.input();
+
+	match address {
+	    0xFF04 => self.counter = 0,
+	    0xFF05 => self.tima = value,
+	    0xFF06 => self.tma = value,
+	    0xFF07 => self.tac = value & 0b111,
+	    _ => {}
+	}
+
+	if before && !self.input() {
+	    self.increment()
+	} else {
+	    0
+	}
+    }
+
+    // Returns the interrupt flag to raise
+    pub fn tick(&mut self, cycles: u32) -> u8 {
+	let mut interrupt = 0;
+
+	for _ in 0..cycles {
+	    let before = self.input();
+	    self.counter = self.counter.wrapping_add(1);
+
+	    if before && !self.input() {
+		interrupt |= self.increment();
+	    }
+	}
+
+	interrupt
+    }
+
+    // The selected counter bit, gated by the timer enable bit
+    fn input(&self) -> bool {
+	let bit = TIMA_BITS[(self.tac & 0b11) as usize];
+	(self.tac & 0b100) != 0 && (self.counter >> bit) & 0b1 == 0b1
+    }
+
+    fn increment(&mut self) -> u8 {
+	let (tima, overflow) = self.tima.overflowing_add(1);
+
+	if overflow {
+	    self.tima = self.tma;
+	    TIMER_INTERRUPT
+	} else {
+	    self.tima = tima;
+	    0
+	}
+    }
+
+    // Save states from other emulators only have DIV, the low bits of the counter are lost
+    pub fn restore_div(&mut self, div: u8) {
+	self.counter = (div as u16) << 8;
+    }
+}
+
+impl SaveState for Timer {
+    fn save_state(&self, writer: &mut StateWriter) {
+	writer.u16(self.counter);
+	writer.u8(self.tima);
+	writer.u8(self.tma);
+	writer.u8(self.tac);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), &'static str> {
+	self.counter = reader.u16()?;
+	self.tima = reader.u8()?;
+	self.tma = reader.u8()?;
+	self.tac = reader.u8()? & 0x7;
+	Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+use std::cell::Cell;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct WatchHit {
+    pub address: u16,
+    pub access: Access,
+    pub value: u8,
+}
+
+// Addresses the debugger stops on when the CPU reads or writes them
+pub struct Watchpoints {
+    watched: Vec<(u16, Access)>,
+    // Reads go through &self, so the last hit is kept in a Cell
+    hit: Cell<Option<WatchHit>>,
+}
+
+impl Watchpoints {
+    pub fn new() -> Self {
+	Watchpoints {
+	    watched: Vec::new(),
+	    hit: Cell::new(None),
+	}
+    }
+
+    pub fn add(&mut self, address: u16, access: Access) {
+	if !self.watched.contains(&(address, access)) {
+	    self.watched.push((address, access));
+	}
+    }
+
+    // Removes every watchpoint on the address, returns whether there was any
+    pub fn remove(&mut self, address: u16) -> bool {
+	let count = self.watched.len();
+	self.watched.retain(|(watched, _)| *watched != address);
+	self.watched.len() != count
+    }
+
+    pub fn check(&self, address: u16, access: Access, value: u8) {
+	if self.watched.contains(&(address, access)) {
+	    self.hit.set(Some(WatchHit { address, access, value }));
+	}
+    }
+
+    pub fn take_hit(&self) -> Option<WatchHit> {
+	self.hit.take()
+    }
+}
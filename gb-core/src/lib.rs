@@ -0,0 +1,24 @@
+// Game Boy emulation core, frontends and tools drive it through `GameBoy`
+mod cpu;
+
+pub use cpu::{
+    apu::DEFAULT_SAMPLE_RATE,
+    compatibility::ManualPalette,
+    disassembler::{disassemble, hardware_register_name, Disassembly, Operand},
+    gameboy::{Config, GameBoy, LoadReport, RegisterState},
+    joypad::{Button, MAX_PLAYERS},
+    model::Model,
+    movie::{Movie, MOVIE_BUTTONS},
+    movie_import::import_movie,
+    ppu::{SCREEN_HEIGHT, SCREEN_WIDTH},
+    rewind::Rewind,
+    screenshot::write_png,
+    sgb::{SGB_SCREEN_HEIGHT, SGB_SCREEN_WIDTH},
+    speed::{Speed, SpeedCommand, FRAME_DURATION},
+    symbols::{parse_hex, parse_location, Symbols},
+    watchpoints::{Access, WatchHit},
+};
+
+// Only there for the single-step and movie import tests, not part of the API
+#[doc(hidden)]
+pub use cpu::{flat::FlatCpu, movie_import::sha1};
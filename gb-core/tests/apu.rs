@@ -0,0 +1,142 @@
+// APU registers and the length and envelope units, driven through the bus
+// while the CPU spins
+mod common;
+
+use gb_core::{Config, GameBoy, Model};
+
+// What NR10-NR51 read back after writing 0, unused bits read as 1
+const READ_BACK: [u8; 0x16] = [
+    0x80, 0x3F, 0x00, 0xFF, 0xBF,
+    0xFF, 0x3F, 0x00, 0xFF, 0xBF,
+    0x7F, 0xFF, 0x9F, 0xFF, 0xBF,
+    0xFF, 0xFF, 0x00, 0x00, 0xBF,
+    0x00, 0x00,
+];
+// 128 dots per sample
+const SAMPLE_RATE: u32 = 32768;
+const LENGTH_CLOCK_DOTS: u64 = 16384;
+
+fn gameboy() -> GameBoy {
+    GameBoy::new(common::rom(&[]), &Config::new(Model::Dmg)).unwrap()
+}
+
+fn write(gameboy: &mut GameBoy, registers: &[(u16, u8)]) {
+    for (address, value) in registers {
+	gameboy.write_memory(*address, *value);
+    }
+}
+
+fn run(gameboy: &mut GameBoy, dots: u64) {
+    let end = gameboy.cycles() + dots;
+    while gameboy.cycles() < end {
+	gameboy.step();
+    }
+}
+
+fn channel_enabled(gameboy: &GameBoy, channel: u8) -> bool {
+    gameboy.read_memory(0xFF26) & (1 << channel) != 0
+}
+
+#[test]
+fn registers_read_back_with_unused_bits_set() {
+    let mut gameboy = gameboy();
+
+    for address in 0xFF10..=0xFF25 {
+	gameboy.write_memory(address, 0x00);
+    }
+    let read: Vec<u8> = (0xFF10..=0xFF25).map(|address| gameboy.read_memory(address)).collect();
+    assert_eq!(read, READ_BACK);
+
+    for address in 0xFF10..=0xFF25 {
+	gameboy.write_memory(address, 0xFF);
+    }
+    for address in (0xFF10..=0xFF25).chain(0xFF27..=0xFF2F) {
+	assert_eq!(gameboy.read_memory(address), 0xFF, "{:04X}", address);
+    }
+    assert_eq!(gameboy.read_memory(0xFF26) & 0xF0, 0xF0);
+}
+
+#[test]
+fn powered_off_apu_clears_and_ignores_registers() {
+    let mut gameboy = gameboy();
+    write(&mut gameboy, &[(0xFF24, 0x77), (0xFF30, 0x12)]);
+
+    gameboy.write_memory(0xFF26, 0x00);
+    assert_eq!(gameboy.read_memory(0xFF26), 0x70);
+    assert_eq!(gameboy.read_memory(0xFF24), 0x00);
+
+    write(&mut gameboy, &[(0xFF24, 0x77), (0xFF12, 0xF0), (0xFF31, 0x34)]);
+    assert_eq!(gameboy.read_memory(0xFF24), 0x00);
+    assert_eq!(gameboy.read_memory(0xFF12), 0x00);
+    // Wave RAM keeps its contents and stays writable
+    assert_eq!(gameboy.read_memory(0xFF30), 0x12);
+    assert_eq!(gameboy.read_memory(0xFF31), 0x34);
+
+    gameboy.write_memory(0xFF26, 0x80);
+    gameboy.write_memory(0xFF24, 0x77);
+    assert_eq!(gameboy.read_memory(0xFF24), 0x77);
+}
+
+#[test]
+fn length_counter_disables_the_channel() {
+    let mut gameboy = gameboy();
+    // Channel 2 with the full 64 steps, clocked at 256Hz
+    write(&mut gameboy, &[(0xFF17, 0xF0), (0xFF16, 0x00), (0xFF19, 0xC0)]);
+    assert!(channel_enabled(&gameboy, 1));
+
+    run(&mut gameboy, 62 * LENGTH_CLOCK_DOTS);
+    assert!(channel_enabled(&gameboy, 1));
+    run(&mut gameboy, 4 * LENGTH_CLOCK_DOTS);
+    assert!(!channel_enabled(&gameboy, 1));
+
+    // Without the length enabled it plays on, the noise channel counts the same way
+    write(&mut gameboy, &[(0xFF19, 0x80), (0xFF21, 0xF0), (0xFF20, 0x3E), (0xFF23, 0xC0)]);
+    run(&mut gameboy, 4 * LENGTH_CLOCK_DOTS);
+    assert!(channel_enabled(&gameboy, 1));
+    assert!(!channel_enabled(&gameboy, 3));
+}
+
+#[test]
+fn turning_the_dac_off_disables_the_channel() {
+    let mut gameboy = gameboy();
+    write(&mut gameboy, &[(0xFF12, 0xF0), (0xFF14, 0x80)]);
+    assert!(channel_enabled(&gameboy, 0));
+
+    gameboy.write_memory(0xFF12, 0x00);
+    assert!(!channel_enabled(&gameboy, 0));
+}
+
+// Volumes channel 2 plays at, one per 1/128s with the louder of each window
+fn envelope_volumes(nr22: u8) -> Vec<u8> {
+    let mut gameboy = gameboy();
+    gameboy.set_sample_rate(SAMPLE_RATE);
+    // Both sides at full volume, duty 50%, about 4kHz
+    write(&mut gameboy, &[(0xFF24, 0x77), (0xFF25, 0x22), (0xFF16, 0x80), (0xFF17, nr22), (0xFF18, 0x00), (0xFF19, 0x87)]);
+    gameboy.take_audio_samples();
+
+    let mut samples = Vec::new();
+    for _ in 0..20 {
+	gameboy.step_frame();
+	samples.extend(gameboy.take_audio_samples().into_iter().step_by(2));
+    }
+
+    // The wave swings between 1.0 and 1 - volume / 7.5, scaled by 8/32 for NR50
+    let mut volumes: Vec<u8> = samples
+	.chunks(256)
+	.map(|window| {
+	    let (low, high) = window.iter().fold((f32::MAX, f32::MIN), |(low, high), sample| (low.min(*sample), high.max(*sample)));
+	    ((high - low) * 30.0).round() as u8
+	})
+	.collect();
+    volumes.dedup();
+    volumes
+}
+
+#[test]
+fn envelope_steps_the_volume() {
+    // Down from 15 and up from 0, one step every 1/64s
+    assert_eq!(envelope_volumes(0xF1), (0..=15).rev().collect::<Vec<u8>>());
+    assert_eq!(envelope_volumes(0x09), (0..=15).collect::<Vec<u8>>());
+    // A period of 0 keeps the initial volume
+    assert_eq!(envelope_volumes(0xA0), vec![10]);
+}
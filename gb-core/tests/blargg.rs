@@ -0,0 +1,48 @@
+// Blargg's test ROMs print their result on the serial port, ending with
+// "Passed" or "Failed". Point BLARGG_ROMS at a checkout of gb-test-roms.
+mod common;
+
+use gb_core::Model;
+
+fn run_blargg(rom: &str, seconds: u64) {
+    let path = common::rom_path("BLARGG_ROMS", "blargg", rom);
+
+    let mut gameboy = common::load(&path, Model::Dmg);
+    common::run_until(&mut gameboy, seconds, |gameboy| {
+	let output = String::from_utf8_lossy(gameboy.serial_output());
+	output.contains("Passed") || output.contains("Failed")
+    });
+
+    let output = String::from_utf8_lossy(gameboy.serial_output());
+    assert!(output.contains("Passed"), "{} didn't pass, serial output:\n{}", rom, output);
+}
+
+macro_rules! blargg_tests {
+    ($($name:ident: $rom:expr, $seconds:expr;)*) => {
+	$(
+	    #[test]
+	    #[ignore = "needs BLARGG_ROMS"]
+	    fn $name() {
+		run_blargg($rom, $seconds);
+	    }
+	)*
+    };
+}
+
+blargg_tests! {
+    cpu_instrs_01_special: "cpu_instrs/individual/01-special.gb", 30;
+    cpu_instrs_02_interrupts: "cpu_instrs/individual/02-interrupts.gb", 30;
+    cpu_instrs_03_op_sp_hl: "cpu_instrs/individual/03-op sp,hl.gb", 30;
+    cpu_instrs_04_op_r_imm: "cpu_instrs/individual/04-op r,imm.gb", 30;
+    cpu_instrs_05_op_rp: "cpu_instrs/individual/05-op rp.gb", 30;
+    cpu_instrs_06_ld_r_r: "cpu_instrs/individual/06-ld r,r.gb", 30;
+    cpu_instrs_07_jr_jp_call_ret_rst: "cpu_instrs/individual/07-jr,jp,call,ret,rst.gb", 30;
+    cpu_instrs_08_misc_instrs: "cpu_instrs/individual/08-misc instrs.gb", 30;
+    cpu_instrs_09_op_r_r: "cpu_instrs/individual/09-op r,r.gb", 30;
+    cpu_instrs_10_bit_ops: "cpu_instrs/individual/10-bit ops.gb", 30;
+    cpu_instrs_11_op_a_hl: "cpu_instrs/individual/11-op a,(hl).gb", 30;
+    instr_timing: "instr_timing/instr_timing.gb", 10;
+    mem_timing_01_read_timing: "mem_timing/individual/01-read_timing.gb", 10;
+    mem_timing_02_write_timing: "mem_timing/individual/02-write_timing.gb", 10;
+    mem_timing_03_modify_timing: "mem_timing/individual/03-modify_timing.gb", 10;
+}
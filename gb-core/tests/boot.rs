@@ -0,0 +1,37 @@
+// The state skip_boot leaves the hardware in, as the boot ROMs do
+mod common;
+
+use gb_core::{Config, GameBoy, Model};
+
+fn gameboy(model: Model) -> GameBoy {
+    GameBoy::new(common::rom(&[]), &Config::new(model)).unwrap()
+}
+
+#[test]
+fn div_starts_where_the_boot_rom_left_it() {
+    for (model, div) in [(Model::Dmg0, 0x18), (Model::Dmg, 0xAB), (Model::Mgb, 0xAB)] {
+	let gameboy = gameboy(model);
+	assert_eq!(gameboy.read_memory(0xFF04), div, "{:?}", model);
+    }
+
+    // It keeps counting from there, once every 256 T-cycles
+    let mut gameboy = gameboy(Model::Dmg);
+    while gameboy.cycles() < 256 {
+	gameboy.step();
+    }
+    assert_eq!(gameboy.read_memory(0xFF04), 0xAC);
+}
+
+#[test]
+fn hands_over_at_the_end_of_vblank() {
+    for model in [Model::Dmg, Model::Sgb, Model::Cgb] {
+	let mut gameboy = gameboy(model);
+	assert_eq!(gameboy.read_memory(0xFF41), 0x85, "{:?}", model);
+	assert_eq!(gameboy.read_memory(0xFF44), 0x00, "{:?}", model);
+
+	// Line 0 starts right away, without another VBlank
+	gameboy.step();
+	assert_eq!(gameboy.read_memory(0xFF41) & 0x03, 0x02, "{:?}", model);
+	assert_eq!(gameboy.frames(), 0);
+    }
+}
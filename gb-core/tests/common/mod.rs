@@ -0,0 +1,68 @@
+// Shared by the test ROM harnesses, each of them uses a part of it
+#![allow(dead_code)]
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use gb_core::{Config, GameBoy, Model};
+
+// 4.194304MHz, the T-cycles in one emulated second
+pub const CYCLES_PER_SECOND: u64 = 4_194_304;
+
+// Test ROMs are never downloaded: they are looked up in the directory named by
+// the environment variable, or in tests/roms/<suite> next to this crate. The
+// tests that need them are ignored, `cargo test -- --ignored` runs them and a
+// missing ROM fails the test.
+fn suite_root(variable: &str, suite: &str) -> PathBuf {
+    match env::var_os(variable) {
+	Some(path) => PathBuf::from(path),
+	None => PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("roms").join(suite),
+    }
+}
+
+pub fn rom_path(variable: &str, suite: &str, rom: &str) -> PathBuf {
+    let path = suite_root(variable, suite).join(rom);
+    assert!(path.is_file(), "{} not found, set {} to the {} directory", path.display(), variable, suite);
+    path
+}
+
+pub fn suite_dir(variable: &str, suite: &str, dir: &str) -> PathBuf {
+    let path = suite_root(variable, suite).join(dir);
+    assert!(path.is_dir(), "{} not found, set {} to the {} directory", path.display(), variable, suite);
+    path
+}
+
+pub fn load(path: &Path, model: Model) -> GameBoy {
+    match GameBoy::from_file(path.to_str().unwrap(), &Config::new(model)) {
+	Ok(gameboy) => gameboy,
+	Err(msg) => panic!("{}: {}", path.display(), msg),
+    }
+}
+
+// A 32KB ROM without a mapper for the tests that build their own: the entry point
+// jumps to the program at 0x150, which spins on JR -2 if it runs off its end.
+// Set the header bytes afterwards, such as 0x143 for CGB games.
+pub fn rom(program: &[u8]) -> Vec<u8> {
+    let mut rom = vec![0; 0x8000];
+    rom[0x100..0x103].copy_from_slice(&[0xC3, 0x50, 0x01]);
+    let end = 0x150 + program.len();
+    rom[0x150..end].copy_from_slice(program);
+    rom[end..end + 2].copy_from_slice(&[0x18, 0xFE]);
+    rom
+}
+
+// Runs until the condition holds or the emulated time runs out, returns whether it held
+pub fn run_until<F: FnMut(&mut GameBoy) -> bool>(gameboy: &mut GameBoy, seconds: u64, mut condition: F) -> bool {
+    let limit = gameboy.cycles() + seconds * CYCLES_PER_SECOND;
+
+    while gameboy.cycles() < limit {
+	if condition(gameboy) {
+	    return true;
+	}
+	gameboy.step();
+    }
+
+    condition(gameboy)
+}
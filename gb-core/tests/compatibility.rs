@@ -0,0 +1,68 @@
+// Palettes the CGB picks for DMG games, checked on the third shade of the BG palette
+mod common;
+
+use gb_core::{Config, GameBoy, ManualPalette, Model};
+
+const BLUE: u32 = 0x0000FF;
+const GREEN: u32 = 0x008400;
+const DARK_GREEN: u32 = 0x0063C6;
+
+// A Nintendo game whose title has the given fourth letter and checksum
+fn rom(fourth_letter: u8, checksum: u8) -> Vec<u8> {
+    let mut rom = common::rom(&[]);
+    rom[0x134..0x138].copy_from_slice(&[b'A', b'B', b'C', fourth_letter]);
+    let sum = rom[0x134..0x138].iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+    rom[0x138] = checksum.wrapping_sub(sum);
+    rom[0x14B] = 0x01;
+    rom
+}
+
+fn background_color(rom: Vec<u8>, config: &Config) -> u32 {
+    let mut gameboy = GameBoy::new(rom, config).unwrap();
+    gameboy.write_memory(0xFF47, 0xAA);
+    gameboy.step_frame();
+    gameboy.step_frame();
+    gameboy.framebuffer()[0]
+}
+
+#[test]
+fn fourth_letter_tells_apart_shared_checksums() {
+    let config = Config::new(Model::Cgb);
+    // POKEMON BLUE and VEGAS STAKES
+    assert_eq!(background_color(rom(b'E', 0x61), &config), BLUE);
+    assert_eq!(background_color(rom(b'A', 0x61), &config), GREEN);
+    assert_eq!(background_color(rom(b'X', 0x61), &config), DARK_GREEN);
+}
+
+#[test]
+fn unknown_and_unlicensed_games_get_the_default() {
+    let config = Config::new(Model::Cgb);
+    assert_eq!(background_color(rom(b'A', 0x5B), &config), DARK_GREEN);
+
+    let mut unlicensed = rom(b'E', 0x61);
+    unlicensed[0x14B] = 0x00;
+    assert_eq!(background_color(unlicensed, &config), DARK_GREEN);
+}
+
+#[test]
+fn manual_palette_overrides_the_table() {
+    let mut config = Config::new(Model::Cgb);
+    config.palette = Some(ManualPalette::Left);
+    assert_eq!(background_color(rom(b'A', 0x61), &config), BLUE);
+}
+
+#[test]
+fn color_correction_can_be_toggled() {
+    let mut gameboy = GameBoy::new(rom(b'E', 0x61), &Config::new(Model::Cgb)).unwrap();
+    gameboy.write_memory(0xFF47, 0xAA);
+    gameboy.step_frame();
+
+    gameboy.set_color_correction(true);
+    gameboy.step_frame();
+    assert!(gameboy.color_correction());
+    assert_ne!(gameboy.framebuffer()[0], BLUE);
+
+    gameboy.set_color_correction(false);
+    gameboy.step_frame();
+    assert_eq!(gameboy.framebuffer()[0], BLUE);
+}
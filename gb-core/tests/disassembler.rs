@@ -0,0 +1,71 @@
+// The disassembler on hand-picked encodings, in RGBDS syntax
+use gb_core::disassemble;
+
+// Address, bytes, text, register named in the comment
+const CASES: [(u16, &[u8], &str, Option<&str>); 22] = [
+    (0x0000, &[0x00], "nop", None),
+    (0x0000, &[0x78], "ld a, b", None),
+    (0x0000, &[0x3E, 0x42], "ld a, $42", None),
+    (0x0000, &[0x36, 0x99], "ld [hl], $99", None),
+    (0x0000, &[0x21, 0x34, 0x12], "ld hl, $1234", None),
+    (0x0000, &[0xFA, 0x34, 0x12], "ld a, [$1234]", None),
+    (0x0000, &[0x08, 0x00, 0xC0], "ld [$c000], sp", None),
+    (0x0000, &[0xEA, 0x0F, 0xFF], "ld [$ff0f], a", Some("IF")),
+    (0x0000, &[0xC3, 0x50, 0x01], "jp $0150", None),
+    (0x0000, &[0xC4, 0x00, 0x40], "call nz, $4000", None),
+    (0x0000, &[0xCB, 0x7C], "bit 7, h", None),
+    (0x0000, &[0xCB, 0x37], "swap a", None),
+    // Relative jumps are shown with their destination
+    (0x0100, &[0x18, 0xFE], "jr $0100", None),
+    (0x0100, &[0x20, 0x05], "jr nz, $0107", None),
+    (0x0200, &[0x38, 0x80], "jr c, $0182", None),
+    (0x0000, &[0xF8, 0x05], "ld hl, sp+$05", None),
+    (0x0000, &[0xF8, 0xFE], "ld hl, sp-$02", None),
+    (0x0000, &[0xE8, 0xFF], "add sp, -$01", None),
+    (0x0000, &[0xE0, 0x40], "ldh [$ff40], a", Some("LCDC")),
+    (0x0000, &[0xF0, 0x44], "ldh a, [$ff44]", Some("LY")),
+    (0x0000, &[0xE2], "ldh [c], a", None),
+    (0x0000, &[0xD3, 0x00], "db $d3", None),
+];
+
+#[test]
+fn decodes_every_form() {
+    for (address, bytes, text, comment) in CASES {
+	let read = |at: u16| bytes.get(at.wrapping_sub(address) as usize).copied().unwrap_or(0);
+	let instruction = disassemble(address, read);
+
+	assert_eq!(instruction.to_string(), text);
+	assert_eq!(instruction.comment(), comment, "{}", text);
+	// Invalid opcodes take a single byte, whatever follows them
+	let length = if instruction.mnemonic == "db" { 1 } else { bytes.len() };
+	assert_eq!(instruction.bytes, bytes[..length], "{}", text);
+    }
+}
+
+#[test]
+fn invalid_opcodes_become_db() {
+    for opcode in [0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD] {
+	let instruction = disassemble(0, |_| opcode);
+	assert_eq!(instruction.to_string(), format!("db ${:02x}", opcode));
+	assert_eq!(instruction.length(), 1);
+    }
+}
+
+#[test]
+fn labels_name_targets_and_addresses() {
+    let label = |address: u16| match address {
+	0x0150 => Some(String::from("Main")),
+	0xC000 => Some(String::from("wCounter")),
+	_ => None,
+    };
+
+    let call = disassemble(0, |at| [0xCD, 0x50, 0x01][at as usize]);
+    assert_eq!(call.format(label), "call Main");
+    assert_eq!(call.target(), Some(0x0150));
+
+    let load = disassemble(0, |at| [0xFA, 0x00, 0xC0][at as usize]);
+    assert_eq!(load.format(label), "ld a, [wCounter]");
+
+    let jump = disassemble(0x0140, |at| [0x18, 0x0E][(at - 0x0140) as usize]);
+    assert_eq!(jump.format(label), "jr Main");
+}
@@ -0,0 +1,39 @@
+// The LY stub Gameboy Doctor logs are made with
+mod common;
+
+use gb_core::{Config, GameBoy, Model};
+
+// LDH A,(LY); LD (C000),A; JR -5
+const PROGRAM: [u8; 7] = [0xF0, 0x44, 0xEA, 0x00, 0xC0, 0x18, 0xF9];
+
+fn gameboy() -> GameBoy {
+    GameBoy::new(common::rom(&PROGRAM), &Config::new(Model::Dmg)).unwrap()
+}
+
+// What the program stored over a frame, once per loop
+fn stored_lines(gameboy: &mut GameBoy) -> Vec<u8> {
+    let mut lines = Vec::new();
+    for _ in 0..6000 {
+	if gameboy.registers().pc == 0x0155 {
+	    lines.push(gameboy.read_memory(0xC000));
+	}
+	gameboy.step();
+    }
+    lines.dedup();
+    lines
+}
+
+#[test]
+fn the_cpu_reads_ly_as_0x90() {
+    let mut gameboy = gameboy();
+    gameboy.set_doctor_mode(true);
+    assert_eq!(stored_lines(&mut gameboy), vec![0x90]);
+    // The PPU still counts lines
+    assert_ne!(gameboy.read_memory(0xFF44), 0x90);
+}
+
+#[test]
+fn the_real_ly_is_read_otherwise() {
+    let mut gameboy = gameboy();
+    assert!(stored_lines(&mut gameboy).len() > 100);
+}
@@ -0,0 +1,84 @@
+// VRAM DMA and the CGB double speed it is timed against, on ROMs built here
+mod common;
+
+use gb_core::{Config, GameBoy, Model};
+
+const DOTS_PER_FRAME: u64 = 70224;
+// LD A,1; LDH (KEY1),A; STOP
+const SWITCH_SPEED: [u8; 6] = [0x3E, 0x01, 0xE0, 0x4D, 0x10, 0x00];
+
+// Flagged for CGB, or it would run in compatibility mode
+fn rom(program: &[u8]) -> Vec<u8> {
+    let mut rom = common::rom(program);
+    rom[0x143] = 0x80;
+    rom
+}
+
+// Copies a block from C000 to 8000
+fn start_dma(gameboy: &mut GameBoy) {
+    for (address, value) in [(0xFF51, 0xC0), (0xFF52, 0x00), (0xFF53, 0x00), (0xFF54, 0x00), (0xFF55, 0x00)] {
+	gameboy.write_memory(address, value);
+    }
+}
+
+#[test]
+fn general_purpose_dma_writes_vram_during_mode_3() {
+    let mut gameboy = GameBoy::new(rom(&[]), &Config::new(Model::Cgb)).unwrap();
+    for offset in 0..0x10 {
+	gameboy.write_memory(0xC000 + offset, offset as u8 + 1);
+    }
+
+    while gameboy.read_memory(0xFF41) & 0x03 != 0x03 {
+	gameboy.step();
+    }
+    start_dma(&mut gameboy);
+    while gameboy.read_memory(0xFF41) & 0x03 == 0x03 {
+	gameboy.step();
+    }
+
+    let vram: Vec<u8> = (0x8000..0x8010).map(|address| gameboy.read_memory(address)).collect();
+    assert_eq!(vram, (1..=0x10).collect::<Vec<u8>>());
+}
+
+#[test]
+fn stop_switches_speed_once_armed() {
+    let mut gameboy = GameBoy::new(rom(&SWITCH_SPEED), &Config::new(Model::Cgb)).unwrap();
+    assert_eq!(gameboy.read_memory(0xFF4D), 0x7E);
+    gameboy.step_frame();
+    assert_eq!(gameboy.read_memory(0xFF4D), 0xFE);
+
+    // The CPU runs twice the cycles in the same frame
+    let cycles = gameboy.step_frame();
+    assert!(cycles.abs_diff(2 * DOTS_PER_FRAME) <= 24, "{} cycles in a double speed frame", cycles);
+
+    let mut dmg = GameBoy::new(rom(&SWITCH_SPEED), &Config::new(Model::Dmg)).unwrap();
+    dmg.step_frame();
+    let cycles = dmg.step_frame();
+    assert!(cycles.abs_diff(DOTS_PER_FRAME) <= 24, "{} cycles in a DMG frame", cycles);
+}
+
+// A block halts the CPU for 8 M-cycles in normal speed and 16 in double speed
+#[test]
+fn dma_stall_lasts_as_long_in_both_speeds() {
+    for (program, stall) in [(&[][..], 32), (&SWITCH_SPEED[..], 64)] {
+	let mut gameboy = GameBoy::new(rom(program), &Config::new(Model::Cgb)).unwrap();
+	gameboy.step_frame();
+	let jump = gameboy.step();
+
+	start_dma(&mut gameboy);
+	assert_eq!(gameboy.step(), jump + stall);
+    }
+}
+
+#[test]
+fn double_speed_survives_a_save_state() {
+    let mut gameboy = GameBoy::new(rom(&SWITCH_SPEED), &Config::new(Model::Cgb)).unwrap();
+    let state = gameboy.save_state();
+    gameboy.step_frame();
+
+    let double_speed = gameboy.save_state();
+    gameboy.load_state(&state).unwrap();
+    assert_eq!(gameboy.read_memory(0xFF4D), 0x7E);
+    gameboy.load_state(&double_speed).unwrap();
+    assert_eq!(gameboy.read_memory(0xFF4D), 0xFE);
+}
@@ -0,0 +1,191 @@
+// Mooneye's acceptance tests end with LD B,B, after loading the Fibonacci
+// numbers 3/5/8/13/21/34 into B-L when they pass. Point MOONEYE_ROMS at a
+// build of mooneye-test-suite.
+mod common;
+
+use std::{
+    fs,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
+
+use gb_core::Model;
+
+const MODELS: [Model; 7] = [Model::Dmg0, Model::Dmg, Model::Mgb, Model::Sgb, Model::Sgb2, Model::Cgb, Model::Agb];
+
+// Model suffixes of the ROM names, e.g. boot_regs-dmgABCmgb or di_timing-GS
+const SUFFIXES: [(&str, &[Model]); 13] = [
+    ("dmgABC", &[Model::Dmg]),
+    ("dmg0", &[Model::Dmg0]),
+    ("mgb", &[Model::Mgb]),
+    ("sgb2", &[Model::Sgb2]),
+    ("sgb", &[Model::Sgb]),
+    ("cgbABCDE", &[Model::Cgb]),
+    ("cgb0", &[Model::Cgb]),
+    ("cgb", &[Model::Cgb]),
+    ("agb", &[Model::Agb]),
+    ("G", &[Model::Dmg0, Model::Dmg, Model::Mgb]),
+    ("S", &[Model::Sgb, Model::Sgb2]),
+    ("C", &[Model::Cgb, Model::Agb]),
+    ("A", &[Model::Agb]),
+];
+
+const PASS_SIGNATURE: [u8; 6] = [3, 5, 8, 13, 21, 34];
+
+// ROMs that fail on every model they run on, and why. One of them passing
+// fails the run too, so that it gets taken off the list.
+const KNOWN_FAILURES: [(&str, &str); 4] = [
+    ("ppu/hblank_ly_scx_timing-GS", "mode 3 always lasts 172 dots, SCX doesn't lengthen it"),
+    ("ppu/intr_2_mode0_timing_sprites", "mode 3 always lasts 172 dots, objects don't lengthen it"),
+    ("ppu/lcdon_timing-GS", "the first line after the LCD is turned on starts with an OAM scan"),
+    ("ppu/lcdon_write_timing-GS", "the first line after the LCD is turned on starts with an OAM scan"),
+];
+
+#[derive(Copy, Clone, PartialEq)]
+enum Outcome {
+    Pass,
+    Fail,
+    Timeout,
+    Crash,
+    Skipped,
+    // Failed, timed out or crashed as listed in KNOWN_FAILURES
+    Expected,
+    Unexpected,
+}
+
+impl Outcome {
+    fn label(&self) -> &'static str {
+	match self {
+	    Outcome::Pass => "pass",
+	    Outcome::Fail => "FAIL",
+	    Outcome::Timeout => "TIME",
+	    Outcome::Crash => "CRASH",
+	    Outcome::Skipped => "-",
+	    Outcome::Expected => "xfail",
+	    Outcome::Unexpected => "XPASS",
+	}
+    }
+}
+
+fn known_failure(name: &str) -> Option<&'static str> {
+    KNOWN_FAILURES.iter().find(|(rom, _)| *rom == name).map(|(_, reason)| *reason)
+}
+
+// The models a ROM targets, all of them when its name has no model suffix
+fn models_for(name: &str) -> Vec<Model> {
+    let mut suffix = match name.rsplit_once('-') {
+	Some((_, suffix)) => suffix,
+	None => return MODELS.to_vec(),
+    };
+
+    let mut models = Vec::new();
+    while !suffix.is_empty() {
+	match SUFFIXES.iter().find(|(prefix, _)| suffix.starts_with(prefix)) {
+	    Some((prefix, group)) => {
+		models.extend_from_slice(group);
+		suffix = &suffix[prefix.len()..];
+	    }
+	    None => return MODELS.to_vec(),
+	}
+    }
+
+    MODELS.iter().copied().filter(|model| models.contains(model)).collect()
+}
+
+fn collect_roms(dir: &Path, roms: &mut Vec<PathBuf>) {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+    entries.sort();
+
+    for path in entries {
+	if path.is_dir() {
+	    // These need someone looking at the screen
+	    if path.file_name().unwrap() != "manual-only" {
+		collect_roms(&path, roms);
+	    }
+	} else if path.extension().is_some_and(|extension| extension == "gb") {
+	    roms.push(path);
+	}
+    }
+}
+
+fn run_mooneye(path: &Path, model: Model) -> Outcome {
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+	let mut gameboy = common::load(path, model);
+	let stopped = common::run_until(&mut gameboy, 20, |gameboy| gameboy.take_breakpoint() || gameboy.invalid_opcode().is_some());
+	if gameboy.invalid_opcode().is_some() {
+	    return Outcome::Crash;
+	}
+	if !stopped {
+	    return Outcome::Timeout;
+	}
+
+	let registers = gameboy.registers();
+	let signature = [registers.b, registers.c, registers.d, registers.e, registers.h, registers.l];
+	if signature == PASS_SIGNATURE {
+	    Outcome::Pass
+	} else {
+	    Outcome::Fail
+	}
+    }));
+
+    result.unwrap_or(Outcome::Crash)
+}
+
+#[test]
+#[ignore = "needs MOONEYE_ROMS"]
+fn acceptance() {
+    let dir = common::suite_dir("MOONEYE_ROMS", "mooneye", "acceptance");
+
+    let mut roms = Vec::new();
+    collect_roms(&dir, &mut roms);
+
+    // A ROM that panics the emulator is reported as a crash in the matrix, the
+    // panic message is still printed by the default hook
+    let results: Vec<(String, Vec<Outcome>)> = roms
+	.iter()
+	.map(|path| {
+	    let name = path.strip_prefix(&dir).unwrap().with_extension("").display().to_string();
+	    let models = models_for(&name);
+	    let outcomes = MODELS
+		.iter()
+		.map(|model| match (models.contains(model), known_failure(&name)) {
+		    (false, _) => Outcome::Skipped,
+		    (true, None) => run_mooneye(path, *model),
+		    (true, Some(_)) if run_mooneye(path, *model) == Outcome::Pass => Outcome::Unexpected,
+		    (true, Some(_)) => Outcome::Expected,
+		})
+		.collect();
+	    (name, outcomes)
+	})
+	.collect();
+
+    let width = results.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    print!("{:width$}", "", width = width);
+    for model in MODELS.iter() {
+	print!(" {:>5}", format!("{:?}", model));
+    }
+    println!();
+
+    let mut passed = 0;
+    let mut failed = 0;
+    let mut expected = 0;
+    for (name, outcomes) in results.iter() {
+	print!("{:width$}", name, width = width);
+	for outcome in outcomes {
+	    print!(" {:>5}", outcome.label());
+	    match outcome {
+		Outcome::Pass => passed += 1,
+		Outcome::Skipped => {}
+		Outcome::Expected => expected += 1,
+		_ => failed += 1,
+	    }
+	}
+	match known_failure(name) {
+	    Some(reason) => println!("  ({})", reason),
+	    None => println!(),
+	}
+    }
+
+    println!("{} passed, {} failed, {} expected failures", passed, failed, expected);
+    assert_eq!(failed, 0, "{} of {} runs failed", failed, passed + failed + expected);
+}
@@ -0,0 +1,72 @@
+// Movies: playing back a recording must end in the very same state, and the
+// text format must keep every frame of input.
+mod common;
+
+use gb_core::{Button, Config, GameBoy, Model, Movie};
+
+// Adds the direction keys read from P1 into WRAM, so every press changes the run
+fn joypad_rom(marker: u8) -> Vec<u8> {
+    let mut rom = common::rom(&[0x3E, 0x20, 0xE0, 0x00, 0xF0, 0x00, 0x21, 0x00, 0xC0, 0x86, 0x77, 0x18, 0xF3]);
+    rom[0x134] = marker;
+    rom
+}
+
+fn record(frames: u64) -> (Movie, u64) {
+    let config = Config::new(Model::Dmg);
+    let mut gameboy = GameBoy::new(joypad_rom(0), &config).unwrap();
+    let mut movie = Movie::new(&gameboy, &config);
+
+    for frame in 0..frames {
+	match frame % 7 {
+	    0 => gameboy.press(Button::Up),
+	    3 => gameboy.release(Button::Up),
+	    4 => gameboy.press(Button::Right),
+	    6 => gameboy.release(Button::Right),
+	    _ => {}
+	}
+	movie.record(&gameboy);
+	gameboy.step_frame();
+    }
+    (movie, gameboy.state_hash())
+}
+
+#[test]
+fn playback_ends_in_the_recorded_state() {
+    let (mut movie, hash) = record(40);
+    movie.final_hash = Some(hash);
+
+    let movie = Movie::parse(&movie.to_string()).unwrap();
+    assert_eq!(movie.len(), 40);
+    assert_eq!(movie.final_hash, Some(hash));
+
+    let config = Config::new(movie.model);
+    let mut gameboy = GameBoy::new(joypad_rom(0), &config).unwrap();
+    movie.check(&gameboy, &config).unwrap();
+    while movie.play(&mut gameboy) {
+	gameboy.step_frame();
+    }
+    assert_eq!(gameboy.frames(), 40);
+    assert_eq!(gameboy.state_hash(), hash);
+
+    // Without the input the run goes elsewhere
+    let mut idle = GameBoy::new(joypad_rom(0), &config).unwrap();
+    for _ in 0..40 {
+	idle.step_frame();
+    }
+    assert_ne!(idle.state_hash(), hash);
+}
+
+#[test]
+fn refuses_another_console() {
+    let (movie, _) = record(1);
+    let config = Config::new(Model::Dmg);
+
+    let other_rom = GameBoy::new(joypad_rom(1), &config).unwrap();
+    assert!(movie.check(&other_rom, &config).is_err());
+
+    let cgb = Config::new(Model::Cgb);
+    assert!(movie.check(&GameBoy::new(joypad_rom(0), &cgb).unwrap(), &cgb).is_err());
+
+    assert!(Movie::parse("lb-emu movie 1\nrom 0\nmodel dmg\ninput\nUDLR\n").is_err());
+    assert!(Movie::parse("not a movie").is_err());
+}
@@ -0,0 +1,227 @@
+// BizHawk .bk2 and VisualBoyAdvance .vbm movies, from fixtures built here
+mod common;
+
+use gb_core::{import_movie, sha1, Model};
+
+const UP: u8 = 0x01;
+const DOWN: u8 = 0x02;
+const LEFT: u8 = 0x04;
+const RIGHT: u8 = 0x08;
+const SELECT: u8 = 0x10;
+const START: u8 = 0x20;
+const B: u8 = 0x40;
+const A: u8 = 0x80;
+
+// Gambatte writes Start before Select
+const GAMBATTE_LOG: &str = "\
+[Input]
+LogKey:#Up|Down|Left|Right|Start|Select|B|A|Power|
+|........P|
+|U...S..A.|
+|.D.R.sB..|
+|........P|
+[/Input]
+";
+
+// The link cable core, a group per console and one for both
+const LINK_LOG: &str = "\
+[Input]
+LogKey:#Toggle Cable|#P1 Up|P1 Down|P1 Left|P1 Right|P1 Start|P1 Select|P1 B|P1 A|P1 Power|#P2 Up|P2 Down|P2 Left|P2 Right|P2 Start|P2 Select|P2 B|P2 A|P2 Power|
+|.|U........|.......A.|
+|T|....S....|..L......|
+[/Input]
+";
+
+fn rom() -> Vec<u8> {
+    let mut rom = common::rom(&[]);
+    rom[0x134..0x140].copy_from_slice(b"MOVIE TEST  ");
+    rom[0x14D] = 0x5A;
+    rom
+}
+
+// A zip archive with the files stored uncompressed, the central directory at its end
+fn zip(files: &[(&str, &str)]) -> Vec<u8> {
+    let mut zip = Vec::new();
+    let mut directory = Vec::new();
+
+    for (name, content) in files {
+	let offset = zip.len() as u32;
+	let sizes = [(content.len() as u32).to_le_bytes(), (content.len() as u32).to_le_bytes()].concat();
+
+	zip.extend_from_slice(b"PK\x03\x04");
+	zip.extend_from_slice(&[20, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+	zip.extend_from_slice(&sizes);
+	zip.extend_from_slice(&(name.len() as u16).to_le_bytes());
+	zip.extend_from_slice(&[0, 0]);
+	zip.extend_from_slice(name.as_bytes());
+	zip.extend_from_slice(content.as_bytes());
+
+	directory.extend_from_slice(b"PK\x01\x02");
+	directory.extend_from_slice(&[20, 0, 20, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+	directory.extend_from_slice(&sizes);
+	directory.extend_from_slice(&(name.len() as u16).to_le_bytes());
+	directory.extend_from_slice(&[0; 12]);
+	directory.extend_from_slice(&offset.to_le_bytes());
+	directory.extend_from_slice(name.as_bytes());
+    }
+
+    let directory_offset = zip.len() as u32;
+    let count = (files.len() as u16).to_le_bytes();
+    zip.extend_from_slice(&directory);
+    zip.extend_from_slice(b"PK\x05\x06\0\0\0\0");
+    zip.extend_from_slice(&count);
+    zip.extend_from_slice(&count);
+    zip.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    zip.extend_from_slice(&directory_offset.to_le_bytes());
+    zip.extend_from_slice(&[0, 0]);
+    zip
+}
+
+fn bk2(header: &str, settings: Option<&str>, log: &str) -> Vec<u8> {
+    let mut files = vec![("Header.txt", header), ("Input Log.txt", log)];
+    files.extend(settings.map(|settings| ("SyncSettings.json", settings)));
+    zip(&files)
+}
+
+// Input words of the players set in the controller flags, frame after frame
+fn vbm(system: u8, emulator_type: u32, controllers: u8, input: &[u16]) -> Vec<u8> {
+    let players = controllers.count_ones() as usize;
+    let mut data = vec![0; 0x40];
+    data[0x00..0x04].copy_from_slice(b"VBM\x1A");
+    data[0x04..0x08].copy_from_slice(&1u32.to_le_bytes());
+    data[0x0C..0x10].copy_from_slice(&((input.len() / players) as u32).to_le_bytes());
+    data[0x15] = controllers;
+    data[0x16] = system;
+    data[0x17] = 0x20;
+    data[0x20..0x24].copy_from_slice(&emulator_type.to_le_bytes());
+    data[0x24..0x30].copy_from_slice(b"MOVIE TEST  ");
+    data[0x31] = 0x5A;
+    data[0x3C..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+    for word in input {
+	data.extend_from_slice(&word.to_le_bytes());
+    }
+    data
+}
+
+#[test]
+fn sha1_known_answers() {
+    assert_eq!(sha1(b""), "DA39A3EE5E6B4B0D3255BFEF95601890AFD80709");
+    assert_eq!(sha1(b"abc"), "A9993E364706816ABA3E25717850C26C9CD0D89D");
+    // Two blocks, the padding doesn't fit after the message
+    assert_eq!(sha1(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"), "84983E441C3BD26EBAAE4AA1F95129E5E54670F1");
+    assert_eq!(sha1(&vec![b'a'; 1_000_000]), "34AA973CD4C4DAA4F61EEB2BDBAD27316534016F");
+}
+
+#[test]
+fn bk2_buttons_are_mapped_by_name() {
+    let rom = rom();
+    let header = format!("MovieVersion BizHawk v2.0.0\nPlatform GB\nSHA1 {}\n", sha1(&rom).to_lowercase());
+    let (movie, warnings) = import_movie(&bk2(&header, None, GAMBATTE_LOG), &rom, None).unwrap();
+
+    let frames: Vec<u8> = movie.frames.iter().map(|frame| frame[0]).collect();
+    assert_eq!(frames, [0, UP | START | A, DOWN | RIGHT | SELECT | B, 0]);
+    // Power on the first frame is the console starting, later it can't be followed
+    assert_eq!(warnings, ["The movie power cycles the console at frame 3, which isn't supported"]);
+}
+
+#[test]
+fn bk2_players_get_their_own_columns() {
+    let (movie, warnings) = import_movie(&bk2("Platform GB\n", None, LINK_LOG), &rom(), None).unwrap();
+
+    assert_eq!(movie.frames[0][..2], [UP, A]);
+    assert_eq!(movie.frames[1][..2], [START, LEFT]);
+    assert_eq!(warnings, ["The Toggle Cable input of the movie is ignored"]);
+}
+
+#[test]
+fn bk2_settings_pick_the_model() {
+    let rom = rom();
+    let model = |header: &str, settings: Option<&str>| import_movie(&bk2(header, settings, GAMBATTE_LOG), &rom, None).unwrap().0.model;
+
+    assert_eq!(model("Platform GB\n", Some(r#"{"o":{"ConsoleMode":2}}"#)), Model::Cgb);
+    assert_eq!(model("Platform GB\n", Some(r#"{"o":{"ConsoleMode":"GBA"}}"#)), Model::Agb);
+    assert_eq!(model("Platform GB\n", Some(r#"{"ConsoleMode":1}"#)), Model::Dmg);
+    // On automatic, what the core picked or the ROM asks for
+    assert_eq!(model("Platform GB\nIsCGBMode 1\n", Some(r#"{"o":{"ConsoleMode":0}}"#)), Model::Cgb);
+    assert_eq!(model("Platform SGB\n", None), Model::Sgb);
+    assert_eq!(model("Platform GB\n", None), Model::Dmg);
+
+    let mut cgb_rom = rom.clone();
+    cgb_rom[0x143] = 0x80;
+    let (movie, _) = import_movie(&bk2("Platform GB\n", None, GAMBATTE_LOG), &cgb_rom, None).unwrap();
+    assert_eq!(movie.model, Model::Cgb);
+}
+
+#[test]
+fn bk2_warns_about_unsupported_settings() {
+    let settings = r#"{"o":{"ConsoleMode":"Odd","EnableBIOS":true,"UseRealTime":true}}"#;
+    let (movie, warnings) = import_movie(&bk2("Platform GB\n", Some(settings), GAMBATTE_LOG), &rom(), None).unwrap();
+
+    assert_eq!(movie.model, Model::Dmg);
+    assert!(warnings.iter().any(|warning| warning.starts_with("Unknown console mode")));
+    assert!(warnings.iter().any(|warning| warning.contains("--boot-rom")));
+    assert!(warnings.iter().any(|warning| warning.contains("clock of the host")));
+}
+
+#[test]
+fn bk2_rejects_what_cant_be_played() {
+    let rom = rom();
+    let rejected = |header: &str| import_movie(&bk2(header, None, GAMBATTE_LOG), &rom, None).is_err();
+
+    assert!(rejected(&format!("SHA1 {}\n", sha1(b"another ROM"))));
+    assert!(rejected("Platform GBA\n"));
+    assert!(rejected("StartsFromSavestate True\n"));
+    assert!(import_movie(&zip(&[("Header.txt", "")]), &rom, None).is_err());
+}
+
+#[test]
+fn vbm_buttons_and_players() {
+    // A, B, Select, Start, Right, Left, Up, Down from the lowest bit
+    let input = [0x0001, 0x0080, 0x000A, 0x0050, 0x0C00, 0x0004];
+    let (movie, warnings) = import_movie(&vbm(0, 0, 0b11, &input), &rom(), None).unwrap();
+
+    assert_eq!(movie.model, Model::Dmg);
+    let frames: Vec<[u8; 2]> = movie.frames.iter().map(|frame| [frame[0], frame[1]]).collect();
+    assert_eq!(frames, [[A, DOWN], [B | START, RIGHT | UP], [0, SELECT]]);
+    assert_eq!(warnings, ["The movie resets the console at frame 2, which isn't supported"]);
+}
+
+#[test]
+fn vbm_flags_pick_the_model() {
+    let rom = rom();
+    let model = |system: u8, emulator_type: u32| import_movie(&vbm(system, emulator_type, 1, &[0]), &rom, None).map(|(movie, _)| movie.model);
+
+    assert_eq!(model(2, 0), Ok(Model::Cgb));
+    assert_eq!(model(2, 4), Ok(Model::Agb));
+    assert_eq!(model(4, 0), Ok(Model::Sgb));
+    assert_eq!(model(4, 5), Ok(Model::Sgb2));
+    assert!(model(1, 0).is_err());
+
+    let mut other = rom.clone();
+    other[0x14D] = 0;
+    assert!(import_movie(&vbm(0, 0, 1, &[0]), &other, None).is_err());
+}
+
+#[test]
+fn vbm_boot_rom_option() {
+    let rom = rom();
+    let boot_rom = vec![0; 0x100];
+    let with_options = |options: u8| {
+	let mut data = vbm(0, 0, 1, &[0]);
+	data[0x17] |= options;
+	data
+    };
+
+    let (movie, warnings) = import_movie(&with_options(0x01), &rom, Some(&boot_rom)).unwrap();
+    assert!(movie.boot_rom_crc32.is_some());
+    assert!(warnings.is_empty());
+
+    let (movie, warnings) = import_movie(&with_options(0x01), &rom, None).unwrap();
+    assert_eq!(movie.boot_rom_crc32, None);
+    assert_eq!(warnings, ["The movie was recorded with the boot ROM, pass it with --boot-rom"]);
+
+    // Skipping the intro starts the game as without the boot ROM
+    let (movie, warnings) = import_movie(&with_options(0x03), &rom, Some(&boot_rom)).unwrap();
+    assert_eq!(movie.boot_rom_crc32, None);
+    assert!(warnings.is_empty());
+}
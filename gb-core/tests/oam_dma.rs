@@ -0,0 +1,72 @@
+// OAM DMA, on ROMs built here with the LCD off so that the PPU leaves OAM alone
+mod common;
+
+use gb_core::{Config, GameBoy, Model};
+
+// 160 bytes after an M-cycle of setup
+const DMA_CYCLES: u64 = 161 * 4;
+
+fn start(source: &[u8]) -> GameBoy {
+    let mut gameboy = GameBoy::new(common::rom(&[]), &Config::new(Model::Dmg)).unwrap();
+    gameboy.write_memory(0xFF40, 0x00);
+    for (offset, value) in source.iter().enumerate() {
+	gameboy.write_memory(0xC000 + offset as u16, *value);
+    }
+    gameboy.write_memory(0xFF46, 0xC0);
+    gameboy
+}
+
+fn run_for(gameboy: &mut GameBoy, cycles: u64) {
+    let end = gameboy.cycles() + cycles;
+    while gameboy.cycles() < end {
+	gameboy.step();
+    }
+}
+
+fn oam(gameboy: &GameBoy) -> Vec<u8> {
+    (0xFE00..0xFEA0).map(|address| gameboy.read_memory(address)).collect()
+}
+
+#[test]
+fn dma_copies_a_page_to_oam() {
+    let source: Vec<u8> = (1..=0xA0).collect();
+    let mut gameboy = start(&source);
+    assert_eq!(gameboy.read_memory(0xFF46), 0xC0);
+    assert_eq!(oam(&gameboy), [0; 0xA0]);
+
+    run_for(&mut gameboy, DMA_CYCLES);
+    assert_eq!(oam(&gameboy), source);
+}
+
+// The CPU reads 0xFF from OAM until the transfer is over
+#[test]
+fn cpu_is_locked_out_of_oam() {
+    #[rustfmt::skip]
+    let program = [
+	0xAF, 0xE0, 0x40,             // XOR A; LDH (LCDC),A
+	0x3E, 0xC0, 0xE0, 0x46,       // LD A,$C0; LDH (DMA),A
+	0xFA, 0x00, 0xFE, 0x47,       // LD A,($FE00); LD B,A
+	0x3E, 0x28, 0x3D, 0x20, 0xFD, // LD A,40; DEC A; JR NZ,-3
+	0xFA, 0x00, 0xFE, 0x4F,       // LD A,($FE00); LD C,A
+    ];
+    let mut gameboy = GameBoy::new(common::rom(&program), &Config::new(Model::Dmg)).unwrap();
+    gameboy.write_memory(0xC000, 0x42);
+    run_for(&mut gameboy, 1000);
+
+    let registers = gameboy.registers();
+    assert_eq!((registers.b, registers.c), (0xFF, 0x42));
+}
+
+#[test]
+fn transfer_resumes_from_a_save_state() {
+    let source: Vec<u8> = (0..0xA0).map(|offset| 0xFF - offset as u8).collect();
+    let mut gameboy = start(&source);
+    run_for(&mut gameboy, DMA_CYCLES / 2);
+    let state = gameboy.save_state();
+    assert_ne!(oam(&gameboy), source);
+
+    let mut restored = GameBoy::new(common::rom(&[]), &Config::new(Model::Dmg)).unwrap();
+    restored.load_state(&state).unwrap();
+    run_for(&mut restored, DMA_CYCLES / 2 + 8);
+    assert_eq!(oam(&restored), source);
+}
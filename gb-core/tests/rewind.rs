@@ -0,0 +1,53 @@
+// Stepping back through the rewind history must return the exact states that
+// were pushed, newest first, without going over the memory budget.
+mod common;
+
+use gb_core::{Config, GameBoy, Model, Rewind};
+
+// Keeps counting up in a page of WRAM, so every frame differs from the previous one
+const COUNTER: [u8; 7] = [0x21, 0x00, 0xC0, 0x34, 0x2C, 0x18, 0xFC];
+
+#[test]
+fn steps_back_through_pushed_frames() {
+    let mut gameboy = GameBoy::new(common::rom(&COUNTER), &Config::new(Model::Dmg)).unwrap();
+    let mut rewind = Rewind::new(600, 64 * 1024 * 1024);
+    let mut history = Vec::new();
+
+    for _ in 0..100 {
+	rewind.push(&gameboy);
+	history.push(gameboy.save_state());
+	gameboy.step_frame();
+    }
+    assert_eq!(rewind.len(), 100);
+    assert!(rewind.memory_used() < history.iter().map(Vec::len).sum::<usize>() / 4);
+
+    while let Some(expected) = history.pop() {
+	assert!(rewind.step_back(&mut gameboy).unwrap());
+	assert!(gameboy.save_state() == expected, "{} frames from the start", history.len());
+    }
+    assert!(!rewind.step_back(&mut gameboy).unwrap());
+    assert!(rewind.is_empty());
+    assert_eq!(rewind.memory_used(), 0);
+}
+
+#[test]
+fn drops_the_oldest_frames_past_the_limits() {
+    let mut gameboy = GameBoy::new(common::rom(&COUNTER), &Config::new(Model::Dmg)).unwrap();
+    let state_size = gameboy.save_state().len();
+
+    let mut by_frames = Rewind::new(45, usize::MAX);
+    let mut by_budget = Rewind::new(usize::MAX, state_size * 3);
+    for _ in 0..100 {
+	by_frames.push(&gameboy);
+	by_budget.push(&gameboy);
+	gameboy.step_frame();
+    }
+
+    assert!(by_frames.len() <= 45 && !by_frames.is_empty());
+    assert!(by_budget.memory_used() <= state_size * 3 && !by_budget.is_empty());
+
+    // The newest frame is always the last one pushed
+    let cycles = gameboy.cycles();
+    assert!(by_budget.step_back(&mut gameboy).unwrap());
+    assert!(gameboy.cycles() < cycles);
+}
@@ -0,0 +1,156 @@
+// Save states: loading one and running again must replay exactly what
+// happened after it was saved. The ROMs are built here, no files needed.
+mod common;
+
+use gb_core::{Config, GameBoy, Model};
+
+// Keeps incrementing bytes of the first VRAM page, which only sticks outside mode 3
+fn vram_rom(marker: u8) -> Vec<u8> {
+    let mut rom = common::rom(&[0x21, 0x00, 0x80, 0x34, 0x2C, 0x18, 0xFC]);
+    rom[0x134] = marker;
+    rom
+}
+
+fn snapshot(gameboy: &GameBoy) -> (u64, u64, Vec<u32>, Vec<u8>) {
+    let vram = (0x8000..0x8100).map(|address| gameboy.read_memory(address)).collect();
+    (gameboy.cycles(), gameboy.frames(), gameboy.framebuffer().to_vec(), vram)
+}
+
+#[test]
+fn load_replays_from_the_saved_point() {
+    for model in [Model::Dmg, Model::Cgb] {
+	let mut gameboy = GameBoy::new(vram_rom(0), &Config::new(model)).unwrap();
+	for _ in 0..3 {
+	    gameboy.step_frame();
+	}
+	for _ in 0..1234 {
+	    gameboy.step();
+	}
+
+	let state = gameboy.save_state();
+	let registers = gameboy.registers();
+	for _ in 0..5 {
+	    gameboy.step_frame();
+	}
+	let expected = snapshot(&gameboy);
+
+	gameboy.load_state(&state).unwrap();
+	assert_eq!(gameboy.registers(), registers);
+	for _ in 0..5 {
+	    gameboy.step_frame();
+	}
+	assert!(snapshot(&gameboy) == expected, "{:?} diverged after loading", model);
+    }
+}
+
+#[test]
+fn rejects_states_from_another_rom_or_model() {
+    let state = GameBoy::new(vram_rom(0), &Config::new(Model::Dmg)).unwrap().save_state();
+
+    let mut other_rom = GameBoy::new(vram_rom(1), &Config::new(Model::Dmg)).unwrap();
+    assert!(other_rom.load_state(&state).is_err());
+
+    let mut other_model = GameBoy::new(vram_rom(0), &Config::new(Model::Mgb)).unwrap();
+    assert!(other_model.load_state(&state).is_err());
+}
+
+#[test]
+fn failed_load_leaves_the_machine_unchanged() {
+    let mut gameboy = GameBoy::new(vram_rom(0), &Config::new(Model::Dmg)).unwrap();
+    gameboy.step_frame();
+    let state = gameboy.save_state();
+    gameboy.step_frame();
+    let before = snapshot(&gameboy);
+
+    assert!(gameboy.load_state(&state[..state.len() - 1]).is_err());
+    assert!(gameboy.load_state(b"not a save state").is_err());
+    assert!(snapshot(&gameboy) == before);
+}
+
+// A BESS file as another emulator would write it, with a block we don't know
+fn foreign_bess(model: &[u8; 4]) -> Vec<u8> {
+    let mut wram = vec![0; 0x2000];
+    wram[0x10] = 0x42;
+    let mut hram = vec![0; 0x7F];
+    hram[0] = 0x99;
+    let oam = vec![0; 0xA0];
+    let vram = vec![0x55; 0x2000];
+
+    let mut file = Vec::new();
+    let mut buffer = |core: &mut Vec<u8>, data: &[u8]| {
+	core.extend_from_slice(&(data.len() as u32).to_le_bytes());
+	core.extend_from_slice(&(file.len() as u32).to_le_bytes());
+	file.extend_from_slice(data);
+    };
+
+    let mut core = vec![1, 0, 1, 0];
+    core.extend_from_slice(model);
+    for register in [0x0150u16, 0x12B0, 0x3456, 0x789A, 0xC010, 0xDFF0] {
+	core.extend_from_slice(&register.to_le_bytes());
+    }
+    core.extend_from_slice(&[1, 0x05, 0, 0]);
+    let mut io = [0; 0x80];
+    io[0x40] = 0x91;
+    io[0x47] = 0xE4;
+    io[0x06] = 0x77;
+    core.extend_from_slice(&io);
+    for data in [&wram[..], &vram, &[], &oam, &hram, &[], &[]] {
+	buffer(&mut core, data);
+    }
+
+    let first_block = file.len() as u32;
+    for (id, data) in [(b"NAME", &b"Other 1.0"[..]), (b"CORE", &core), (b"XYZW", b"opaque"), (b"END ", &[])] {
+	file.extend_from_slice(id);
+	file.extend_from_slice(&(data.len() as u32).to_le_bytes());
+	file.extend_from_slice(data);
+    }
+    file.extend_from_slice(&first_block.to_le_bytes());
+    file.extend_from_slice(b"BESS");
+    file
+}
+
+#[test]
+fn state_files_round_trip_with_bess_blocks() {
+    let mut gameboy = GameBoy::new(vram_rom(0), &Config::new(Model::Cgb)).unwrap();
+    gameboy.step_frame();
+    let file = gameboy.save_state_file();
+    assert!(file.ends_with(b"BESS"));
+    gameboy.step_frame();
+    let expected = snapshot(&gameboy);
+
+    let report = gameboy.load_state_file(&file).unwrap();
+    assert!(report.skipped.is_empty(), "{:?}", report.skipped);
+    gameboy.step_frame();
+    assert!(snapshot(&gameboy) == expected);
+
+    // Bare native states are still accepted
+    let state = gameboy.save_state();
+    assert!(gameboy.load_state_file(&state).is_ok());
+}
+
+#[test]
+fn loads_bess_files_from_other_emulators() {
+    let mut gameboy = GameBoy::new(vram_rom(0), &Config::new(Model::Dmg)).unwrap();
+    let report = gameboy.load_state_file(&foreign_bess(b"GDB ")).unwrap();
+
+    assert_eq!(report.emulator.as_deref(), Some("Other 1.0"));
+    assert!(report.restored.contains(&"work RAM"));
+    assert!(report.skipped.iter().any(|skipped| skipped.starts_with("XYZW")));
+
+    let registers = gameboy.registers();
+    assert_eq!((registers.pc, registers.sp, registers.a, registers.f), (0x0150, 0xDFF0, 0x12, 0xB0));
+    assert_eq!(gameboy.read_memory(0xC010), 0x42);
+    assert_eq!(gameboy.read_memory(0xFF80), 0x99);
+    assert_eq!(gameboy.read_memory(0xFF47), 0xE4);
+    assert_eq!(gameboy.read_memory(0xFF06), 0x77);
+    assert!(gameboy.ime());
+
+    // Unknown blocks are written back as they were
+    let file = gameboy.save_state_file();
+    assert!(file.windows(14).any(|window| window == b"XYZW\x06\0\0\0opaque"));
+    let mut other = GameBoy::new(vram_rom(0), &Config::new(Model::Dmg)).unwrap();
+    assert!(other.load_state_file(&file).unwrap().skipped.iter().any(|skipped| skipped.starts_with("XYZW")));
+
+    let mut cgb = GameBoy::new(vram_rom(0), &Config::new(Model::Cgb)).unwrap();
+    assert!(cgb.load_state_file(&foreign_bess(b"GDB ")).is_err());
+}
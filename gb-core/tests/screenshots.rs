@@ -0,0 +1,174 @@
+// PPU accuracy ROMs are checked against reference screenshots. Config::new
+// leaves color correction off, so the framebuffer already holds the palette
+// the references use: DMG greys from white to black, RGB555 scaled to 8bit.
+mod common;
+
+use std::{
+    env,
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use gb_core::{write_png, GameBoy, Model, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+#[derive(Copy, Clone)]
+enum Trigger {
+    Breakpoint,
+    Frames(u64),
+}
+
+fn run_until_trigger(gameboy: &mut GameBoy, trigger: Trigger) -> bool {
+    match trigger {
+	Trigger::Breakpoint => common::run_until(gameboy, 20, GameBoy::take_breakpoint),
+	Trigger::Frames(frames) => common::run_until(gameboy, 20, |gameboy| gameboy.frames() >= frames),
+    }
+}
+
+fn read_png(path: &Path) -> Result<(usize, usize, Vec<u32>), &'static str> {
+    let file = File::open(path).map_err(|_| "Couldn't open the PNG file")?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+    let mut reader = decoder.read_info().map_err(|_| "Couldn't read the PNG header")?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data).map_err(|_| "Couldn't read the PNG image")?;
+
+    let channels = info.color_type.samples();
+    let pixels = data[..info.buffer_size()]
+	.chunks(channels)
+	.map(|pixel| match pixel.len() {
+	    1 | 2 => (pixel[0] as u32) * 0x010101,
+	    _ => (pixel[0] as u32) << 16 | (pixel[1] as u32) << 8 | pixel[2] as u32,
+	})
+	.collect();
+
+    Ok((info.width as usize, info.height as usize, pixels))
+}
+
+// Runs the ROM and compares its screen with the reference, returns why they differ.
+// Mismatching screens are saved next to a diff where wrong pixels are red.
+fn compare_screenshot(rom: &Path, reference: &Path, model: Model, trigger: Trigger) -> Result<(), String> {
+    let mut gameboy = common::load(rom, model);
+    if !run_until_trigger(&mut gameboy, trigger) {
+	return Err(String::from("timed out before the trigger"));
+    }
+
+    let (width, height, expected) = read_png(reference).map_err(String::from)?;
+    if (width, height) != (SCREEN_WIDTH, SCREEN_HEIGHT) {
+	return Err(format!("the reference is {}x{}", width, height));
+    }
+
+    let actual = gameboy.framebuffer();
+    let mismatches = actual.iter().zip(expected.iter()).filter(|(actual, expected)| actual != expected).count();
+    if mismatches == 0 {
+	return Ok(());
+    }
+
+    // Matching pixels are dimmed to keep the picture recognizable
+    let diff: Vec<u32> = actual
+	.iter()
+	.zip(expected.iter())
+	.map(|(actual, expected)| if actual == expected { (actual >> 2) & 0x3F3F3F } else { 0xFF0000 })
+	.collect();
+
+    let name = format!("{}-{:?}", rom.file_stem().unwrap().to_string_lossy(), model);
+    let output = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("screenshots");
+    fs::create_dir_all(&output).unwrap();
+    write_png(&output.join(format!("{}-actual.png", name)), SCREEN_WIDTH, SCREEN_HEIGHT, actual)?;
+    write_png(&output.join(format!("{}-diff.png", name)), SCREEN_WIDTH, SCREEN_HEIGHT, &diff)?;
+
+    Err(format!("{} pixels differ, see {}", mismatches, output.join(format!("{}-diff.png", name)).display()))
+}
+
+fn check_screenshot(variable: &str, suite: &str, rom: &str, reference: &str, model: Model, trigger: Trigger) {
+    let rom = common::rom_path(variable, suite, rom);
+    let reference = common::rom_path(variable, suite, reference);
+
+    if let Err(msg) = compare_screenshot(&rom, &reference, model, trigger) {
+	panic!("{}: {}", rom.display(), msg);
+    }
+}
+
+#[test]
+#[ignore = "needs DMG_ACID2_ROMS"]
+fn dmg_acid2_dmg() {
+    check_screenshot("DMG_ACID2_ROMS", "dmg-acid2", "dmg-acid2.gb", "img/reference-dmg.png", Model::Dmg, Trigger::Breakpoint);
+}
+
+#[test]
+#[ignore = "needs DMG_ACID2_ROMS"]
+fn dmg_acid2_cgb() {
+    check_screenshot("DMG_ACID2_ROMS", "dmg-acid2", "dmg-acid2.gb", "img/reference-cgb.png", Model::Cgb, Trigger::Breakpoint);
+}
+
+#[test]
+#[ignore = "needs CGB_ACID2_ROMS"]
+fn cgb_acid2() {
+    check_screenshot("CGB_ACID2_ROMS", "cgb-acid2", "cgb-acid2.gbc", "img/reference.png", Model::Cgb, Trigger::Breakpoint);
+}
+
+// Every ROM of build/ppu that has a reference for the model, failures are reported together
+fn mealybug(expected: &str, model: Model) {
+    let dir = common::suite_dir("MEALYBUG_ROMS", "mealybug", "build/ppu");
+
+    let mut roms: Vec<PathBuf> = fs::read_dir(&dir)
+	.unwrap()
+	.map(|entry| entry.unwrap().path())
+	.filter(|path| path.extension().is_some_and(|extension| extension == "gb"))
+	.collect();
+    roms.sort();
+
+    let mut failures = Vec::new();
+    for rom in roms.iter() {
+	let reference = dir.join("../../expected").join(expected).join(rom.file_stem().unwrap()).with_extension("png");
+	if !reference.is_file() {
+	    continue;
+	}
+
+	if let Err(msg) = compare_screenshot(rom, &reference, model, Trigger::Breakpoint) {
+	    failures.push(format!("{}: {}", rom.file_stem().unwrap().to_string_lossy(), msg));
+	}
+    }
+
+    assert!(failures.is_empty(), "{} of {} failed:\n{}", failures.len(), roms.len(), failures.join("\n"));
+}
+
+// Expected to fail: each ROM writes PPU registers during mode 3, and lines
+// are rendered at its end, so a write only shows from the next line on
+#[test]
+#[ignore = "needs MEALYBUG_ROMS"]
+#[should_panic(expected = "failed")]
+fn mealybug_dmg() {
+    mealybug("DMG-blob", Model::Dmg);
+}
+
+#[test]
+#[ignore = "needs MEALYBUG_ROMS"]
+#[should_panic(expected = "failed")]
+fn mealybug_cgb() {
+    mealybug("CPU CGB C", Model::Cgb);
+}
+
+// Any other ROM: SCREENSHOT_ROM and SCREENSHOT_REFERENCE, with SCREENSHOT_FRAMES
+// for ROMs without a breakpoint and SCREENSHOT_MODEL to change the DMG default
+#[test]
+#[ignore = "needs SCREENSHOT_ROM"]
+fn custom() {
+    let (rom, reference) = match (env::var_os("SCREENSHOT_ROM"), env::var_os("SCREENSHOT_REFERENCE")) {
+	(Some(rom), Some(reference)) => (PathBuf::from(rom), PathBuf::from(reference)),
+	_ => panic!("set SCREENSHOT_ROM and SCREENSHOT_REFERENCE"),
+    };
+
+    let trigger = match env::var("SCREENSHOT_FRAMES") {
+	Ok(frames) => Trigger::Frames(frames.parse().expect("SCREENSHOT_FRAMES isn't a number")),
+	Err(_) => Trigger::Breakpoint,
+    };
+    let model = match env::var("SCREENSHOT_MODEL") {
+	Ok(name) => Model::try_from(name.as_str()).unwrap(),
+	Err(_) => Model::Dmg,
+    };
+
+    if let Err(msg) = compare_screenshot(&rom, &reference, model, trigger) {
+	panic!("{}: {}", rom.display(), msg);
+    }
+}
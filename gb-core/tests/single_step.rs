@@ -0,0 +1,215 @@
+// SM83 single-step test vectors, one JSON file per opcode ("00.json" to "cb ff.json")
+// with 1000 cases each. Point SM83_TESTS at the directory holding them. Every case
+// runs one instruction on the CPU alone and the whole final state is compared,
+// along with the reads and writes it put on the bus.
+mod common;
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use gb_core::{Access, FlatCpu, RegisterState};
+use serde_json::Value;
+
+const FLAGS: [(u8, char); 4] = [(0x80, 'Z'), (0x40, 'N'), (0x20, 'H'), (0x10, 'C')];
+
+fn field(state: &Value, name: &str) -> u16 {
+    state[name].as_u64().unwrap_or_else(|| panic!("missing {}", name)) as u16
+}
+
+fn registers(state: &Value) -> RegisterState {
+    RegisterState {
+	a: field(state, "a") as u8,
+	f: field(state, "f") as u8,
+	b: field(state, "b") as u8,
+	c: field(state, "c") as u8,
+	d: field(state, "d") as u8,
+	e: field(state, "e") as u8,
+	h: field(state, "h") as u8,
+	l: field(state, "l") as u8,
+	sp: field(state, "sp"),
+	pc: field(state, "pc"),
+    }
+}
+
+fn ram(state: &Value) -> Vec<(u16, u8)> {
+    state["ram"]
+	.as_array()
+	.unwrap()
+	.iter()
+	.map(|entry| (entry[0].as_u64().unwrap() as u16, entry[1].as_u64().unwrap() as u8))
+	.collect()
+}
+
+// The "cycles" of a case, one per M-cycle as [address, value, "r-m"], "-wm" or
+// "---". Idle cycles have no access and may leave the value out.
+fn bus_accesses(cycles: &[Value]) -> Vec<(u16, u8, Access)> {
+    cycles
+	.iter()
+	.filter_map(|cycle| {
+	    let address = cycle[0].as_u64()? as u16;
+	    let value = cycle[1].as_u64()? as u8;
+	    let access = match cycle[2].as_str()? {
+		activity if activity.contains('w') => Access::Write,
+		activity if activity.contains('r') => Access::Read,
+		_ => return None,
+	    };
+	    Some((address, value, access))
+	})
+	.collect()
+}
+
+fn describe(access: Option<&(u16, u8, Access)>) -> String {
+    match access {
+	Some((address, value, Access::Read)) => format!("read {:#04x} from {:#06x}", value, address),
+	Some((address, value, Access::Write)) => format!("write {:#04x} to {:#06x}", value, address),
+	None => String::from("nothing"),
+    }
+}
+
+fn flags(f: u8) -> String {
+    FLAGS.iter().map(|(mask, name)| if f & mask != 0 { *name } else { '-' }).collect()
+}
+
+// Every difference between the expected final state and the CPU one
+fn run_case(case: &Value) -> Vec<String> {
+    let initial = &case["initial"];
+    let expected = &case["final"];
+    let mut cpu = FlatCpu::new();
+
+    cpu.set_registers(registers(initial));
+    cpu.set_ime(field(initial, "ime") != 0);
+    for (address, value) in ram(initial) {
+	cpu.write_memory(address, value);
+    }
+    if let Some(ie) = initial["ie"].as_u64() {
+	cpu.write_memory(0xFFFF, ie as u8);
+    }
+
+    let cycles = cpu.step();
+    let mut diffs = Vec::new();
+
+    let want = registers(expected);
+    let got = cpu.registers();
+    let pairs: [(&str, u16, u16); 9] = [
+	("A", want.a as u16, got.a as u16),
+	("B", want.b as u16, got.b as u16),
+	("C", want.c as u16, got.c as u16),
+	("D", want.d as u16, got.d as u16),
+	("E", want.e as u16, got.e as u16),
+	("H", want.h as u16, got.h as u16),
+	("L", want.l as u16, got.l as u16),
+	("SP", want.sp, got.sp),
+	("PC", want.pc, got.pc),
+    ];
+    for (name, want, got) in pairs {
+	if want != got {
+	    diffs.push(format!("{} expected {:#06x} got {:#06x}", name, want, got));
+	}
+    }
+    if want.f != got.f {
+	diffs.push(format!("F expected {} got {}", flags(want.f), flags(got.f)));
+    }
+
+    if let Some(ime) = expected["ime"].as_u64() {
+	if (ime != 0) != cpu.ime() {
+	    diffs.push(format!("IME expected {} got {}", ime, cpu.ime() as u8));
+	}
+    }
+    if let Some(ei) = expected["ei"].as_u64() {
+	if (ei != 0) != cpu.ime_scheduled() {
+	    diffs.push(format!("EI expected {} got {}", ei, cpu.ime_scheduled() as u8));
+	}
+    }
+
+    // Bytes only listed in the initial state must be left untouched
+    let mut memory: BTreeMap<u16, u8> = ram(initial).into_iter().collect();
+    memory.extend(ram(expected));
+    for (address, want) in memory {
+	let got = cpu.read_memory(address);
+	if want != got {
+	    diffs.push(format!("[{:#06x}] expected {:#04x} got {:#04x}", address, want, got));
+	}
+    }
+
+    if let Some(bus) = case["cycles"].as_array() {
+	if bus.len() as u32 * 4 != cycles {
+	    diffs.push(format!("expected {} T-cycles got {}", bus.len() * 4, cycles));
+	}
+
+	let want = bus_accesses(bus);
+	let got = cpu.take_accesses();
+	if let Some(index) = (0..want.len().max(got.len())).find(|index| want.get(*index) != got.get(*index)) {
+	    diffs.push(format!("bus access {} expected {} got {}", index, describe(want.get(index)), describe(got.get(index))));
+	}
+    }
+
+    diffs
+}
+
+// Returns how many cases failed and a description of the first one
+fn run_file(path: &Path) -> (usize, usize, Option<String>) {
+    let cases: Value = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+    let cases = cases.as_array().unwrap();
+
+    let mut failed = 0;
+    let mut first = None;
+    for case in cases {
+	let diffs = run_case(case);
+	if !diffs.is_empty() {
+	    failed += 1;
+	    first.get_or_insert_with(|| format!("{}: {}", case["name"].as_str().unwrap_or("?"), diffs.join(", ")));
+	}
+    }
+
+    (failed, cases.len(), first)
+}
+
+#[test]
+#[ignore = "needs SM83_TESTS"]
+fn single_step() {
+    let dir = common::suite_dir("SM83_TESTS", "sm83", "v1");
+
+    let mut files: Vec<_> = fs::read_dir(&dir)
+	.unwrap()
+	.map(|entry| entry.unwrap().path())
+	.filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+	.collect();
+    files.sort();
+
+    let mut failures = Vec::new();
+    for path in files.iter() {
+	let opcode = path.file_stem().unwrap().to_string_lossy().to_string();
+	let (failed, total, first) = run_file(path);
+
+	if let Some(first) = first {
+	    failures.push(format!("{}: {} of {} failed, first {}", opcode, failed, total, first));
+	}
+    }
+
+    assert!(failures.is_empty(), "{} of {} opcodes failed:\n{}", failures.len(), files.len(), failures.join("\n"));
+}
+
+// A case written here, so the comparison itself is checked without the vectors
+fn ld_hl_a_case(write_address: u16) -> Value {
+    let state = |pc: u16, ram: Value| {
+	serde_json::json!({
+	    "a": 0x42, "b": 0, "c": 0, "d": 0, "e": 0, "f": 0, "h": 0xD0, "l": 0x00,
+	    "pc": pc, "sp": 0xFFFE, "ime": 0, "ram": ram,
+	})
+    };
+
+    serde_json::json!({
+	"name": "77 0000",
+	"initial": state(0xC000, serde_json::json!([[0xC000, 0x77], [0xD000, 0x00]])),
+	"final": state(0xC001, serde_json::json!([[0xC000, 0x77], [0xD000, 0x42]])),
+	"cycles": [[0xC000, 0x77, "r-m"], [write_address, 0x42, "-wm"]],
+    })
+}
+
+#[test]
+fn bus_activity_is_compared() {
+    assert_eq!(run_case(&ld_hl_a_case(0xD000)), Vec::<String>::new());
+    assert_eq!(
+	run_case(&ld_hl_a_case(0xD001)),
+	vec![String::from("bus access 1 expected write 0x42 to 0xd001 got write 0x42 to 0xd000")]
+    );
+}
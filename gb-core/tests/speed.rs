@@ -0,0 +1,58 @@
+// Speed controls: which frames run, how long they last and the audio played meanwhile
+use gb_core::{Speed, SpeedCommand, FRAME_DURATION};
+
+// A frame of stereo samples, the left channel counting up and the right down
+fn frame_samples() -> Vec<f32> {
+    (0..800).flat_map(|index| [index as f32, -(index as f32)]).collect()
+}
+
+#[test]
+fn pause_lets_only_frame_advances_through() {
+    let mut speed = Speed::new();
+    assert!(speed.next_frame());
+
+    speed.command(SpeedCommand::TogglePause);
+    assert!(!speed.next_frame());
+    assert!(speed.audio(&frame_samples()).is_empty());
+
+    speed.command(SpeedCommand::FrameAdvance);
+    assert!(speed.next_frame());
+    assert!(!speed.next_frame());
+    assert!(speed.is_paused());
+
+    speed.command(SpeedCommand::TogglePause);
+    assert!(speed.next_frame() && speed.next_frame());
+}
+
+#[test]
+fn fast_forward_and_slow_motion_scale_frames_and_pitch() {
+    let mut speed = Speed::new();
+    assert_eq!(speed.frame_duration(), FRAME_DURATION);
+    assert_eq!(speed.audio(&frame_samples()), frame_samples());
+
+    speed.command(SpeedCommand::HoldFastForward(true));
+    assert_eq!(speed.multiplier(), Some(4.0));
+    assert_eq!(speed.frame_duration(), FRAME_DURATION / 4);
+    let faster = speed.audio(&frame_samples());
+    assert_eq!(faster.len(), 400);
+    assert_eq!(&faster[..4], &[0.0, 0.0, 4.0, -4.0]);
+
+    speed.command(SpeedCommand::SetFastForward(None));
+    assert_eq!(speed.frame_duration().as_nanos(), 0);
+    assert!(speed.audio(&frame_samples()).is_empty());
+
+    // The toggle keeps going after the key is released
+    speed.command(SpeedCommand::SetFastForward(Some(2.0)));
+    speed.mute_fast_forward = true;
+    speed.command(SpeedCommand::ToggleFastForward);
+    speed.command(SpeedCommand::HoldFastForward(false));
+    assert_eq!(speed.multiplier(), Some(2.0));
+    assert!(speed.audio(&frame_samples()).is_empty());
+
+    speed.command(SpeedCommand::ToggleFastForward);
+    speed.command(SpeedCommand::SetSpeed(0.5));
+    assert_eq!(speed.frame_duration(), FRAME_DURATION * 2);
+    let slower = speed.audio(&frame_samples());
+    assert_eq!(slower.len(), 3200);
+    assert_eq!(&slower[..6], &[0.0, 0.0, 0.0, 0.0, 1.0, -1.0]);
+}
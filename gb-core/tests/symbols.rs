@@ -0,0 +1,115 @@
+// Symbol files as the assemblers write them, and how typed locations resolve against them
+use gb_core::{parse_hex, parse_location, Symbols};
+
+const RGBDS_SYM: &str = "\
+; File generated by rgblink
+00:0150 Main
+00:0150 Start
+00:0158 Main.loop
+01:4000 Add
+01:4010 Fade
+00:c000 wCounter
+00:ff80 hVBlankFlag
+";
+
+const WLA_SYM: &str = "\
+; this file was created with wlalink
+[labels]
+0000:0150 Main
+0001:4000 cafe
+0000:c000 wCounter
+
+[definitions]
+00000010 _sizeof_Main
+";
+
+const RGBDS_MAP: &str = "\
+SUMMARY:
+\tROM0: 338 bytes used / 16046 free
+\tROMX: 32 bytes used / 16352 free in 1 bank
+
+ROM0 bank #0:
+\tSECTION: $0150-$0163 ($0014 bytes) [\"Main\"]
+\t         $0150 = Main
+\t         $0158 = Main.loop
+\tEMPTY: $0164-$3fff ($3e9c bytes)
+
+ROMX bank #2:
+\tSECTION: $4000-$401f ($0020 bytes) [\"Effects\"]
+\t         $4000 = Add
+\t         $4010 = Fade
+
+WRAM0 bank #0:
+\tSECTION: $c000-$c000 ($0001 byte) [\"Variables\"]
+\t         $c000 = wCounter
+";
+
+#[test]
+fn parses_rgbds_sym() {
+    let symbols = Symbols::parse_sym(RGBDS_SYM).unwrap();
+    assert_eq!(symbols.len(), 7);
+
+    // The first label at an address names it
+    assert_eq!(symbols.label(0, 0x0150), Some("Main"));
+    assert_eq!(symbols.address_of("Start"), Some((0, 0x0150)));
+    assert_eq!(symbols.address_of("Fade"), Some((1, 0x4010)));
+    assert_eq!(symbols.label(1, 0x4010), Some("Fade"));
+    assert_eq!(symbols.label(0, 0x4010), None);
+
+    assert_eq!(symbols.describe(0, 0x015A).as_deref(), Some("Main.loop+$2"));
+    assert_eq!(symbols.describe(0, 0xFF81).as_deref(), Some("hVBlankFlag+$1"));
+    // Labels only describe addresses in their own memory area
+    assert_eq!(symbols.describe(0, 0xD000).as_deref(), Some("wCounter+$1000"));
+    assert_eq!(symbols.describe(0, 0x8000), None);
+    assert_eq!(symbols.describe(0, 0x0100), None);
+}
+
+#[test]
+fn parses_wla_labels_section_only() {
+    let symbols = Symbols::parse_sym(WLA_SYM).unwrap();
+    assert_eq!(symbols.len(), 3);
+    assert_eq!(symbols.address_of("cafe"), Some((1, 0x4000)));
+    assert_eq!(symbols.address_of("_sizeof_Main"), None);
+}
+
+#[test]
+fn parses_rgbds_map() {
+    let symbols = Symbols::parse_map(RGBDS_MAP).unwrap();
+    assert_eq!(symbols.len(), 5);
+    assert_eq!(symbols.address_of("Main.loop"), Some((0, 0x0158)));
+    assert_eq!(symbols.address_of("Fade"), Some((2, 0x4010)));
+    assert_eq!(symbols.label(0, 0xC000), Some("wCounter"));
+}
+
+#[test]
+fn rejects_malformed_files() {
+    assert!(Symbols::parse_sym("0150 Main").is_err());
+    assert!(Symbols::parse_sym("00:zz50 Main").is_err());
+    assert!(Symbols::parse_sym("00:0150").is_err());
+    assert!(Symbols::parse_map("ROMX bank #x:").is_err());
+}
+
+#[test]
+fn labels_take_precedence_over_hex() {
+    let symbols = Symbols::parse_sym(RGBDS_SYM).unwrap();
+    let symbols = Some(&symbols);
+
+    // `Add` and `Fade` are valid hex too
+    assert_eq!(parse_location(symbols, "Add"), Ok((Some(1), 0x4000)));
+    assert_eq!(parse_location(symbols, "Fade"), Ok((Some(1), 0x4010)));
+    assert_eq!(parse_location(None, "Fade"), Ok((None, 0xFADE)));
+
+    assert_eq!(parse_location(symbols, "$0150"), Ok((None, 0x0150)));
+    assert_eq!(parse_location(symbols, "0x150"), Ok((None, 0x0150)));
+    assert_eq!(parse_location(symbols, "02:4000"), Ok((Some(2), 0x4000)));
+    assert!(parse_location(symbols, "Missing").is_err());
+}
+
+#[test]
+fn hex_takes_every_prefix() {
+    for text in ["1ff", "$1FF", "0x1ff", "0X1FF"] {
+	assert_eq!(parse_hex(text), Ok(0x1FF), "{}", text);
+    }
+    assert!(parse_hex("10000").is_err());
+    assert!(parse_hex("g").is_err());
+}
@@ -0,0 +1,70 @@
+use gb_core::{disassemble, GameBoy};
+
+pub const RETURN_OPCODES: [u8; 6] = [0xC9, 0xD9, 0xC0, 0xC8, 0xD0, 0xD8];
+const CALL_OPCODES: [u8; 5] = [0xCD, 0xC4, 0xCC, 0xD4, 0xDC];
+const INTERRUPT_VECTORS: [u16; 5] = [0x40, 0x48, 0x50, 0x58, 0x60];
+
+// A CALL, RST or interrupt that hasn't returned yet
+#[derive(Clone)]
+pub struct Frame {
+    pub call_site: u16,
+    pub return_address: u16,
+    pub target: u16,
+}
+
+impl Frame {
+    pub fn is_interrupt(&self) -> bool {
+	self.call_site == self.return_address
+    }
+}
+
+// Follows calls and returns around GameBoy::step, for the debuggers' backtraces
+#[derive(Clone)]
+pub struct CallStack {
+    frames: Vec<Frame>,
+}
+
+impl CallStack {
+    pub fn new() -> Self {
+	CallStack { frames: Vec::new() }
+    }
+
+    // Innermost call last
+    pub fn frames(&self) -> &[Frame] {
+	&self.frames
+    }
+
+    // Executes one instruction, or one interrupt dispatch, and returns its opcode
+    pub fn step(&mut self, gameboy: &mut GameBoy) -> u8 {
+	let before = gameboy.registers();
+	let opcode = gameboy.read_memory(before.pc);
+	let next = next_address(gameboy, before.pc);
+
+	gameboy.step();
+	let after = gameboy.registers();
+
+	if after.sp == before.sp.wrapping_sub(2) && after.pc != next {
+	    if is_call(opcode) {
+		self.frames.push(Frame { call_site: before.pc, return_address: next, target: after.pc });
+	    } else if INTERRUPT_VECTORS.contains(&after.pc) {
+		self.frames.push(Frame { call_site: before.pc, return_address: before.pc, target: after.pc });
+	    }
+	} else if RETURN_OPCODES.contains(&opcode) && after.sp == before.sp.wrapping_add(2) {
+	    if let Some(index) = self.frames.iter().rposition(|frame| frame.return_address == after.pc) {
+		self.frames.truncate(index);
+	    }
+	}
+
+	opcode
+    }
+}
+
+// CALL and RST, the instructions stepped over as a whole
+pub fn is_call(opcode: u8) -> bool {
+    CALL_OPCODES.contains(&opcode) || opcode & 0xC7 == 0xC7
+}
+
+// Address of the instruction following the one at `address`
+pub fn next_address(gameboy: &GameBoy, address: u16) -> u16 {
+    address.wrapping_add(disassemble(address, |address| gameboy.read_memory(address)).length())
+}
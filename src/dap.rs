@@ -0,0 +1,577 @@
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+};
+
+use gb_core::{disassemble, parse_hex, parse_location, Config, GameBoy, Model, Symbols};
+use serde_json::{json, Value};
+
+use crate::call_stack::{self, CallStack, RETURN_OPCODES};
+
+const THREAD_ID: u64 = 1;
+const REGISTERS_REFERENCE: u64 = 1;
+const FLAGS_REFERENCE: u64 = 2;
+// Instructions run between two looks at the incoming requests while running
+const RUN_BATCH: u32 = 4096;
+// Instruction offsets are clamped to this, further ones would go around the address space
+const ADDRESS_SPACE: u64 = 0x10000;
+
+enum Run {
+    Continue,
+    StepOver { next: u16, sp: u16 },
+    StepOut { sp: u16 },
+}
+
+enum Breakpoint {
+    Address(u16),
+    Banked(u16, u16),
+}
+
+struct Target {
+    gameboy: GameBoy,
+    symbols: Option<Symbols>,
+    call_stack: CallStack,
+}
+
+struct Server {
+    output: io::Stdout,
+    seq: u64,
+    target: Option<Target>,
+    // setFunctionBreakpoints and setInstructionBreakpoints each replace their own list
+    function_breakpoints: Vec<Breakpoint>,
+    instruction_breakpoints: Vec<Breakpoint>,
+    stop_on_entry: bool,
+    running: Option<Run>,
+}
+
+// Debug Adapter Protocol on stdin and stdout, so editors can launch a ROM and debug it
+pub fn run() -> Result<(), &'static str> {
+    let requests = spawn_reader();
+    let mut server = Server {
+	output: io::stdout(),
+	seq: 1,
+	target: None,
+	function_breakpoints: Vec::new(),
+	instruction_breakpoints: Vec::new(),
+	stop_on_entry: true,
+	running: None,
+    };
+
+    loop {
+	let request = if server.running.is_some() {
+	    match requests.try_recv() {
+		Ok(request) => request,
+		Err(TryRecvError::Empty) => {
+		    server.run_batch()?;
+		    continue;
+		}
+		Err(TryRecvError::Disconnected) => return Ok(()),
+	    }
+	} else {
+	    match requests.recv() {
+		Ok(request) => request,
+		Err(_) => return Ok(()),
+	    }
+	};
+
+	if !server.handle(&request)? {
+	    return Ok(());
+	}
+    }
+}
+
+// Messages are JSON bodies after a Content-Length header, read on their own thread
+// so a pause request can arrive while the emulator runs
+fn spawn_reader() -> Receiver<Value> {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+	let mut input = BufReader::new(io::stdin());
+	loop {
+	    let mut length = None;
+	    loop {
+		let mut line = String::new();
+		match input.read_line(&mut line) {
+		    Ok(0) | Err(_) => return,
+		    Ok(_) => {}
+		}
+		let line = line.trim();
+		if line.is_empty() {
+		    break;
+		}
+		if let Some(value) = line.strip_prefix("Content-Length:") {
+		    length = value.trim().parse::<usize>().ok();
+		}
+	    }
+
+	    let mut body = vec![0; length.unwrap_or(0)];
+	    if input.read_exact(&mut body).is_err() {
+		return;
+	    }
+	    if let Ok(message) = serde_json::from_slice(&body) {
+		if sender.send(message).is_err() {
+		    return;
+		}
+	    }
+	}
+    });
+
+    receiver
+}
+
+impl Server {
+    // Returns false once the client disconnected
+    fn handle(&mut self, request: &Value) -> Result<bool, &'static str> {
+	let command = request["command"].as_str().unwrap_or("");
+	let arguments = &request["arguments"];
+
+	let result = match command {
+	    "initialize" => Ok(json!({
+		"supportsConfigurationDoneRequest": true,
+		"supportsFunctionBreakpoints": true,
+		"supportsInstructionBreakpoints": true,
+		"supportsReadMemoryRequest": true,
+		"supportsWriteMemoryRequest": true,
+		"supportsDisassembleRequest": true,
+		"supportsSetVariable": true,
+		"supportsEvaluateForHovers": true,
+	    })),
+	    "launch" => self.launch(arguments),
+	    "disconnect" => {
+		self.respond(request, Ok(Value::Null))?;
+		return Ok(false);
+	    }
+	    _ if self.target.is_none() => Err("Launch a ROM first"),
+	    "configurationDone" => {
+		self.respond(request, Ok(Value::Null))?;
+		if self.stop_on_entry {
+		    self.stopped("entry", None)?;
+		} else {
+		    self.running = Some(Run::Continue);
+		}
+		return Ok(true);
+	    }
+	    "setFunctionBreakpoints" => {
+		let (breakpoints, reply) = self.parse_breakpoints(arguments["breakpoints"].as_array(), "name");
+		self.function_breakpoints = breakpoints;
+		Ok(reply)
+	    }
+	    "setInstructionBreakpoints" => {
+		let (breakpoints, reply) = self.parse_breakpoints(arguments["breakpoints"].as_array(), "instructionReference");
+		self.instruction_breakpoints = breakpoints;
+		Ok(reply)
+	    }
+	    // There is no line information to map source lines to addresses
+	    "setBreakpoints" => {
+		let count = arguments["breakpoints"].as_array().map_or(0, Vec::len);
+		let unverified = json!({ "verified": false, "message": "Use function breakpoints with a label or an address" });
+		let breakpoints = vec![unverified; count];
+		Ok(json!({ "breakpoints": breakpoints }))
+	    }
+	    "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "SM83" }] })),
+	    "stackTrace" => Ok(self.stack_trace()),
+	    "scopes" => Ok(json!({ "scopes": [
+		{ "name": "Registers", "variablesReference": REGISTERS_REFERENCE, "expensive": false },
+		{ "name": "Flags", "variablesReference": FLAGS_REFERENCE, "expensive": false },
+	    ] })),
+	    "variables" => Ok(self.variables(arguments["variablesReference"].as_u64().unwrap_or(0))),
+	    "setVariable" => self.set_variable(arguments),
+	    "evaluate" => self.evaluate(arguments["expression"].as_str().unwrap_or("")),
+	    "readMemory" => self.read_memory(arguments),
+	    "writeMemory" => self.write_memory(arguments),
+	    "disassemble" => self.disassemble(arguments),
+	    "continue" => {
+		self.running = Some(Run::Continue);
+		Ok(json!({ "allThreadsContinued": true }))
+	    }
+	    "next" => {
+		let target = self.target.as_mut().unwrap();
+		let registers = target.gameboy.registers();
+		if call_stack::is_call(target.gameboy.read_memory(registers.pc)) {
+		    let next = call_stack::next_address(&target.gameboy, registers.pc);
+		    self.running = Some(Run::StepOver { next, sp: registers.sp });
+		    Ok(Value::Null)
+		} else {
+		    return self.step_in(request);
+		}
+	    }
+	    "stepIn" => return self.step_in(request),
+	    "stepOut" => {
+		let sp = self.target.as_ref().unwrap().gameboy.registers().sp;
+		self.running = Some(Run::StepOut { sp });
+		Ok(Value::Null)
+	    }
+	    "pause" => {
+		self.respond(request, Ok(Value::Null))?;
+		if self.running.take().is_some() {
+		    self.stopped("pause", None)?;
+		}
+		return Ok(true);
+	    }
+	    _ => Err("Unsupported request"),
+	};
+
+	self.respond(request, result)?;
+	Ok(true)
+    }
+
+    fn launch(&mut self, arguments: &Value) -> Result<Value, &'static str> {
+	let program = arguments["program"].as_str().ok_or("The launch configuration needs a program")?;
+	let model = match arguments["model"].as_str() {
+	    Some(model) => Model::try_from(model)?,
+	    None => Model::Dmg,
+	};
+
+	let gameboy = GameBoy::from_file(program, &Config::new(model))?;
+	let symbols = Symbols::for_rom(program)?;
+	self.stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or(true);
+	self.target = Some(Target {
+	    gameboy,
+	    symbols,
+	    call_stack: CallStack::new(),
+	});
+
+	self.event("initialized", Value::Null)?;
+	Ok(Value::Null)
+    }
+
+    fn step_in(&mut self, request: &Value) -> Result<bool, &'static str> {
+	self.respond(request, Ok(Value::Null))?;
+	let target = self.target.as_mut().unwrap();
+	target.call_stack.step(&mut target.gameboy);
+	match target.gameboy.invalid_opcode() {
+	    Some(_) => self.stopped("exception", Some("Invalid opcode, the CPU hung"))?,
+	    None => self.stopped("step", None)?,
+	}
+	Ok(true)
+    }
+
+    // Breakpoints are given by label, address or bank:address
+    fn parse_breakpoints(&self, requested: Option<&Vec<Value>>, field: &str) -> (Vec<Breakpoint>, Value) {
+	let mut breakpoints = Vec::new();
+	let mut replies = Vec::new();
+
+	for breakpoint in requested.into_iter().flatten() {
+	    let text = breakpoint[field].as_str().unwrap_or("");
+	    let offset = breakpoint["offset"].as_i64().unwrap_or(0) as i16;
+
+	    match self.parse_location(text) {
+		Some((bank, address)) => {
+		    let address = address.wrapping_add_signed(offset);
+		    breakpoints.push(match bank {
+			Some(bank) => Breakpoint::Banked(bank, address),
+			None => Breakpoint::Address(address),
+		    });
+		    replies.push(json!({ "verified": true, "instructionReference": format!("0x{:04X}", address) }));
+		}
+		None => replies.push(json!({ "verified": false, "message": "Not an address nor a known label" })),
+	    }
+	}
+
+	(breakpoints, json!({ "breakpoints": replies }))
+    }
+
+    fn parse_location(&self, text: &str) -> Option<(Option<u16>, u16)> {
+	let symbols = self.target.as_ref().and_then(|target| target.symbols.as_ref());
+	parse_location(symbols, text).ok()
+    }
+
+    fn run_batch(&mut self) -> Result<(), &'static str> {
+	let target = self.target.as_mut().unwrap();
+
+	for _ in 0..RUN_BATCH {
+	    let opcode = target.call_stack.step(&mut target.gameboy);
+	    let registers = target.gameboy.registers();
+
+	    if target.gameboy.invalid_opcode().is_some() {
+		self.running = None;
+		return self.stopped("exception", Some("Invalid opcode, the CPU hung"));
+	    }
+
+	    let done = match self.running {
+		Some(Run::StepOver { next, sp }) => registers.pc == next && registers.sp >= sp,
+		Some(Run::StepOut { sp }) => RETURN_OPCODES.contains(&opcode) && registers.sp > sp,
+		_ => false,
+	    };
+	    if done {
+		self.running = None;
+		return self.stopped("step", None);
+	    }
+
+	    let bank = target.gameboy.bank(registers.pc);
+	    let hit = self.function_breakpoints.iter().chain(&self.instruction_breakpoints).any(|breakpoint| match *breakpoint {
+		Breakpoint::Address(address) => address == registers.pc,
+		Breakpoint::Banked(breakpoint_bank, address) => address == registers.pc && breakpoint_bank == bank,
+	    });
+	    if hit {
+		self.running = None;
+		return self.stopped("breakpoint", None);
+	    }
+	}
+
+	Ok(())
+    }
+
+    fn stack_trace(&self) -> Value {
+	let target = self.target.as_ref().unwrap();
+	let pc = target.gameboy.registers().pc;
+
+	let mut frames = vec![self.frame(0, pc)];
+	for (depth, frame) in target.call_stack.frames().iter().rev().enumerate() {
+	    frames.push(self.frame(depth as u64 + 1, frame.call_site));
+	}
+
+	json!({ "stackFrames": frames, "totalFrames": frames.len() })
+    }
+
+    fn frame(&self, id: u64, address: u16) -> Value {
+	json!({
+	    "id": id,
+	    "name": self.describe(address),
+	    "line": 0,
+	    "column": 0,
+	    "instructionPointerReference": format!("0x{:04X}", address),
+	})
+    }
+
+    fn describe(&self, address: u16) -> String {
+	let target = self.target.as_ref().unwrap();
+	let bank = target.gameboy.bank(address);
+	match target.symbols.as_ref().and_then(|symbols| symbols.describe(bank, address)) {
+	    Some(label) => label,
+	    None => format!("{:02X}:{:04X}", bank, address),
+	}
+    }
+
+    fn variables(&self, reference: u64) -> Value {
+	let gameboy = &self.target.as_ref().unwrap().gameboy;
+	let registers = gameboy.registers();
+
+	let variables: Vec<Value> = match reference {
+	    REGISTERS_REFERENCE => {
+		let bytes = [("a", registers.a), ("f", registers.f), ("b", registers.b), ("c", registers.c), ("d", registers.d), ("e", registers.e), ("h", registers.h), ("l", registers.l)];
+		let words = [("af", word(registers.a, registers.f)), ("bc", word(registers.b, registers.c)), ("de", word(registers.d, registers.e)), ("hl", word(registers.h, registers.l)), ("sp", registers.sp), ("pc", registers.pc)];
+
+		bytes
+		    .iter()
+		    .map(|(name, value)| json!({ "name": name, "value": format!("${:02X}", value), "variablesReference": 0 }))
+		    .chain(words.iter().map(|(name, value)| {
+			json!({ "name": name, "value": format!("${:04X}", value), "variablesReference": 0, "memoryReference": format!("0x{:04X}", value) })
+		    }))
+		    .collect()
+	    }
+	    FLAGS_REFERENCE => [(7, "z"), (6, "n"), (5, "h"), (4, "c")]
+		.iter()
+		.map(|(bit, name)| json!({ "name": name, "value": (registers.f >> bit & 1).to_string(), "variablesReference": 0 }))
+		.chain([json!({ "name": "ime", "value": (gameboy.ime() as u8).to_string(), "variablesReference": 0 })])
+		.collect(),
+	    _ => Vec::new(),
+	};
+
+	json!({ "variables": variables })
+    }
+
+    fn set_variable(&mut self, arguments: &Value) -> Result<Value, &'static str> {
+	let name = arguments["name"].as_str().unwrap_or("");
+	let value = parse_value(arguments["value"].as_str().unwrap_or("")).ok_or("Invalid value, use $hex or decimal")?;
+	let gameboy = &mut self.target.as_mut().unwrap().gameboy;
+	let mut registers = gameboy.registers();
+
+	let flag = |bit: u8| if value != 0 { registers.f | 1 << bit } else { registers.f & !(1 << bit) };
+	match (arguments["variablesReference"].as_u64(), name) {
+	    (Some(FLAGS_REFERENCE), "z") => registers.f = flag(7),
+	    (Some(FLAGS_REFERENCE), "n") => registers.f = flag(6),
+	    (Some(FLAGS_REFERENCE), "h") => registers.f = flag(5),
+	    (Some(FLAGS_REFERENCE), "c") => registers.f = flag(4),
+	    (Some(REGISTERS_REFERENCE), name) => registers.set(name, value)?,
+	    _ => return Err("This variable can't be changed"),
+	}
+
+	gameboy.set_registers(registers);
+	// Register pairs, SP and PC are the two letter names
+	let value = if name.len() == 2 { format!("${:04X}", value) } else { format!("${:02X}", value) };
+	Ok(json!({ "value": value }))
+    }
+
+    // Expressions are an address or a label, the result is the byte there
+    fn evaluate(&self, expression: &str) -> Result<Value, &'static str> {
+	let (_, address) = self.parse_location(expression.trim()).ok_or("Expected an address or a label")?;
+	let value = self.target.as_ref().unwrap().gameboy.read_memory(address);
+	Ok(json!({
+	    "result": format!("[${:04X}] = ${:02X}", address, value),
+	    "variablesReference": 0,
+	    "memoryReference": format!("0x{:04X}", address),
+	}))
+    }
+
+    fn read_memory(&self, arguments: &Value) -> Result<Value, &'static str> {
+	let address = self.memory_reference(arguments)?;
+	let count = arguments["count"].as_u64().unwrap_or(0).min(0x10000 - address as u64);
+	let gameboy = &self.target.as_ref().unwrap().gameboy;
+	let data: Vec<u8> = (0..count).map(|offset| gameboy.read_memory(address + offset as u16)).collect();
+
+	Ok(json!({ "address": format!("0x{:04X}", address), "data": encode_base64(&data) }))
+    }
+
+    fn write_memory(&mut self, arguments: &Value) -> Result<Value, &'static str> {
+	let address = self.memory_reference(arguments)?;
+	let data = decode_base64(arguments["data"].as_str().unwrap_or("")).ok_or("Invalid base64 data")?;
+	let gameboy = &mut self.target.as_mut().unwrap().gameboy;
+	for (offset, byte) in data.iter().enumerate() {
+	    gameboy.write_memory(address.wrapping_add(offset as u16), *byte);
+	}
+
+	Ok(json!({ "bytesWritten": data.len() }))
+    }
+
+    fn memory_reference(&self, arguments: &Value) -> Result<u16, &'static str> {
+	let reference = arguments["memoryReference"].as_str().ok_or("Missing memory reference")?;
+	let (_, address) = self.parse_location(reference).ok_or("Invalid memory reference")?;
+	Ok(address.wrapping_add_signed(arguments["offset"].as_i64().unwrap_or(0) as i16))
+    }
+
+    // A negative instruction offset is found by decoding from far enough back that
+    // the instructions line up with the reference again. Those that would come
+    // before address 0 are placeholders, so the reference stays at index -offset.
+    fn disassemble(&self, arguments: &Value) -> Result<Value, &'static str> {
+	let target = self.target.as_ref().unwrap();
+	let reference = self.memory_reference(arguments)?;
+	let offset = arguments["instructionOffset"].as_i64().unwrap_or(0);
+	let count = arguments["instructionCount"].as_u64().unwrap_or(0) as usize;
+	let read = |address: u16| target.gameboy.read_memory(address);
+
+	let mut instructions = Vec::new();
+	let mut address = reference;
+	if offset < 0 {
+	    let back = offset.unsigned_abs().min(ADDRESS_SPACE) as usize;
+	    let mut addresses = Vec::new();
+	    let mut current = (reference as usize).saturating_sub(back * 3);
+	    while current < reference as usize {
+		addresses.push(current as u16);
+		current += disassemble(current as u16, read).length() as usize;
+	    }
+
+	    let padding = back.saturating_sub(addresses.len()).min(count);
+	    instructions.extend((0..padding).map(|_| json!({ "address": "0x0000", "instruction": "invalid", "presentationHint": "invalid" })));
+	    address = addresses.get(addresses.len().saturating_sub(back)).copied().unwrap_or(reference);
+	} else {
+	    for _ in 0..offset.min(ADDRESS_SPACE as i64) {
+		address = address.wrapping_add(disassemble(address, read).length());
+	    }
+	}
+
+	while instructions.len() < count {
+	    let instruction = disassemble(address, read);
+	    let bank = target.gameboy.bank(address);
+	    let label = |target_address: u16| {
+		let symbols = target.symbols.as_ref()?;
+		symbols.label(target.gameboy.bank(target_address), target_address).map(String::from)
+	    };
+	    let bytes: Vec<String> = instruction.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+
+	    let mut entry = json!({
+		"address": format!("0x{:04X}", address),
+		"instructionBytes": bytes.join(" "),
+		"instruction": instruction.format(label),
+	    });
+	    if let Some(name) = target.symbols.as_ref().and_then(|symbols| symbols.label(bank, address)) {
+		entry["symbol"] = json!(name);
+	    }
+	    instructions.push(entry);
+	    address = address.wrapping_add(instruction.length());
+	}
+
+	Ok(json!({ "instructions": instructions }))
+    }
+
+    fn stopped(&mut self, reason: &str, text: Option<&str>) -> Result<(), &'static str> {
+	let mut body = json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true });
+	if let Some(text) = text {
+	    body["text"] = json!(text);
+	}
+	self.event("stopped", body)
+    }
+
+    fn respond(&mut self, request: &Value, result: Result<Value, &'static str>) -> Result<(), &'static str> {
+	let mut response = json!({
+	    "type": "response",
+	    "request_seq": request["seq"],
+	    "command": request["command"],
+	    "success": result.is_ok(),
+	});
+	match result {
+	    Ok(Value::Null) => {}
+	    Ok(body) => response["body"] = body,
+	    Err(message) => response["message"] = json!(message),
+	}
+	self.send(response)
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> Result<(), &'static str> {
+	let mut message = json!({ "type": "event", "event": event });
+	if !body.is_null() {
+	    message["body"] = body;
+	}
+	self.send(message)
+    }
+
+    fn send(&mut self, mut message: Value) -> Result<(), &'static str> {
+	message["seq"] = json!(self.seq);
+	self.seq += 1;
+
+	let body = message.to_string();
+	let mut output = self.output.lock();
+	match write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body).and_then(|_| output.flush()) {
+	    Ok(_) => Ok(()),
+	    Err(_) => Err("Couldn't write to the debug adapter client"),
+	}
+    }
+}
+
+fn word(high: u8, low: u8) -> u16 {
+    (high as u16) << 8 | low as u16
+}
+
+// Values typed in the editor, $hex or 0xhex, decimal otherwise
+fn parse_value(text: &str) -> Option<u16> {
+    let text = text.trim();
+    if text.starts_with('$') || text.starts_with("0x") {
+	parse_hex(text).ok()
+    } else {
+	text.parse().ok()
+    }
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn encode_base64(data: &[u8]) -> String {
+    let mut text = String::new();
+    for chunk in data.chunks(3) {
+	let bits = chunk.iter().enumerate().fold(0u32, |bits, (index, byte)| bits | (*byte as u32) << (16 - index * 8));
+	for index in 0..4 {
+	    if index <= chunk.len() {
+		text.push(BASE64[(bits >> (18 - index * 6) & 0x3F) as usize] as char);
+	    } else {
+		text.push('=');
+	    }
+	}
+    }
+    text
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut data = Vec::new();
+    let mut bits = 0u32;
+    let mut count = 0;
+    for character in text.bytes().filter(|character| *character != b'=') {
+	let value = BASE64.iter().position(|symbol| *symbol == character)? as u32;
+	bits = bits << 6 | value;
+	count += 6;
+	if count >= 8 {
+	    count -= 8;
+	    data.push((bits >> count) as u8);
+	}
+    }
+    Some(data)
+}
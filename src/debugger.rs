@@ -0,0 +1,577 @@
+use std::{
+    io::{self, BufRead, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use colored::Colorize;
+use gb_core::{disassemble, parse_hex, parse_location, Access, GameBoy, RegisterState, Symbols, WatchHit};
+
+use crate::call_stack::{self, CallStack, RETURN_OPCODES};
+
+const HELP: &str = "Commands:
+  s, step [n]                  run one or n instructions
+  n, next                      step over calls and RSTs
+  finish                       run until the current function returns
+  c, continue [frames]         run until a breakpoint, a watchpoint or the frame limit
+  rs, reverse-step [n]         go back one or n instructions
+  rc, reverse-continue         go back to the previous breakpoint or watchpoint hit
+  b, break <address>           break at an address, bank:address or label
+  break opcode <byte>          break before any instruction with this opcode
+  delete <n>                   remove breakpoint n
+  watch <address> [r|w|rw]     stop when the CPU reads or writes the address, rw by default
+  unwatch <address>            remove the watchpoints on the address
+  info                         list breakpoints and watchpoints
+  r, regs                      show registers and flags
+  x <address> [length]         hex dump of memory
+  dis [address] [count]        disassemble
+  bt                           call stack
+  set <register> <value>       a, f, b, c, d, e, h, l, af, bc, de, hl, sp or pc
+  write <address> <byte>...    write memory
+  q, quit                      exit
+Numbers are hexadecimal, counts are decimal. An empty line repeats the last command.
+Going backwards loads the nearest checkpoint and runs forward again to the target.";
+
+// Set by Ctrl+C, which stops execution instead of quitting
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// Steps between checkpoints at first, the interval doubles every time the
+// checkpoints are thinned out so that they always cover the whole session
+const CHECKPOINT_INTERVAL: u64 = 100_000;
+const MAX_CHECKPOINTS: usize = 256;
+
+enum Breakpoint {
+    Address(u16),
+    Banked(u16, u16),
+    Opcode(u8),
+}
+
+enum Stop {
+    Breakpoint(usize),
+    Watchpoint(WatchHit),
+    InvalidOpcode(u16, u8),
+    Interrupted,
+}
+
+// The machine after `position` steps, the debugger runs forward from here to go back
+struct Checkpoint {
+    position: u64,
+    state: Vec<u8>,
+    call_stack: CallStack,
+}
+
+struct Debugger<'a> {
+    gameboy: &'a mut GameBoy,
+    symbols: Option<&'a Symbols>,
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<(u16, Access)>,
+    call_stack: CallStack,
+    // Steps taken since the debugger started
+    position: u64,
+    checkpoints: Vec<Checkpoint>,
+    checkpoint_interval: u64,
+    // Positions of the changes made with set or write, in order
+    changes: Vec<u64>,
+}
+
+// Reads commands from stdin until quit or the end of input
+pub fn run(gameboy: &mut GameBoy, symbols: Option<&Symbols>) -> Result<(), &'static str> {
+    let mut debugger = Debugger {
+	gameboy,
+	symbols,
+	breakpoints: Vec::new(),
+	watchpoints: Vec::new(),
+	call_stack: CallStack::new(),
+	position: 0,
+	checkpoints: Vec::new(),
+	checkpoint_interval: CHECKPOINT_INTERVAL,
+	changes: Vec::new(),
+    };
+    debugger.add_checkpoint();
+
+    if ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::Relaxed)).is_err() {
+	return Err("Couldn't install the Ctrl+C handler");
+    }
+
+    println!("Type help for the list of commands, Ctrl+C stops a running program");
+    debugger.show_location();
+
+    let stdin = io::stdin();
+    let mut last_command = String::new();
+    loop {
+	print!("{} ", "(gb)".cyan().bold());
+	let _ = io::stdout().flush();
+
+	let mut line = String::new();
+	match stdin.lock().read_line(&mut line) {
+	    Ok(0) | Err(_) => return Ok(()),
+	    Ok(_) => {}
+	}
+
+	let line = match line.trim() {
+	    "" => last_command.clone(),
+	    line => line.to_string(),
+	};
+	let words: Vec<&str> = line.split_whitespace().collect();
+	if words.is_empty() {
+	    continue;
+	}
+	if matches!(words[0], "q" | "quit") {
+	    return Ok(());
+	}
+
+	if let Err(msg) = debugger.command(&words) {
+	    println!("{}", msg.red());
+	}
+	last_command = line;
+    }
+}
+
+impl Debugger<'_> {
+    fn command(&mut self, words: &[&str]) -> Result<(), &'static str> {
+	let argument = |index: usize| words.get(index).copied();
+
+	match words[0] {
+	    "help" | "h" => println!("{}", HELP),
+	    "s" | "step" => {
+		let count = match argument(1) {
+		    Some(count) => parse_count(count)?,
+		    None => 1,
+		};
+		let mut stop = None;
+		for _ in 0..count {
+		    stop = self.step_instruction();
+		    if stop.is_some() {
+			break;
+		    }
+		}
+		self.report(stop);
+	    }
+	    "n" | "next" => {
+		let registers = self.gameboy.registers();
+		let opcode = self.gameboy.read_memory(registers.pc);
+		let stop = if call_stack::is_call(opcode) {
+		    let next = call_stack::next_address(self.gameboy, registers.pc);
+		    let start_sp = registers.sp;
+		    self.run_until(None, |registers, _| registers.pc == next && registers.sp >= start_sp)
+		} else {
+		    self.step_instruction()
+		};
+		self.report(stop);
+	    }
+	    "finish" => {
+		let start_sp = self.gameboy.registers().sp;
+		let stop = self.run_until(None, |registers, opcode| RETURN_OPCODES.contains(&opcode) && registers.sp > start_sp);
+		self.report(stop);
+	    }
+	    "c" | "continue" => {
+		let frames = match argument(1) {
+		    Some(frames) => Some(self.gameboy.frames() + parse_count(frames)?),
+		    None => None,
+		};
+		let stop = self.run_until(frames, |_, _| false);
+		self.report(stop);
+	    }
+	    "rs" | "reverse-step" => {
+		let count = match argument(1) {
+		    Some(count) => parse_count(count)?,
+		    None => 1,
+		};
+		if self.position == 0 {
+		    return Err("Already at the start of the recorded history");
+		}
+		self.seek(self.position.saturating_sub(count));
+		self.show_location();
+	    }
+	    "rc" | "reverse-continue" => {
+		let stop = self.reverse_continue();
+		if stop.is_none() && self.position == 0 {
+		    println!("{}", "Reached the start of the recorded history".yellow().bold());
+		}
+		self.report(stop);
+	    }
+	    "b" | "break" => match (argument(1), argument(2)) {
+		(Some("opcode"), Some(opcode)) => {
+		    let opcode = parse_hex(opcode)?;
+		    if opcode > 0xFF {
+			return Err("An opcode is a single byte");
+		    }
+		    self.add_breakpoint(Breakpoint::Opcode(opcode as u8));
+		}
+		(Some(location), None) => {
+		    let breakpoint = match self.parse_location(location)? {
+			(Some(bank), address) => Breakpoint::Banked(bank, address),
+			(None, address) => Breakpoint::Address(address),
+		    };
+		    self.add_breakpoint(breakpoint);
+		}
+		_ => return Err("Usage: break <address> or break opcode <byte>"),
+	    },
+	    "delete" => {
+		let number = parse_count(argument(1).ok_or("Usage: delete <n>")?)? as usize;
+		if number == 0 || number > self.breakpoints.len() {
+		    return Err("No such breakpoint");
+		}
+		self.breakpoints.remove(number - 1);
+	    }
+	    "watch" => {
+		let (_, address) = self.parse_location(argument(1).ok_or("Usage: watch <address> [r|w|rw]")?)?;
+		let accesses: &[Access] = match argument(2).unwrap_or("rw") {
+		    "r" => &[Access::Read],
+		    "w" => &[Access::Write],
+		    "rw" => &[Access::Read, Access::Write],
+		    _ => return Err("Usage: watch <address> [r|w|rw]"),
+		};
+		for access in accesses {
+		    self.gameboy.watch(address, *access);
+		    if !self.watchpoints.contains(&(address, *access)) {
+			self.watchpoints.push((address, *access));
+		    }
+		}
+	    }
+	    "unwatch" => {
+		let (_, address) = self.parse_location(argument(1).ok_or("Usage: unwatch <address>")?)?;
+		if !self.gameboy.unwatch(address) {
+		    return Err("No watchpoint on this address");
+		}
+		self.watchpoints.retain(|(watched, _)| *watched != address);
+	    }
+	    "info" => self.show_points(),
+	    "r" | "regs" => self.show_registers(),
+	    "x" => {
+		let (_, address) = self.parse_location(argument(1).ok_or("Usage: x <address> [length]")?)?;
+		let length = match argument(2) {
+		    Some(length) => parse_count(length)?,
+		    None => 64,
+		};
+		self.hex_dump(address, length);
+	    }
+	    "dis" => {
+		let address = match argument(1) {
+		    Some(location) => self.parse_location(location)?.1,
+		    None => self.gameboy.registers().pc,
+		};
+		let count = match argument(2) {
+		    Some(count) => parse_count(count)?,
+		    None => 10,
+		};
+		let mut address = address;
+		for _ in 0..count {
+		    address = address.wrapping_add(self.show_instruction(address));
+		}
+	    }
+	    "bt" => self.show_call_stack(),
+	    "set" => match (argument(1), argument(2)) {
+		(Some(register), Some(value)) => {
+		    let mut registers = self.gameboy.registers();
+		    registers.set(register, parse_hex(value)?)?;
+		    self.gameboy.set_registers(registers);
+		    self.record_change();
+		    self.show_registers();
+		}
+		_ => return Err("Usage: set <register> <value>"),
+	    },
+	    "write" => {
+		if words.len() < 3 {
+		    return Err("Usage: write <address> <byte>...");
+		}
+		let (_, address) = self.parse_location(words[1])?;
+		for (offset, byte) in words[2..].iter().enumerate() {
+		    let byte = parse_hex(byte)?;
+		    if byte > 0xFF {
+			return Err("Memory is written one byte at a time");
+		    }
+		    self.gameboy.write_memory(address.wrapping_add(offset as u16), byte as u8);
+		}
+		self.record_change();
+	    }
+	    _ => return Err("Unknown command, type help for the list"),
+	}
+
+	Ok(())
+    }
+
+    // Executes one instruction, or one interrupt dispatch, keeping track of the calls
+    fn step_instruction(&mut self) -> Option<Stop> {
+	self.call_stack.step(self.gameboy);
+	self.position += 1;
+	if self.position.is_multiple_of(self.checkpoint_interval) {
+	    self.add_checkpoint();
+	}
+
+	if let Some((address, opcode)) = self.gameboy.invalid_opcode() {
+	    return Some(Stop::InvalidOpcode(address, opcode));
+	}
+	self.gameboy.take_watch_hit().map(Stop::Watchpoint)
+    }
+
+    // Steps until `done` holds for the registers after an instruction and its opcode,
+    // or until a breakpoint, a watchpoint or the frame limit
+    fn run_until<F: Fn(&RegisterState, u8) -> bool>(&mut self, frames: Option<u64>, done: F) -> Option<Stop> {
+	loop {
+	    let opcode = self.gameboy.read_memory(self.gameboy.registers().pc);
+	    if let Some(stop) = self.step_instruction() {
+		return Some(stop);
+	    }
+	    if done(&self.gameboy.registers(), opcode) {
+		return None;
+	    }
+	    if let Some(index) = self.breakpoint_hit() {
+		return Some(Stop::Breakpoint(index));
+	    }
+	    if INTERRUPTED.swap(false, Ordering::Relaxed) {
+		return Some(Stop::Interrupted);
+	    }
+	    if frames.is_some_and(|frames| self.gameboy.frames() >= frames) {
+		return None;
+	    }
+	}
+    }
+
+    // Checkpoints past the current position stay valid as long as nothing is changed by hand
+    fn add_checkpoint(&mut self) {
+	if self.checkpoints.last().is_some_and(|checkpoint| checkpoint.position >= self.position) {
+	    return;
+	}
+
+	self.checkpoints.push(Checkpoint {
+	    position: self.position,
+	    state: self.gameboy.save_state(),
+	    call_stack: self.call_stack.clone(),
+	});
+	if self.checkpoints.len() > MAX_CHECKPOINTS {
+	    // Keeps every other one, starting with the first
+	    let mut keep = false;
+	    self.checkpoints.retain(|_| {
+		keep = !keep;
+		keep
+	    });
+	    self.checkpoint_interval *= 2;
+	}
+    }
+
+    // After set or write, running forward from an older checkpoint would no longer get here
+    fn record_change(&mut self) {
+	let position = self.position;
+	self.checkpoints.retain(|checkpoint| checkpoint.position < position);
+	self.add_checkpoint();
+	if self.changes.last() != Some(&position) {
+	    self.changes.push(position);
+	}
+    }
+
+    // Loads the last checkpoint at or before `target` and steps forward to it
+    fn seek(&mut self, target: u64) {
+	// Before a change the history goes on without it, the checkpoints from then on are stale
+	if let Some(change) = self.changes.iter().copied().find(|change| *change > target) {
+	    self.checkpoints.retain(|checkpoint| checkpoint.position < change);
+	    self.changes.retain(|change| *change <= target);
+	}
+
+	let checkpoint = self.checkpoints.iter().rev().find(|checkpoint| checkpoint.position <= target).unwrap();
+	self.gameboy.load_state(&checkpoint.state).unwrap();
+	self.call_stack = checkpoint.call_stack.clone();
+	self.position = checkpoint.position;
+
+	while self.position < target {
+	    self.step_instruction();
+	}
+    }
+
+    // Replays the history one checkpoint at a time, from the latest, to find the
+    // last breakpoint or watchpoint hit before the current position
+    fn reverse_continue(&mut self) -> Option<Stop> {
+	let current = self.position;
+	let starts: Vec<u64> = self
+	    .checkpoints
+	    .iter()
+	    .map(|checkpoint| checkpoint.position)
+	    .filter(|position| *position < current)
+	    .collect();
+
+	let mut end = current;
+	for start in starts.into_iter().rev() {
+	    self.seek(start);
+	    let mut last_hit = None;
+	    while self.position < end - 1 {
+		let stop = self.step_instruction().or_else(|| self.breakpoint_hit().map(Stop::Breakpoint));
+		if let Some(stop) = stop {
+		    last_hit = Some((self.position, stop));
+		}
+		if INTERRUPTED.swap(false, Ordering::Relaxed) {
+		    return Some(Stop::Interrupted);
+		}
+	    }
+
+	    if let Some((position, stop)) = last_hit {
+		self.seek(position);
+		return Some(stop);
+	    }
+	    end = start + 1;
+	}
+
+	self.seek(0);
+	None
+    }
+
+    fn breakpoint_hit(&self) -> Option<usize> {
+	let pc = self.gameboy.registers().pc;
+	self.breakpoints.iter().position(|breakpoint| match *breakpoint {
+	    Breakpoint::Address(address) => address == pc,
+	    Breakpoint::Banked(bank, address) => address == pc && bank == self.gameboy.bank(pc),
+	    Breakpoint::Opcode(opcode) => opcode == self.gameboy.read_memory(pc),
+	})
+    }
+
+    fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+	self.breakpoints.push(breakpoint);
+	println!("Breakpoint {} set", self.breakpoints.len());
+    }
+
+    fn report(&mut self, stop: Option<Stop>) {
+	match stop {
+	    Some(Stop::Breakpoint(index)) => println!("{}", format!("Breakpoint {} hit", index + 1).yellow().bold()),
+	    Some(Stop::Watchpoint(hit)) => {
+		let access = match hit.access {
+		    Access::Read => "Read",
+		    Access::Write => "Write",
+		};
+		let message = format!("{} of ${:02X} at {}", access, hit.value, self.describe(hit.address));
+		println!("{}", message.yellow().bold());
+	    }
+	    Some(Stop::InvalidOpcode(address, opcode)) => {
+		let message = format!("Invalid opcode ${:02X} at {}, the CPU hung", opcode, self.describe(address));
+		println!("{}", message.red().bold());
+	    }
+	    Some(Stop::Interrupted) => println!("{}", "Interrupted".yellow().bold()),
+	    None => {}
+	}
+	self.show_location();
+    }
+
+    // Accepts a label from the symbol file, bank:address or an address
+    fn parse_location(&self, text: &str) -> Result<(Option<u16>, u16), &'static str> {
+	parse_location(self.symbols, text)
+    }
+
+    fn describe(&self, address: u16) -> String {
+	let bank = self.gameboy.bank(address);
+	match self.symbols.and_then(|symbols| symbols.describe(bank, address)) {
+	    Some(label) => format!("{:02X}:{:04X} <{}>", bank, address, label),
+	    None => format!("{:02X}:{:04X}", bank, address),
+	}
+    }
+
+    fn show_location(&self) {
+	self.show_registers();
+	self.show_instruction(self.gameboy.registers().pc);
+    }
+
+    fn show_registers(&self) {
+	let registers = self.gameboy.registers();
+	let pairs = [
+	    ("AF", (registers.a as u16) << 8 | registers.f as u16),
+	    ("BC", (registers.b as u16) << 8 | registers.c as u16),
+	    ("DE", (registers.d as u16) << 8 | registers.e as u16),
+	    ("HL", (registers.h as u16) << 8 | registers.l as u16),
+	    ("SP", registers.sp),
+	    ("PC", registers.pc),
+	];
+
+	let mut line = String::new();
+	for (name, value) in pairs {
+	    line += &format!("{}={} ", name.bold(), format!("{:04X}", value).cyan());
+	}
+	for (bit, name) in [(7, "Z"), (6, "N"), (5, "H"), (4, "C")] {
+	    let flag = if registers.f & 1 << bit != 0 { name.green().bold() } else { "-".dimmed() };
+	    line += &flag.to_string();
+	}
+	let ime = if self.gameboy.ime() { "IME".green().bold() } else { "ime".dimmed() };
+	line += &format!(" {}", ime);
+	if self.gameboy.halted() {
+	    line += &format!(" {}", "HALT".yellow());
+	}
+	println!("{}", line);
+    }
+
+    // Prints the instruction and returns its length
+    fn show_instruction(&self, address: u16) -> u16 {
+	let instruction = disassemble(address, |address| self.gameboy.read_memory(address));
+	let bank = self.gameboy.bank(address);
+	let label = |target: u16| self.symbols?.label(self.gameboy.bank(target), target).map(String::from);
+
+	if let Some(name) = label(address) {
+	    println!("{}:", name.yellow());
+	}
+
+	let bytes: Vec<String> = instruction.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+	let marker = if address == self.gameboy.registers().pc { "=>" } else { "  " };
+	let text = instruction.format(label);
+	match instruction.comment() {
+	    Some(register) => println!("{} {:02X}:{:04X}  {:<9} {:<20} ; {}", marker, bank, address, bytes.join(" "), text, register.dimmed()),
+	    None => println!("{} {:02X}:{:04X}  {:<9} {}", marker, bank, address, bytes.join(" "), text),
+	}
+
+	instruction.length()
+    }
+
+    fn hex_dump(&self, address: u16, length: u64) {
+	let start = address & 0xFFF0;
+	let end = address as u64 + length;
+
+	let mut row = start as u64;
+	while row < end && row <= 0xFFFF {
+	    let mut bytes = String::new();
+	    let mut text = String::new();
+	    for column in 0..16 {
+		let current = row + column;
+		if current < address as u64 || current >= end || current > 0xFFFF {
+		    bytes += "   ";
+		    text.push(' ');
+		    continue;
+		}
+
+		let byte = self.gameboy.read_memory(current as u16);
+		bytes += &format!("{:02X} ", byte);
+		text.push(if byte.is_ascii_graphic() { byte as char } else { '.' });
+	    }
+	    println!("{}  {} {}", format!("{:04X}", row).cyan(), bytes, text.dimmed());
+	    row += 16;
+	}
+    }
+
+    fn show_points(&self) {
+	if self.breakpoints.is_empty() && self.watchpoints.is_empty() {
+	    println!("No breakpoints or watchpoints");
+	}
+	for (index, breakpoint) in self.breakpoints.iter().enumerate() {
+	    let location = match *breakpoint {
+		Breakpoint::Address(address) => self.describe(address),
+		Breakpoint::Banked(bank, address) => format!("{:02X}:{:04X}", bank, address),
+		Breakpoint::Opcode(opcode) => format!("opcode ${:02X}", opcode),
+	    };
+	    println!("{}  break {}", index + 1, location);
+	}
+	for (address, access) in &self.watchpoints {
+	    let access = match access {
+		Access::Read => "read",
+		Access::Write => "write",
+	    };
+	    println!("   watch {} {}", self.describe(*address), access);
+	}
+    }
+
+    fn show_call_stack(&self) {
+	println!("#0  {}", self.describe(self.gameboy.registers().pc));
+	for (depth, frame) in self.call_stack.frames().iter().rev().enumerate() {
+	    let kind = if frame.is_interrupt() { "interrupted" } else { "called" };
+	    println!("#{}  {} {} {}", depth + 1, self.describe(frame.call_site), kind, self.describe(frame.target).dimmed());
+	}
+    }
+}
+
+fn parse_count(text: &str) -> Result<u64, &'static str> {
+    match text.parse() {
+	Ok(count) => Ok(count),
+	Err(_) => Err("Invalid count, expected a decimal number"),
+    }
+}
@@ -0,0 +1,101 @@
+use std::fs;
+
+use gb_core::{disassemble, Symbols};
+
+const BANK_SIZE: usize = 0x4000;
+
+const USAGE: &str = "Usage: lb-emu disasm <rom_file> [--bank <n>] [--range <start hex>:<end hex>]";
+
+// Prints the instructions of a ROM bank or of an address range of the mapped banks
+pub fn run(args: &[String]) -> Result<(), &'static str> {
+    let path = match args.first() {
+	Some(path) => path,
+	None => return Err(USAGE),
+    };
+    let rom = match fs::read(path) {
+	Ok(data) => data,
+	Err(_) => return Err("Couldn't read the ROM file"),
+    };
+    let symbols = Symbols::for_rom(path)?;
+
+    let mut bank = 0;
+    let mut range = None;
+
+    let mut options = args[1..].iter();
+    while let Some(arg) = options.next() {
+	match (arg.as_str(), options.next()) {
+	    ("--bank", Some(value)) => match value.parse::<usize>() {
+		Ok(number) => bank = number,
+		Err(_) => return Err("Invalid bank number"),
+	    },
+	    ("--range", Some(value)) => range = Some(parse_range(value)?),
+	    _ => return Err(USAGE),
+	}
+    }
+
+    if bank * BANK_SIZE >= rom.len() {
+	return Err("The ROM has no such bank");
+    }
+
+    let (start, end) = match range {
+	Some((start, end)) if start >= 0x8000 || end >= 0x8000 => {
+	    return Err("The range must be within the ROM area, 0000-7FFF");
+	}
+	Some(range) => range,
+	None if bank == 0 => (0x0000, 0x3FFF),
+	None => (0x4000, 0x7FFF),
+    };
+
+    // Bank 0 is always at 0x0000, the others are switched in at 0x4000, where
+    // bank 0 can't be mapped and bank 1 shows instead. A range can span both.
+    // RAM and I/O operands are looked up in bank 0, where RGBDS puts their labels.
+    let switched = bank.max(1);
+    let bank_of = |address: u16| match address {
+	0x4000..=0x7FFF => switched,
+	_ => 0,
+    };
+    let read = |address: u16| {
+	let offset = match address {
+	    0x0000..=0x3FFF => address as usize,
+	    _ => bank_of(address) * BANK_SIZE + (address as usize & (BANK_SIZE - 1)),
+	};
+	rom.get(offset).copied().unwrap_or(0xFF)
+    };
+    let label = |address: u16| symbols.as_ref()?.label(bank_of(address) as u16, address).map(String::from);
+
+    let mut address = start as u32;
+    while address <= end as u32 {
+	let instruction = disassemble(address as u16, read);
+	let bytes: Vec<String> = instruction.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+	let text = instruction.format(label);
+
+	if let Some(name) = label(address as u16) {
+	    println!("{}:", name);
+	}
+
+	match instruction.comment() {
+	    Some(register) => println!("{:02X}:{:04X}  {:<9} {:<20} ; {}", bank_of(address as u16), address, bytes.join(" "), text, register),
+	    None => println!("{:02X}:{:04X}  {:<9} {}", bank_of(address as u16), address, bytes.join(" "), text),
+	}
+
+	address += instruction.length() as u32;
+    }
+
+    Ok(())
+}
+
+fn parse_range(value: &str) -> Result<(u16, u16), &'static str> {
+    let (start, end) = match value.split_once(':') {
+	Some(bounds) => bounds,
+	None => return Err("Invalid range, expected <start>:<end>"),
+    };
+
+    match (parse_address(start), parse_address(end)) {
+	(Some(start), Some(end)) if start <= end => Ok((start, end)),
+	_ => Err("Invalid range, expected hexadecimal addresses with start <= end"),
+    }
+}
+
+fn parse_address(value: &str) -> Option<u16> {
+    u16::from_str_radix(value.trim_start_matches("0x").trim_start_matches('$'), 16).ok()
+}
@@ -0,0 +1,261 @@
+use std::{fs, path::Path};
+
+use colored::Colorize;
+use gb_core::{import_movie, parse_location, Config, GameBoy, LoadReport, ManualPalette, Model, Movie, Rewind, Speed, SpeedCommand, Symbols};
+
+use crate::{
+    debugger, gdb,
+    headless::{self, HeadlessOptions},
+    movie::MovieSession,
+    movie_import,
+    trace::{TraceOptions, TracePoint},
+};
+
+const USAGE: &str = "Usage: lb-emu <rom_file> [--model <dmg0|dmg|mgb|sgb|sgb2|cgb|agb>] [--boot-rom <file>] [--palette <direction[+a|+b]>] [--color-correction] [--load-state <file>] [--rewind <seconds>] [--rewind-memory <MiB>] [--speed <fraction>] [--fast-forward <multiplier|max>] [--mute-fast-forward] [--record <movie> | --play <movie|bk2|vbm>] [--debug] [--gdb <port>] [--headless [--frames <n>] [--cycles <n>] [--until-serial <text>] [--until-pc <hex address|label>] [--screenshot <png file>] [--serial-out <file>] [--save-state <file>] [--trace <file> [--trace-start <point>] [--trace-stop <point>] [--doctor]]]\n       lb-emu disasm <rom_file> [--bank <n>] [--range <start hex>:<end hex>]\n       lb-emu convert-movie <rom_file> <bk2|vbm file> <output> [--boot-rom <file>]\n       lb-emu dap\n       lb-emu trace-diff <rom_file> <reference_trace> [--model <model>] [--context <n>]";
+
+struct Options {
+    model: Model,
+    boot_rom: Option<String>,
+    palette: Option<ManualPalette>,
+    color_correction: bool,
+    load_state: Option<String>,
+    record: Option<String>,
+    play: Option<String>,
+    debug: bool,
+    gdb_port: Option<u16>,
+    rewind_seconds: usize,
+    rewind_memory: usize,
+    speed: Speed,
+    headless: Option<HeadlessOptions>,
+}
+
+pub fn emu_run(args: &Vec<String>) -> Result<(), &'static str>{
+    if args.len() < 2 {
+	return Err(USAGE);
+    }
+
+    let mut options = parse_options(&args[2..])?;
+
+    // Without a boot ROM the CPU starts straight from the state it would leave behind
+    let boot_rom = match &options.boot_rom {
+	Some(path) => Some(fs::read(path).map_err(|_| "Couldn't read the boot ROM file")?),
+	None => None,
+    };
+
+    // Movies start from power on, on the console they were recorded with.
+    // Those of other emulators are converted, they need the ROM for that.
+    let played = match &options.play {
+	Some(path) => {
+	    let data = fs::read(path).map_err(|_| "Couldn't read the movie file")?;
+	    let rom = fs::read(&args[1]).map_err(|_| "Couldn't read the ROM file")?;
+	    let (movie, warnings) = import_movie(&data, &rom, boot_rom.as_deref())?;
+	    movie_import::print_warnings(&warnings);
+	    options.model = movie.model;
+	    Some(movie)
+	}
+	None => None,
+    };
+    if (options.record.is_some() || played.is_some()) && options.load_state.is_some() {
+	return Err("Movies start from power on, they can't be used with --load-state");
+    }
+
+    let mut config = Config::new(options.model);
+    config.palette = options.palette;
+    config.color_correction = options.color_correction;
+    config.boot_rom = boot_rom;
+
+    let mut gameboy = match cartrige_loaded(&args[1], &config) {
+	Ok(gameboy) => gameboy,
+	Err(msg) => return Err(msg)
+    };
+
+    println!("{}", format!("Cartrige loaded successfully!").green().bold());
+
+    let movie = match (played, options.record.take()) {
+	(Some(movie), _) => {
+	    movie.check(&gameboy, &config)?;
+	    Some(MovieSession::Play(movie))
+	}
+	(None, Some(path)) => Some(MovieSession::Record { path, movie: Movie::new(&gameboy, &config) }),
+	(None, None) => None,
+    };
+
+    if let Some(path) = &options.load_state {
+	let state = fs::read(path).map_err(|_| "Couldn't read the save state file")?;
+	print_load_report(&gameboy.load_state_file(&state)?);
+    }
+
+    // RGBDS and wla-dx write their labels next to the ROM
+    let symbols = Symbols::for_rom(&args[1])?;
+    if let Some(symbols) = &symbols {
+	println!("{}", format!("Loaded {} symbols", symbols.len()).green());
+    }
+
+    if let Some(port) = options.gdb_port {
+	return gdb::run(&mut gameboy, port);
+    }
+
+    if options.debug {
+	return debugger::run(&mut gameboy, symbols.as_ref());
+    }
+
+    if let Some(headless_options) = &mut options.headless {
+	if let Some(location) = headless_options.until_location.take() {
+	    headless_options.until_pc = Some(parse_location(symbols.as_ref(), &location)?.1);
+	}
+
+	if let Some(trace) = &mut headless_options.trace {
+	    if trace.path.is_empty() {
+		return Err("--trace-start, --trace-stop and --doctor need --trace <file>");
+	    }
+	    for point in trace.start.iter_mut().chain(trace.stop.iter_mut()) {
+		point.resolve(symbols.as_ref())?;
+	    }
+	}
+
+	let reason = headless::run(&mut gameboy, headless_options, movie)?;
+	println!("{}", format!("Stopped: {}", reason).green().bold());
+	return Ok(());
+    }
+
+    // F5 and F7 save and load next to the ROM, C toggles color correction
+    let state_path = Path::new(&args[1]).with_extension("state");
+    // One state per frame, R steps back through them while held
+    let rewind = Rewind::new(options.rewind_seconds * 60, options.rewind_memory * 1024 * 1024);
+    run_window(&mut gameboy, &state_path, rewind, movie, options.speed)
+}
+
+// Graphics Library: https://docs.rs/sdl2/0.35.2/sdl2/index.html#getting-started
+#[cfg(feature = "sdl")]
+fn run_window(gameboy: &mut GameBoy, state_path: &Path, rewind: Rewind, movie: Option<MovieSession>, speed: Speed) -> Result<(), &'static str> {
+    crate::window::run(gameboy, state_path, rewind, movie, speed)
+}
+
+#[cfg(not(feature = "sdl"))]
+fn run_window(_gameboy: &mut GameBoy, _state_path: &Path, _rewind: Rewind, _movie: Option<MovieSession>, _speed: Speed) -> Result<(), &'static str> {
+    Err("Built without the sdl feature, only --headless is available")
+}
+
+// States from other emulators only restore part of the machine
+pub fn print_load_report(report: &LoadReport) {
+    let source = report.emulator.as_deref().unwrap_or("lb-emu");
+    println!("{}", format!("Save state from {}, restored: {}", source, report.restored.join(", ")).green());
+    for skipped in &report.skipped {
+	println!("{}", format!("  not restored: {}", skipped).yellow());
+    }
+}
+
+fn cartrige_loaded(rom: &str, config: &Config) -> Result<GameBoy, &'static str>{
+    GameBoy::from_file(rom, config)
+}
+
+fn parse_options(args: &[String]) -> Result<Options, &'static str> {
+    let mut options = Options {
+	model: Model::Dmg,
+	boot_rom: None,
+	palette: None,
+	color_correction: false,
+	load_state: None,
+	record: None,
+	play: None,
+	debug: false,
+	gdb_port: None,
+	rewind_seconds: 10,
+	rewind_memory: 64,
+	speed: Speed::new(),
+	headless: None,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+	match arg.as_str() {
+	    "--model" => match args.next() {
+		Some(model) => options.model = Model::try_from(model.as_str())?,
+		None => return Err(USAGE),
+	    },
+	    "--boot-rom" => match args.next() {
+		Some(path) => options.boot_rom = Some(path.clone()),
+		None => return Err(USAGE),
+	    },
+	    "--color-correction" => options.color_correction = true,
+	    "--load-state" => match args.next() {
+		Some(path) => options.load_state = Some(path.clone()),
+		None => return Err(USAGE),
+	    },
+	    "--debug" => options.debug = true,
+	    "--gdb" => match args.next().map(|port| port.parse()) {
+		Some(Ok(port)) => options.gdb_port = Some(port),
+		Some(Err(_)) => return Err("Invalid port number"),
+		None => return Err(USAGE),
+	    },
+	    "--record" => match args.next() {
+		Some(path) => options.record = Some(path.clone()),
+		None => return Err(USAGE),
+	    },
+	    "--play" => match args.next() {
+		Some(path) => options.play = Some(path.clone()),
+		None => return Err(USAGE),
+	    },
+	    "--rewind" => match args.next() {
+		Some(seconds) => options.rewind_seconds = parse_number(seconds)? as usize,
+		None => return Err(USAGE),
+	    },
+	    "--rewind-memory" => match args.next() {
+		Some(megabytes) => options.rewind_memory = parse_number(megabytes)? as usize,
+		None => return Err(USAGE),
+	    },
+	    "--speed" => match args.next().map(|speed| speed.parse::<f64>()) {
+		Some(Ok(speed)) if speed > 0.0 => options.speed.command(SpeedCommand::SetSpeed(speed)),
+		_ => return Err("--speed takes a fraction of the normal speed, like 0.5"),
+	    },
+	    "--fast-forward" => match args.next().map(String::as_str) {
+		Some("max") => options.speed.command(SpeedCommand::SetFastForward(None)),
+		Some(multiplier) => match multiplier.parse::<f64>() {
+		    Ok(multiplier) if multiplier > 0.0 => options.speed.command(SpeedCommand::SetFastForward(Some(multiplier))),
+		    _ => return Err("--fast-forward takes a multiplier or max"),
+		},
+		None => return Err(USAGE),
+	    },
+	    "--mute-fast-forward" => options.speed.mute_fast_forward = true,
+	    "--palette" => match args.next() {
+		Some(combination) => options.palette = Some(ManualPalette::try_from(combination.as_str())?),
+		None => return Err(USAGE),
+	    },
+	    "--headless" => {
+		options.headless.get_or_insert_with(HeadlessOptions::new);
+	    }
+	    "--doctor" => options.headless.get_or_insert_with(HeadlessOptions::new).trace.get_or_insert_with(TraceOptions::new).doctor = true,
+	    "--frames" | "--cycles" | "--until-serial" | "--until-pc" | "--screenshot" | "--serial-out" | "--save-state" | "--trace" | "--trace-start"
+	    | "--trace-stop" => {
+		let value = match args.next() {
+		    Some(value) => value,
+		    None => return Err(USAGE),
+		};
+		let headless = options.headless.get_or_insert_with(HeadlessOptions::new);
+
+		match arg.as_str() {
+		    "--frames" => headless.frames = Some(parse_number(value)?),
+		    "--cycles" => headless.cycles = Some(parse_number(value)?),
+		    "--until-serial" => headless.until_serial = Some(value.clone()),
+		    "--until-pc" => headless.until_location = Some(value.clone()),
+		    "--screenshot" => headless.screenshot = Some(value.clone()),
+		    "--save-state" => headless.save_state = Some(value.clone()),
+		    "--trace" => headless.trace.get_or_insert_with(TraceOptions::new).path = value.clone(),
+		    "--trace-start" => headless.trace.get_or_insert_with(TraceOptions::new).start = Some(TracePoint::try_from(value.as_str())?),
+		    "--trace-stop" => headless.trace.get_or_insert_with(TraceOptions::new).stop = Some(TracePoint::try_from(value.as_str())?),
+		    _ => headless.serial_output = Some(value.clone()),
+		}
+	    }
+	    _ => return Err(USAGE),
+	}
+    }
+
+    Ok(options)
+}
+
+fn parse_number(value: &str) -> Result<u64, &'static str> {
+    match value.parse() {
+	Ok(number) => Ok(number),
+	Err(_) => Err("Invalid number"),
+    }
+}
@@ -0,0 +1,382 @@
+use std::{
+    io::{ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream},
+};
+
+use gb_core::{Access, GameBoy, RegisterState};
+
+// Registers are sent as AF, BC, DE, HL, SP and PC, 16 bits little-endian each
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.gnu.gdb.sm83.core">
+    <reg name="af" bitsize="16" type="int"/>
+    <reg name="bc" bitsize="16" type="int"/>
+    <reg name="de" bitsize="16" type="int"/>
+    <reg name="hl" bitsize="16" type="data_ptr"/>
+    <reg name="sp" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>"#;
+
+const REGISTER_COUNT: usize = 6;
+// How many instructions run between two checks for a Ctrl+C from the client
+const INTERRUPT_POLL_STEPS: u32 = 1024;
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+
+struct Session<'a> {
+    stream: TcpStream,
+    gameboy: &'a mut GameBoy,
+    breakpoints: Vec<u16>,
+    // Address and the Z packet type, 2 write, 3 read and 4 access
+    watchpoints: Vec<(u16, u8)>,
+    no_ack: bool,
+}
+
+// Waits for a single client on localhost, the session ends when it detaches
+pub fn run(gameboy: &mut GameBoy, port: u16) -> Result<(), &'static str> {
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+	Ok(listener) => listener,
+	Err(_) => return Err("Couldn't listen on the GDB port"),
+    };
+    println!("Waiting for a GDB connection on 127.0.0.1:{}", port);
+
+    let stream = match listener.accept() {
+	Ok((stream, _)) => stream,
+	Err(_) => return Err("Couldn't accept the GDB connection"),
+    };
+    let _ = stream.set_nodelay(true);
+
+    let mut session = Session {
+	stream,
+	gameboy,
+	breakpoints: Vec::new(),
+	watchpoints: Vec::new(),
+	no_ack: false,
+    };
+
+    while let Some(packet) = session.receive()? {
+	match session.handle(&packet)? {
+	    Some(reply) => session.send(&reply)?,
+	    None => return Ok(()),
+	}
+    }
+
+    Ok(())
+}
+
+impl Session<'_> {
+    // Returns the reply, None once the client detached or killed the target
+    fn handle(&mut self, packet: &str) -> Result<Option<String>, &'static str> {
+	if packet.is_empty() || !packet.is_char_boundary(1) {
+	    return Ok(Some(String::new()));
+	}
+	let (command, arguments) = packet.split_at(1);
+
+	let reply = match command {
+	    "?" => stop_reply(SIGTRAP),
+	    "g" => {
+		let registers = registers_to_words(self.gameboy.registers());
+		registers.iter().map(|word| encode_word(*word)).collect()
+	    }
+	    "G" => match decode_hex(arguments) {
+		Some(bytes) if bytes.len() >= REGISTER_COUNT * 2 => {
+		    let mut words = [0; REGISTER_COUNT];
+		    for (index, word) in words.iter_mut().enumerate() {
+			*word = u16::from_le_bytes([bytes[index * 2], bytes[index * 2 + 1]]);
+		    }
+		    self.gameboy.set_registers(words_to_registers(words));
+		    String::from("OK")
+		}
+		_ => String::from("E01"),
+	    },
+	    "p" => match usize::from_str_radix(arguments, 16) {
+		Ok(index) if index < REGISTER_COUNT => encode_word(registers_to_words(self.gameboy.registers())[index]),
+		_ => String::from("E01"),
+	    },
+	    "P" => {
+		let register = arguments.split_once('=').and_then(|(index, value)| {
+		    let index = usize::from_str_radix(index, 16).ok()?;
+		    let bytes = decode_hex(value)?;
+		    (index < REGISTER_COUNT && bytes.len() == 2).then(|| (index, u16::from_le_bytes([bytes[0], bytes[1]])))
+		});
+		match register {
+		    Some((index, value)) => {
+			let mut words = registers_to_words(self.gameboy.registers());
+			words[index] = value;
+			self.gameboy.set_registers(words_to_registers(words));
+			String::from("OK")
+		    }
+		    None => String::from("E01"),
+		}
+	    }
+	    "m" => match parse_range(arguments) {
+		Some((address, length)) => (0..length)
+		    .map(|offset| format!("{:02x}", self.gameboy.read_memory(address.wrapping_add(offset))))
+		    .collect(),
+		None => String::from("E01"),
+	    },
+	    "M" => {
+		let write = arguments.split_once(':').and_then(|(range, data)| Some((parse_range(range)?, decode_hex(data)?)));
+		match write {
+		    Some(((address, length), bytes)) if bytes.len() == length as usize => {
+			for (offset, byte) in bytes.iter().enumerate() {
+			    self.gameboy.write_memory(address.wrapping_add(offset as u16), *byte);
+			}
+			String::from("OK")
+		    }
+		    _ => String::from("E01"),
+		}
+	    }
+	    "Z" | "z" => self.set_point(command == "Z", arguments),
+	    "s" => {
+		self.gameboy.step();
+		self.stop_reason().unwrap_or_else(|| stop_reply(SIGTRAP))
+	    }
+	    "c" => self.resume()?,
+	    "q" => self.query(arguments),
+	    // The packet itself was still acknowledged
+	    "Q" if arguments == "StartNoAckMode" => {
+		self.no_ack = true;
+		String::from("OK")
+	    }
+	    "H" | "T" => String::from("OK"),
+	    "D" => {
+		self.send("OK")?;
+		return Ok(None);
+	    }
+	    "k" => return Ok(None),
+	    // Anything unsupported gets the empty reply, as the protocol asks
+	    _ => String::new(),
+	};
+
+	Ok(Some(reply))
+    }
+
+    fn query(&self, arguments: &str) -> String {
+	if arguments.starts_with("Supported") {
+	    return String::from("PacketSize=4000;qXfer:features:read+;QStartNoAckMode+");
+	}
+	if let Some(range) = arguments.strip_prefix("Xfer:features:read:target.xml:") {
+	    return match parse_range(range) {
+		Some((offset, length)) => {
+		    let data = TARGET_XML.as_bytes();
+		    let start = (offset as usize).min(data.len());
+		    let end = (start + length as usize).min(data.len());
+		    let prefix = if end < data.len() { 'm' } else { 'l' };
+		    format!("{}{}", prefix, String::from_utf8_lossy(&data[start..end]))
+		}
+		None => String::from("E01"),
+	    };
+	}
+
+	match arguments {
+	    "Attached" => String::from("1"),
+	    "C" => String::from("QC1"),
+	    "fThreadInfo" => String::from("m1"),
+	    "sThreadInfo" => String::from("l"),
+	    _ => String::new(),
+	}
+    }
+
+    // Z0 and Z1 are breakpoints, Z2, Z3 and Z4 write, read and access watchpoints
+    fn set_point(&mut self, insert: bool, arguments: &str) -> String {
+	let mut fields = arguments.split(',');
+	let kind = fields.next().and_then(|kind| kind.parse::<u8>().ok());
+	let address = fields.next().and_then(|address| u16::from_str_radix(address, 16).ok());
+	let length = fields.next().and_then(|length| u16::from_str_radix(length, 16).ok()).unwrap_or(1);
+
+	let (kind, address) = match (kind, address) {
+	    (Some(kind), Some(address)) => (kind, address),
+	    _ => return String::from("E01"),
+	};
+
+	match kind {
+	    0 | 1 => {
+		if insert {
+		    self.breakpoints.push(address);
+		} else if let Some(index) = self.breakpoints.iter().position(|breakpoint| *breakpoint == address) {
+		    self.breakpoints.remove(index);
+		}
+	    }
+	    2..=4 => {
+		for address in (0..length.max(1)).map(|offset| address.wrapping_add(offset)) {
+		    if insert {
+			self.watchpoints.push((address, kind));
+		    } else if let Some(index) = self.watchpoints.iter().position(|watchpoint| *watchpoint == (address, kind)) {
+			self.watchpoints.remove(index);
+		    }
+		    self.arm_watchpoints(address);
+		}
+	    }
+	    _ => return String::new(),
+	}
+
+	String::from("OK")
+    }
+
+    // The core watches accesses by address, so they are set again from the kinds left there
+    fn arm_watchpoints(&mut self, address: u16) {
+	self.gameboy.unwatch(address);
+	for (_, kind) in self.watchpoints.iter().filter(|(watched, _)| *watched == address) {
+	    if *kind != 3 {
+		self.gameboy.watch(address, Access::Write);
+	    }
+	    if *kind != 2 {
+		self.gameboy.watch(address, Access::Read);
+	    }
+	}
+    }
+
+    // Runs until a breakpoint, a watchpoint, a hung CPU or a Ctrl+C from the client
+    fn resume(&mut self) -> Result<String, &'static str> {
+	let mut steps = 0;
+	loop {
+	    self.gameboy.step();
+
+	    if let Some(reply) = self.stop_reason() {
+		return Ok(reply);
+	    }
+	    if self.breakpoints.contains(&self.gameboy.registers().pc) {
+		return Ok(stop_reply(SIGTRAP));
+	    }
+
+	    steps += 1;
+	    if steps % INTERRUPT_POLL_STEPS == 0 && self.interrupt_requested()? {
+		return Ok(stop_reply(SIGINT));
+	    }
+	}
+    }
+
+    fn stop_reason(&mut self) -> Option<String> {
+	if self.gameboy.invalid_opcode().is_some() {
+	    return Some(stop_reply(SIGILL));
+	}
+
+	let hit = self.gameboy.take_watch_hit()?;
+	// The watchpoint of the address that covers the access, several kinds can share one
+	let kind = self
+	    .watchpoints
+	    .iter()
+	    .filter(|(address, _)| *address == hit.address)
+	    .map(|(_, kind)| *kind)
+	    .find(|kind| matches!((kind, hit.access), (2, Access::Write) | (3, Access::Read) | (4, _)));
+	let name = match (kind, hit.access) {
+	    (Some(4), _) => "awatch",
+	    (_, Access::Read) => "rwatch",
+	    (_, Access::Write) => "watch",
+	};
+	Some(format!("T{:02x}{}:{:04x};", SIGTRAP, name, hit.address))
+    }
+
+    fn interrupt_requested(&mut self) -> Result<bool, &'static str> {
+	let mut byte = [0];
+	if self.stream.set_nonblocking(true).is_err() {
+	    return Err("Couldn't poll the GDB connection");
+	}
+	let result = self.stream.read(&mut byte);
+	let _ = self.stream.set_nonblocking(false);
+
+	match result {
+	    Ok(0) => Err("The GDB client disconnected"),
+	    Ok(_) => Ok(byte[0] == 0x03),
+	    Err(error) if error.kind() == ErrorKind::WouldBlock => Ok(false),
+	    Err(_) => Err("Couldn't read from the GDB connection"),
+	}
+    }
+
+    // Packets are $data#checksum, acknowledged with + unless the client turned that off
+    fn receive(&mut self) -> Result<Option<String>, &'static str> {
+	let mut byte = [0];
+	loop {
+	    match self.stream.read(&mut byte) {
+		Ok(0) => return Ok(None),
+		Ok(_) if byte[0] == b'$' => break,
+		// Acks, and a Ctrl+C while already stopped, need no answer
+		Ok(_) => continue,
+		Err(_) => return Err("Couldn't read from the GDB connection"),
+	    }
+	}
+
+	let mut data = Vec::new();
+	loop {
+	    match self.stream.read(&mut byte) {
+		Ok(0) => return Ok(None),
+		Ok(_) if byte[0] == b'#' => break,
+		Ok(_) => data.push(byte[0]),
+		Err(_) => return Err("Couldn't read from the GDB connection"),
+	    }
+	}
+
+	let mut checksum = [0; 2];
+	if self.stream.read_exact(&mut checksum).is_err() {
+	    return Ok(None);
+	}
+	if !self.no_ack && self.stream.write_all(b"+").is_err() {
+	    return Err("Couldn't write to the GDB connection");
+	}
+
+	Ok(Some(String::from_utf8_lossy(&data).into_owned()))
+    }
+
+    fn send(&mut self, data: &str) -> Result<(), &'static str> {
+	let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+	let packet = format!("${}#{:02x}", data, checksum);
+
+	match self.stream.write_all(packet.as_bytes()) {
+	    Ok(_) => Ok(()),
+	    Err(_) => Err("Couldn't write to the GDB connection"),
+	}
+    }
+}
+
+fn stop_reply(signal: u8) -> String {
+    format!("S{:02x}", signal)
+}
+
+fn registers_to_words(registers: RegisterState) -> [u16; REGISTER_COUNT] {
+    [
+	(registers.a as u16) << 8 | registers.f as u16,
+	(registers.b as u16) << 8 | registers.c as u16,
+	(registers.d as u16) << 8 | registers.e as u16,
+	(registers.h as u16) << 8 | registers.l as u16,
+	registers.sp,
+	registers.pc,
+    ]
+}
+
+fn words_to_registers(words: [u16; REGISTER_COUNT]) -> RegisterState {
+    let [af, bc, de, hl, sp, pc] = words;
+    RegisterState {
+	a: (af >> 8) as u8,
+	f: af as u8 & 0xF0,
+	b: (bc >> 8) as u8,
+	c: bc as u8,
+	d: (de >> 8) as u8,
+	e: de as u8,
+	h: (hl >> 8) as u8,
+	l: hl as u8,
+	sp,
+	pc,
+    }
+}
+
+fn encode_word(word: u16) -> String {
+    let [low, high] = word.to_le_bytes();
+    format!("{:02x}{:02x}", low, high)
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+	return None;
+    }
+    (0..text.len()).step_by(2).map(|index| u8::from_str_radix(text.get(index..index + 2)?, 16).ok()).collect()
+}
+
+// `address,length` in hexadecimal
+fn parse_range(text: &str) -> Option<(u16, u16)> {
+    let (address, length) = text.split_once(',')?;
+    Some((u16::from_str_radix(address, 16).ok()?, u16::from_str_radix(length, 16).ok()?))
+}
@@ -0,0 +1,144 @@
+use std::{fs, path::Path};
+
+use gb_core::{write_png, GameBoy};
+
+use crate::{
+    movie::MovieSession,
+    trace::{TraceOptions, Tracer},
+};
+
+// Runs without a window, until any of the configured limits is reached
+pub struct HeadlessOptions {
+    pub frames: Option<u64>,
+    pub cycles: Option<u64>,
+    pub until_serial: Option<String>,
+    pub until_pc: Option<u16>,
+    // --until-pc as typed, until_pc once the symbol file is loaded
+    pub until_location: Option<String>,
+    pub screenshot: Option<String>,
+    pub serial_output: Option<String>,
+    pub save_state: Option<String>,
+    pub trace: Option<TraceOptions>,
+}
+
+impl HeadlessOptions {
+    pub fn new() -> Self {
+	HeadlessOptions {
+	    frames: None,
+	    cycles: None,
+	    until_serial: None,
+	    until_pc: None,
+	    until_location: None,
+	    screenshot: None,
+	    serial_output: None,
+	    save_state: None,
+	    trace: None,
+	}
+    }
+
+    fn has_limit(&self) -> bool {
+	self.frames.is_some() || self.cycles.is_some() || self.until_serial.is_some() || self.until_pc.is_some() || self.until_location.is_some()
+    }
+}
+
+// A played movie is a limit of its own, it stops when the input runs out
+pub fn run(gameboy: &mut GameBoy, options: &HeadlessOptions, mut movie: Option<MovieSession>) -> Result<&'static str, &'static str> {
+    let playing = movie.as_ref().is_some_and(|movie| !movie.is_recording());
+    if !options.has_limit() && !playing {
+	return Err("Headless mode needs --frames, --cycles, --until-serial, --until-pc or --play");
+    }
+
+    let mut tracer = match &options.trace {
+	Some(trace) => {
+	    gameboy.set_doctor_mode(trace.doctor);
+	    Some(Tracer::new(trace)?)
+	}
+	None => None,
+    };
+
+    let first_frame = gameboy.frames();
+    let first_cycle = gameboy.cycles();
+    let mut movie_frame = None;
+    // Length of the serial output already searched for --until-serial
+    let mut serial_searched = None;
+
+    let reason = loop {
+	if movie_frame != Some(gameboy.frames()) {
+	    movie_frame = Some(gameboy.frames());
+	    if movie.as_mut().is_some_and(|movie| !movie.frame(gameboy)) {
+		break "movie finished";
+	    }
+	}
+
+	if let Some(frames) = options.frames {
+	    if gameboy.frames() - first_frame >= frames {
+		break "frame limit reached";
+	    }
+	}
+	if let Some(limit) = options.cycles {
+	    if gameboy.cycles() - first_cycle >= limit {
+		break "cycle limit reached";
+	    }
+	}
+	if let Some(text) = &options.until_serial {
+	    let output = gameboy.serial_output();
+	    if serial_searched != Some(output.len()) {
+		// Only a match that ends in the new bytes is left to find
+		let start = serial_searched.unwrap_or(0).saturating_sub(text.len().saturating_sub(1)).min(output.len());
+		serial_searched = Some(output.len());
+		if contains(&output[start..], text.as_bytes()) {
+		    break "serial output matched";
+		}
+	    }
+	}
+	if options.until_pc == Some(gameboy.registers().pc) {
+	    break "address reached";
+	}
+	if gameboy.invalid_opcode().is_some() {
+	    break "invalid opcode, the CPU hung";
+	}
+
+	if let Some(tracer) = &mut tracer {
+	    tracer.trace(gameboy)?;
+	}
+	gameboy.step();
+    };
+
+    if let Some(tracer) = tracer {
+	tracer.finish()?;
+    }
+
+    // The input of the current frame is already recorded, so it runs to the end
+    if let Some(movie) = movie {
+	if movie.is_recording() {
+	    let frame = gameboy.frames();
+	    while gameboy.frames() == frame {
+		gameboy.step();
+	    }
+	}
+	movie.finish(gameboy)?;
+    }
+
+    if let Some(path) = &options.screenshot {
+	let (width, height, pixels) = gameboy.screen();
+	write_png(Path::new(path), width, height, pixels)?;
+    }
+
+    if let Some(path) = &options.serial_output {
+	if fs::write(path, gameboy.serial_output()).is_err() {
+	    return Err("Couldn't write the serial output");
+	}
+    }
+
+    if let Some(path) = &options.save_state {
+	if fs::write(path, gameboy.save_state_file()).is_err() {
+	    return Err("Couldn't write the save state");
+	}
+    }
+
+    Ok(reason)
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    needle.is_empty() || haystack.windows(needle.len()).any(|window| window == needle)
+}
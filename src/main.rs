@@ -1,11 +1,31 @@
 use std::{process, env};
 
-mod cpu;
+mod call_stack;
+mod dap;
+mod debugger;
+mod disassemble;
+mod emulator;
+mod gdb;
+mod headless;
+mod movie;
+mod movie_import;
+mod trace;
+mod trace_diff;
+#[cfg(feature = "sdl")]
+mod window;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     
-    if let Err(msg) = cpu::emulator::emu_run(&args) {
+    let result = match args.get(1).map(String::as_str) {
+	Some("disasm") => disassemble::run(&args[2..]),
+	Some("dap") => dap::run(),
+	Some("convert-movie") => movie_import::run(&args[2..]),
+	Some("trace-diff") => trace_diff::run(&args[2..]),
+	_ => emulator::emu_run(&args),
+    };
+
+    if let Err(msg) = result {
 	eprintln!("{}", msg);
 	process::exit(1);
     }
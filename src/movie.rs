@@ -0,0 +1,58 @@
+use std::fs;
+
+use colored::Colorize;
+use gb_core::{GameBoy, Movie};
+
+// A movie recorded or played back by the window or the headless runner
+pub enum MovieSession {
+    Record { path: String, movie: Movie },
+    Play(Movie),
+}
+
+impl MovieSession {
+    // Called at the start of every frame, returns false once a played movie is over
+    pub fn frame(&mut self, gameboy: &mut GameBoy) -> bool {
+	match self {
+	    MovieSession::Record { movie, .. } => {
+		movie.record(gameboy);
+		true
+	    }
+	    MovieSession::Play(movie) => movie.play(gameboy),
+	}
+    }
+
+    pub fn is_recording(&self) -> bool {
+	matches!(self, MovieSession::Record { .. })
+    }
+
+    // Saves a recording with the hash of the final state, or checks a playback against it.
+    // Both happen on a frame boundary so that the hashes can match.
+    pub fn finish(self, gameboy: &GameBoy) -> Result<(), &'static str> {
+	let hash = gameboy.state_hash();
+
+	match self {
+	    MovieSession::Record { path, mut movie } => {
+		// Frames undone by a rewind just before stopping
+		movie.frames.truncate(gameboy.frames() as usize);
+		movie.final_hash = Some(hash);
+		if fs::write(&path, movie.to_string()).is_err() {
+		    return Err("Couldn't write the movie file");
+		}
+		println!("{}", format!("Recorded {} frames, final state hash {:016X}", movie.len(), hash).green());
+	    }
+	    MovieSession::Play(movie) if (gameboy.frames() as usize) < movie.len() => {
+		let message = format!("Playback stopped at frame {} of {}, state hash {:016X}", gameboy.frames(), movie.len(), hash);
+		println!("{}", message.yellow());
+	    }
+	    MovieSession::Play(movie) => match movie.final_hash {
+		Some(expected) if expected == hash => println!("{}", format!("Playback verified, final state hash {:016X}", hash).green().bold()),
+		Some(expected) => {
+		    println!("{}", format!("Final state hash {:016X}, the movie expects {:016X}", hash, expected).red().bold());
+		    return Err("The playback didn't end in the recorded state");
+		}
+		None => println!("Playback finished, final state hash {:016X}", hash),
+	    },
+	}
+	Ok(())
+    }
+}
@@ -0,0 +1,36 @@
+// Converts the movies of other emulators into ours, the parsing is in gb-core
+use std::fs;
+
+use colored::Colorize;
+use gb_core::import_movie;
+
+const USAGE: &str = "Usage: lb-emu convert-movie <rom_file> <bk2|vbm file> <output> [--boot-rom <file>]";
+
+pub fn run(args: &[String]) -> Result<(), &'static str> {
+    let (rom_path, movie_path, output) = match args {
+	[rom, movie, output, ..] => (rom, movie, output),
+	_ => return Err(USAGE),
+    };
+    let boot_rom = match &args[3..] {
+	[] => None,
+	[option, path] if option == "--boot-rom" => Some(fs::read(path).map_err(|_| "Couldn't read the boot ROM file")?),
+	_ => return Err(USAGE),
+    };
+
+    let rom = fs::read(rom_path).map_err(|_| "Couldn't read the ROM file")?;
+    let data = fs::read(movie_path).map_err(|_| "Couldn't read the movie file")?;
+    let (movie, warnings) = import_movie(&data, &rom, boot_rom.as_deref())?;
+    print_warnings(&warnings);
+
+    if fs::write(output, movie.to_string()).is_err() {
+	return Err("Couldn't write the movie file");
+    }
+    println!("{}", format!("Converted {} frames for model {}", movie.len(), movie.model.name()).green());
+    Ok(())
+}
+
+pub fn print_warnings(warnings: &[String]) {
+    for warning in warnings {
+	println!("{}", format!("Warning: {}", warning).yellow());
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use gb_core::{parse_location, GameBoy, Symbols};
+
+// Traces are written in 1 MB chunks, they grow to gigabytes on long runs
+const TRACE_BUFFER_SIZE: usize = 1 << 20;
+
+// When a trace starts or stops: `cycle:<n>`, `frame:<n>` or `pc:<label|hex address>`
+#[derive(Clone)]
+pub enum TracePoint {
+    Cycle(u64),
+    Frame(u64),
+    Address(u16),
+    // The text after `pc:`, until the symbol file is loaded
+    Location(String),
+}
+
+impl TryFrom<&str> for TracePoint {
+    type Error = &'static str;
+
+    fn try_from(text: &str) -> Result<Self, Self::Error> {
+	let invalid = "Invalid trace point, expected cycle:<n>, frame:<n> or pc:<address>";
+
+	match text.split_once(':') {
+	    Some(("cycle", value)) => value.parse().map(TracePoint::Cycle).map_err(|_| invalid),
+	    Some(("frame", value)) => value.parse().map(TracePoint::Frame).map_err(|_| invalid),
+	    Some(("pc", value)) => Ok(TracePoint::Location(value.to_string())),
+	    _ => Err(invalid),
+	}
+    }
+}
+
+impl TracePoint {
+    // Locations become addresses once the symbol file is loaded, labels taking precedence
+    pub fn resolve(&mut self, symbols: Option<&Symbols>) -> Result<(), &'static str> {
+	if let TracePoint::Location(text) = self {
+	    let (_, address) = parse_location(symbols, text)?;
+	    *self = TracePoint::Address(address);
+	}
+	Ok(())
+    }
+
+    fn reached(&self, gameboy: &GameBoy) -> bool {
+	match *self {
+	    TracePoint::Cycle(cycle) => gameboy.cycles() >= cycle,
+	    TracePoint::Frame(frame) => gameboy.frames() >= frame,
+	    TracePoint::Address(address) => gameboy.registers().pc == address,
+	    TracePoint::Location(_) => false,
+	}
+    }
+}
+
+pub struct TraceOptions {
+    pub path: String,
+    pub start: Option<TracePoint>,
+    pub stop: Option<TracePoint>,
+    // --doctor, LY reads 0x90 so the trace can be compared with Gameboy Doctor logs
+    pub doctor: bool,
+}
+
+impl TraceOptions {
+    pub fn new() -> Self {
+	TraceOptions {
+	    path: String::new(),
+	    start: None,
+	    stop: None,
+	    doctor: false,
+	}
+    }
+}
+
+// Writes the state before each instruction in the Gameboy Doctor format
+pub struct Tracer {
+    writer: BufWriter<File>,
+    start: Option<TracePoint>,
+    stop: Option<TracePoint>,
+    active: bool,
+    stopped: bool,
+}
+
+impl Tracer {
+    pub fn new(options: &TraceOptions) -> Result<Tracer, &'static str> {
+	let file = match File::create(&options.path) {
+	    Ok(file) => file,
+	    Err(_) => return Err("Couldn't create the trace file"),
+	};
+
+	Ok(Tracer {
+	    writer: BufWriter::with_capacity(TRACE_BUFFER_SIZE, file),
+	    start: options.start.clone(),
+	    stop: options.stop.clone(),
+	    active: false,
+	    stopped: false,
+	})
+    }
+
+    // Called before every step, halted steps and interrupt dispatches aren't logged
+    pub fn trace(&mut self, gameboy: &GameBoy) -> Result<(), &'static str> {
+	if self.stopped {
+	    return Ok(());
+	}
+	if !self.active {
+	    match &self.start {
+		Some(start) if !start.reached(gameboy) => return Ok(()),
+		_ => {
+		    self.active = true;
+		    self.start = None;
+		}
+	    }
+	}
+	if self.stop.as_ref().is_some_and(|stop| stop.reached(gameboy)) {
+	    self.stopped = true;
+	    return Ok(());
+	}
+	if !gameboy.next_step_is_instruction() {
+	    return Ok(());
+	}
+
+	match write_line(&mut self.writer, gameboy) {
+	    Ok(()) => Ok(()),
+	    Err(_) => Err("Couldn't write the trace file"),
+	}
+    }
+
+    pub fn finish(mut self) -> Result<(), &'static str> {
+	match self.writer.flush() {
+	    Ok(()) => Ok(()),
+	    Err(_) => Err("Couldn't write the trace file"),
+	}
+    }
+}
+
+// A:00 F:11 B:22 C:33 D:44 E:55 H:66 L:77 SP:8888 PC:9999 PCMEM:AA,BB,CC,DD
+pub fn write_line<W: Write>(writer: &mut W, gameboy: &GameBoy) -> std::io::Result<()> {
+    let registers = gameboy.registers();
+    let pc = registers.pc;
+    let memory = |offset: u16| gameboy.read_memory(pc.wrapping_add(offset));
+
+    writeln!(
+	writer,
+	"A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+	registers.a,
+	registers.f,
+	registers.b,
+	registers.c,
+	registers.d,
+	registers.e,
+	registers.h,
+	registers.l,
+	registers.sp,
+	pc,
+	memory(0),
+	memory(1),
+	memory(2),
+	memory(3)
+    )
+}
@@ -0,0 +1,181 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufRead, BufReader},
+};
+
+use colored::Colorize;
+use gb_core::{disassemble, Config, GameBoy, Model};
+
+use crate::trace;
+
+const USAGE: &str = "Usage: lb-emu trace-diff <rom_file> <reference_trace> [--model <model>] [--context <n>]";
+const DEFAULT_CONTEXT: usize = 8;
+// How long the CPU may stay halted before giving up, a second of emulated time
+const HALT_LIMIT: u64 = 4_194_304;
+
+// Runs the ROM next to a Gameboy Doctor reference trace, stopping at the first line that differs
+pub fn run(args: &[String]) -> Result<(), &'static str> {
+    let (rom, reference) = match args {
+	[rom, reference, ..] => (rom, reference),
+	_ => return Err(USAGE),
+    };
+
+    let mut model = Model::Dmg;
+    let mut context = DEFAULT_CONTEXT;
+    let mut options = args[2..].iter();
+    while let Some(option) = options.next() {
+	match (option.as_str(), options.next()) {
+	    ("--model", Some(value)) => model = Model::try_from(value.as_str())?,
+	    ("--context", Some(value)) => match value.parse() {
+		Ok(lines) => context = lines,
+		Err(_) => return Err("Invalid number of context lines"),
+	    },
+	    _ => return Err(USAGE),
+	}
+    }
+
+    let reference = match File::open(reference) {
+	Ok(file) => BufReader::new(file),
+	Err(_) => return Err("Couldn't open the reference trace"),
+    };
+    let mut gameboy = GameBoy::from_file(rom, &Config::new(model))?;
+    // Reference logs come with LY stubbed to 0x90
+    gameboy.set_doctor_mode(true);
+
+    // The lines that matched before the divergence, to show how execution got there
+    let mut history: VecDeque<String> = VecDeque::with_capacity(context + 1);
+    // Kept whatever the context, the instruction it starts is the one that went wrong
+    let mut previous: Option<String> = None;
+    let mut line_number = 0u64;
+    let mut actual = Vec::new();
+
+    for expected in reference.lines() {
+	let expected = match expected {
+	    Ok(line) => line,
+	    Err(_) => return Err("Couldn't read the reference trace"),
+	};
+	if expected.trim().is_empty() {
+	    continue;
+	}
+	line_number += 1;
+
+	let halted_at = gameboy.cycles();
+	while !gameboy.next_step_is_instruction() {
+	    let stuck = if let Some((address, opcode)) = gameboy.invalid_opcode() {
+		Some(format!("The CPU hung on opcode ${:02X} at ${:04X} at line {}", opcode, address, line_number))
+	    } else if gameboy.read_memory(0xFFFF) & 0x1F == 0 {
+		Some(format!("The CPU halted with no interrupt enabled at line {}", line_number))
+	    } else if gameboy.cycles() - halted_at > HALT_LIMIT {
+		Some(format!("The CPU stayed halted for a second at line {}", line_number))
+	    } else {
+		None
+	    };
+	    if let Some(message) = stuck {
+		println!("{}", message.red().bold());
+		show_history(&history);
+		return Ok(());
+	    }
+	    gameboy.step();
+	}
+
+	actual.clear();
+	if trace::write_line(&mut actual, &gameboy).is_err() {
+	    return Err("Couldn't format the trace line");
+	}
+	let actual = String::from_utf8_lossy(&actual).trim_end().to_string();
+
+	if !same_state(&actual, &expected) {
+	    report(line_number, &history, previous.as_deref(), &actual, &expected);
+	    return Ok(());
+	}
+
+	if context > 0 {
+	    if history.len() == context {
+		history.pop_front();
+	    }
+	    history.push_back(actual.clone());
+	}
+	previous = Some(actual);
+	gameboy.step();
+    }
+
+    println!("{}", format!("No divergence in {} lines", line_number).green().bold());
+    Ok(())
+}
+
+// Compares the fields, so spacing and letter case of the reference don't matter
+fn same_state(actual: &str, expected: &str) -> bool {
+    let actual = fields(actual);
+    let expected = fields(expected);
+    expected.iter().all(|(name, value)| actual.iter().any(|(other, other_value)| other == name && other_value == value))
+}
+
+fn fields(line: &str) -> Vec<(String, String)> {
+    line.split_whitespace()
+	.filter_map(|field| field.split_once(':'))
+	.map(|(name, value)| (name.to_uppercase(), value.to_uppercase()))
+	.collect()
+}
+
+fn field<'a>(fields: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    fields.iter().find(|(field, _)| field == name).map(|(_, value)| value.as_str())
+}
+
+fn report(line_number: u64, history: &VecDeque<String>, previous: Option<&str>, actual: &str, expected: &str) {
+    println!("{}", format!("Divergence at line {}", line_number).red().bold());
+    show_history(history);
+
+    println!("{} {}", "expected".green(), expected);
+    println!("{} {}", "actual  ".red(), actual);
+
+    let actual_fields = fields(actual);
+    let expected_fields = fields(expected);
+    for (name, value) in &expected_fields {
+	let ours = field(&actual_fields, name).unwrap_or("missing");
+	if ours == value {
+	    continue;
+	}
+	match name.as_str() {
+	    "F" => println!("  F: expected {} ({}), got {} ({})", value, flags(value), ours, flags(ours)),
+	    _ => println!("  {}: expected {}, got {}", name, value, ours),
+	}
+    }
+
+    // The state differs after the last instruction that matched, that's where the bug is
+    match previous {
+	Some(previous) => println!("{} {}", "Offending instruction:".bold(), disassemble_line(previous)),
+	None => println!("The very first line differs, the start state doesn't match the reference"),
+    }
+}
+
+fn show_history(history: &VecDeque<String>) {
+    for line in history {
+	println!("  {}  {}", line.dimmed(), disassemble_line(line));
+    }
+}
+
+// Decodes the instruction from the PC and PCMEM fields of a trace line
+fn disassemble_line(line: &str) -> String {
+    let fields = fields(line);
+    let pc = field(&fields, "PC").and_then(|pc| u16::from_str_radix(pc, 16).ok());
+    let memory: Vec<u8> = field(&fields, "PCMEM")
+	.map(|bytes| bytes.split(',').filter_map(|byte| u8::from_str_radix(byte, 16).ok()).collect())
+	.unwrap_or_default();
+
+    match pc {
+	Some(pc) if !memory.is_empty() => {
+	    let instruction = disassemble(pc, |address| memory.get(address.wrapping_sub(pc) as usize).copied().unwrap_or(0));
+	    format!("${:04X}: {}", pc, instruction)
+	}
+	_ => String::from("?"),
+    }
+}
+
+fn flags(value: &str) -> String {
+    let value = u8::from_str_radix(value, 16).unwrap_or(0);
+    [(7, 'Z'), (6, 'N'), (5, 'H'), (4, 'C')]
+	.iter()
+	.map(|(bit, name)| if value & 1 << bit != 0 { *name } else { '-' })
+	.collect()
+}
@@ -0,0 +1,159 @@
+use std::{fs, path::Path, time::Instant};
+
+use gb_core::{Button, GameBoy, Rewind, Speed, SpeedCommand, DEFAULT_SAMPLE_RATE};
+
+use crate::movie::MovieSession;
+use sdl2::{audio::AudioSpecDesired, event::Event, keyboard::Keycode, pixels::PixelFormatEnum};
+
+const SCALE: u32 = 3;
+// - and = halve and double the speed, between this and full speed
+const MIN_SPEED: f64 = 0.125;
+
+pub fn run(gameboy: &mut GameBoy, state_path: &Path, mut rewind: Rewind, mut movie: Option<MovieSession>, mut speed: Speed) -> Result<(), &'static str> {
+    let sdl_context = sdl2::init().map_err(|_| "Couldn't initialize SDL")?;
+    let video_subsystem = sdl_context.video().map_err(|_| "Couldn't initialize the SDL video subsystem")?;
+
+    let (width, height, _) = gameboy.screen();
+    let window = video_subsystem
+	.window("lb-emu", width as u32 * SCALE, height as u32 * SCALE)
+	.position_centered()
+	.build()
+	.map_err(|_| "Couldn't create the window")?;
+
+    let mut canvas = window.into_canvas().build().map_err(|_| "Couldn't create the window canvas")?;
+    let texture_creator = canvas.texture_creator();
+    let mut texture = texture_creator
+	.create_texture_streaming(PixelFormatEnum::RGB888, width as u32, height as u32)
+	.map_err(|_| "Couldn't create the screen texture")?;
+    let mut event_pump = sdl_context.event_pump().map_err(|_| "Couldn't read SDL events")?;
+
+    // The game still runs without sound if no audio device is available
+    let audio_spec = AudioSpecDesired {
+	freq: Some(DEFAULT_SAMPLE_RATE as i32),
+	channels: Some(2),
+	samples: Some(1024),
+    };
+    let audio_queue = sdl_context
+	.audio()
+	.and_then(|audio_subsystem| audio_subsystem.open_queue::<f32, _>(None, &audio_spec))
+	.ok();
+    if let Some(queue) = &audio_queue {
+	queue.resume();
+    }
+
+    let mut rewinding = false;
+    'running: loop {
+	let frame_start = Instant::now();
+
+	for event in event_pump.poll_iter() {
+	    match event {
+		Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => break 'running,
+		Event::KeyDown { keycode: Some(Keycode::F5), repeat: false, .. } => {
+		    fs::write(state_path, gameboy.save_state_file()).map_err(|_| "Couldn't write the save state")?;
+		}
+		Event::KeyDown { keycode: Some(Keycode::F7), .. } if movie.is_some() => {
+		    eprintln!("Save states can't be loaded during a movie");
+		}
+		// A missing or broken state is reported without quitting
+		Event::KeyDown { keycode: Some(Keycode::F7), repeat: false, .. } => {
+		    let result = fs::read(state_path)
+			.map_err(|_| "Couldn't read the save state file")
+			.and_then(|state| gameboy.load_state_file(&state));
+		    match result {
+			Ok(report) => crate::emulator::print_load_report(&report),
+			Err(msg) => eprintln!("{}", msg),
+		    }
+		}
+		Event::KeyDown { keycode: Some(Keycode::Tab), repeat: false, .. } => speed.command(SpeedCommand::HoldFastForward(true)),
+		Event::KeyUp { keycode: Some(Keycode::Tab), .. } => speed.command(SpeedCommand::HoldFastForward(false)),
+		Event::KeyDown { keycode: Some(Keycode::Backquote), repeat: false, .. } => speed.command(SpeedCommand::ToggleFastForward),
+		Event::KeyDown { keycode: Some(Keycode::P), repeat: false, .. } => speed.command(SpeedCommand::TogglePause),
+		Event::KeyDown { keycode: Some(Keycode::N), .. } => speed.command(SpeedCommand::FrameAdvance),
+		Event::KeyDown { keycode: Some(keycode @ (Keycode::Minus | Keycode::Equals)), repeat: false, .. } => {
+		    let current = speed.multiplier().filter(|_| !speed.is_fast_forwarding()).unwrap_or(1.0);
+		    let changed = if keycode == Keycode::Minus { current / 2.0 } else { current * 2.0 };
+		    speed.command(SpeedCommand::SetSpeed(changed.clamp(MIN_SPEED, 1.0)));
+		    println!("Speed {}%", speed.multiplier().unwrap_or(1.0) * 100.0);
+		}
+		Event::KeyDown { keycode: Some(Keycode::C), repeat: false, .. } => {
+		    gameboy.set_color_correction(!gameboy.color_correction());
+		    println!("Color correction {}", if gameboy.color_correction() { "on" } else { "off" });
+		}
+		Event::KeyDown { keycode: Some(Keycode::R), .. } => rewinding = true,
+		Event::KeyUp { keycode: Some(Keycode::R), .. } => rewinding = false,
+		// A played movie holds the buttons itself, pressing keys would change the run
+		Event::KeyDown { .. } | Event::KeyUp { .. } if movie.as_ref().is_some_and(|session| !session.is_recording()) => {}
+		Event::KeyDown { keycode: Some(keycode), repeat: false, .. } => {
+		    if let Some((player, button)) = key_button(keycode) {
+			gameboy.press_player(player, button);
+		    }
+		}
+		Event::KeyUp { keycode: Some(keycode), .. } => {
+		    if let Some((player, button)) = key_button(keycode) {
+			gameboy.release_player(player, button);
+		    }
+		}
+		_ => {}
+	    }
+	}
+
+	// Rewinding stops on the oldest state once the history runs out.
+	// While paused only a frame advance runs.
+	let running = speed.next_frame();
+	if running && rewinding {
+	    rewind.step_back(gameboy)?;
+	} else if running {
+	    // Once a played movie is over the keyboard takes over
+	    if movie.as_mut().is_some_and(|session| !session.frame(gameboy)) {
+		movie.take().unwrap().finish(gameboy)?;
+	    }
+	    rewind.push(gameboy);
+	    gameboy.step_frame();
+	}
+
+	// Loading a state leaves no samples behind, rewinding is silent
+	let samples = speed.audio(&gameboy.take_audio_samples());
+	if let Some(queue) = audio_queue.as_ref().filter(|_| running && !rewinding) {
+	    queue.queue_audio(&samples).map_err(|_| "Couldn't queue audio samples")?;
+	}
+
+	// RGB888 is stored as 32bit little endian words, same as the framebuffer
+	let (_, _, pixels) = gameboy.screen();
+	let bytes: Vec<u8> = pixels.iter().flat_map(|pixel| pixel.to_le_bytes()).collect();
+	texture.update(None, &bytes, width * 4).map_err(|_| "Couldn't update the screen texture")?;
+	canvas.copy(&texture, None, None).map_err(|_| "Couldn't draw the screen")?;
+	canvas.present();
+
+	if let Some(remaining) = speed.frame_duration().checked_sub(frame_start.elapsed()) {
+	    std::thread::sleep(remaining);
+	}
+    }
+
+    match movie {
+	Some(movie) => movie.finish(gameboy),
+	None => Ok(()),
+    }
+}
+
+// The second controller is only read by SGB games after MLT_REQ
+fn key_button(keycode: Keycode) -> Option<(usize, Button)> {
+    match keycode {
+	Keycode::Right => Some((0, Button::Right)),
+	Keycode::Left => Some((0, Button::Left)),
+	Keycode::Up => Some((0, Button::Up)),
+	Keycode::Down => Some((0, Button::Down)),
+	Keycode::X => Some((0, Button::A)),
+	Keycode::Z => Some((0, Button::B)),
+	Keycode::Backspace => Some((0, Button::Select)),
+	Keycode::Return => Some((0, Button::Start)),
+	Keycode::D => Some((1, Button::Right)),
+	Keycode::A => Some((1, Button::Left)),
+	Keycode::W => Some((1, Button::Up)),
+	Keycode::S => Some((1, Button::Down)),
+	Keycode::G => Some((1, Button::A)),
+	Keycode::F => Some((1, Button::B)),
+	Keycode::Q => Some((1, Button::Select)),
+	Keycode::E => Some((1, Button::Start)),
+	_ => None,
+    }
+}